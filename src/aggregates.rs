@@ -19,6 +19,52 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-pub fn avg() -> f64 {
+//! The aggregate functions, used to type check the aggregates and get their result type.
+
+/// A type whose average can be computed by `avg()`.
+pub trait Avg {
+    type Output;
+}
+
+/// A type which can be summed by `sum()`, with the type of the sum which is widened to avoid
+/// overflows.
+pub trait Sum {
+    type Output;
+}
+
+macro_rules! impl_aggregate {
+    ($trait:ident: $($typ:ty => $output:ty),*) => {
+        $(
+            impl $trait for $typ {
+                type Output = $output;
+            }
+        )*
+
+        impl<T: $trait> $trait for Option<T> {
+            type Output = Option<T::Output>;
+        }
+    };
+}
+
+impl_aggregate!(Avg: i8 => f64, i16 => f64, i32 => f64, i64 => f64, f32 => f64, f64 => f64);
+impl_aggregate!(Sum: i8 => i64, i16 => i64, i32 => i64, i64 => i64, f32 => f64, f64 => f64);
+
+pub fn avg<T: Avg>(_field: &T) -> T::Output {
+    unreachable!()
+}
+
+pub fn count<T>(_field: &T) -> i64 {
+    unreachable!()
+}
+
+pub fn max<T: PartialOrd>(_field: &T) -> T {
+    unreachable!()
+}
+
+pub fn min<T: PartialOrd>(_field: &T) -> T {
+    unreachable!()
+}
+
+pub fn sum<T: Sum>(_field: &T) -> T::Output {
     unreachable!()
 }
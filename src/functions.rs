@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) 2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! The functions evaluated by the database, used to type check their calls.
+
+#[cfg(feature = "chrono")]
+use chrono::{self, Local, NaiveDate, NaiveDateTime, Utc};
+
+/// A date type, which can be the result of `current_date()`.
+pub trait DateValue {}
+
+/// A date and time type, which can be the result of `now()`.
+pub trait DateTimeValue {}
+
+/// A date or date and time type, which can be truncated or shifted by an interval.
+pub trait Timestamp {}
+
+/// The result of an interval function like `days()`, which can only be added to or subtracted from
+/// a `Timestamp`.
+pub struct Interval;
+
+macro_rules! impl_function_trait {
+    ($trait:ident: $($typ:ty),*) => {
+        $(
+            #[cfg(feature = "chrono")]
+            impl $trait for $typ {}
+        )*
+
+        impl<T: $trait> $trait for Option<T> {}
+    };
+}
+
+impl_function_trait!(DateValue: chrono::Date<Local>, chrono::Date<Utc>, NaiveDate);
+impl_function_trait!(DateTimeValue: chrono::DateTime<Local>, chrono::DateTime<Utc>, NaiveDateTime);
+impl_function_trait!(Timestamp: chrono::Date<Local>, chrono::Date<Utc>, NaiveDate, chrono::DateTime<Local>,
+                     chrono::DateTime<Utc>, NaiveDateTime);
+
+pub fn current_date<T: DateValue>() -> T {
+    unreachable!()
+}
+
+pub fn now<T: DateTimeValue>() -> T {
+    unreachable!()
+}
+
+/// The type of `date + days(7)`.
+pub fn shift<T: Timestamp>(_date: T, _interval: Interval) -> T {
+    unreachable!()
+}
+
+pub fn years(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn months(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn days(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn hours(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn minutes(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn seconds(_count: i32) -> Interval {
+    unreachable!()
+}
+
+pub fn trunc_year<T: Timestamp>(_date: T) -> T {
+    unreachable!()
+}
+
+pub fn trunc_month<T: Timestamp>(_date: T) -> T {
+    unreachable!()
+}
+
+pub fn trunc_day<T: Timestamp>(_date: T) -> T {
+    unreachable!()
+}
+
+pub fn trunc_hour<T: DateTimeValue>(_date: T) -> T {
+    unreachable!()
+}
+
+pub fn trunc_minute<T: DateTimeValue>(_date: T) -> T {
+    unreachable!()
+}
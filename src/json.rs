@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) 2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! The `Json` type for the fields stored as JSON.
+
+#[cfg(all(feature = "json", feature = "postgres"))]
+use std::error::Error;
+use std::ops::{Deref, DerefMut};
+
+#[cfg(all(feature = "json", feature = "postgres"))]
+use postgres::types::{self, FromSql, IsNull, JSON, JSONB, ToSql, Type};
+#[cfg(all(feature = "json", feature = "rusqlite"))]
+use rusqlite;
+#[cfg(all(feature = "json", feature = "rusqlite"))]
+use rusqlite::types::{FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+#[cfg(all(feature = "json", any(feature = "postgres", feature = "rusqlite")))]
+use serde::Serialize;
+#[cfg(all(feature = "json", any(feature = "postgres", feature = "rusqlite")))]
+use serde::de::DeserializeOwned;
+#[cfg(all(feature = "json", any(feature = "postgres", feature = "rusqlite")))]
+use serde_json;
+
+/// A `Json` field contains a value serialized to JSON, which is stored as `JSONB` with PostgreSQL
+/// and as `TEXT` with SQLite.
+///
+/// Use `Json<serde_json::Value>` for a value without a fixed structure.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(all(feature = "json", feature = "postgres"))]
+impl<T: Serialize> ToSql for Json<T> {
+    fn to_sql(&self, ty: &Type, out: &mut Vec<u8>) -> Result<IsNull, Box<Error + Sync + Send>> {
+        if *ty == JSONB {
+            // The version of the JSONB format.
+            out.push(1);
+        }
+        serde_json::to_writer(out, &self.0)?;
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == JSON || *ty == JSONB
+    }
+
+    fn to_sql_checked(&self, ty: &Type, out: &mut Vec<u8>) -> Result<IsNull, Box<Error + Sync + Send>> {
+        types::__to_sql_checked(self, ty, out)
+    }
+}
+
+#[cfg(all(feature = "json", feature = "postgres"))]
+impl<T: DeserializeOwned> FromSql for Json<T> {
+    fn from_sql(ty: &Type, raw: &[u8]) -> Result<Self, Box<Error + Sync + Send>> {
+        let raw =
+            if *ty == JSONB {
+                match raw.split_first() {
+                    Some((&1, json)) => json,
+                    _ => return Err("unsupported JSONB format version".into()),
+                }
+            }
+            else {
+                raw
+            };
+        Ok(Json(serde_json::from_slice(raw)?))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == JSON || *ty == JSONB
+    }
+}
+
+#[cfg(all(feature = "json", feature = "rusqlite"))]
+impl<T: Serialize> rusqlite::types::ToSql for Json<T> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput> {
+        serde_json::to_string(&self.0)
+            .map(ToSqlOutput::from)
+            .map_err(|error| rusqlite::Error::ToSqlConversionFailure(Box::new(error)))
+    }
+}
+
+#[cfg(all(feature = "json", feature = "rusqlite"))]
+impl<T: DeserializeOwned> rusqlite::types::FromSql for Json<T> {
+    fn column_result(value: ValueRef) -> FromSqlResult<Self> {
+        value.as_str()
+            .and_then(|json| serde_json::from_str(json)
+                .map(Json)
+                .map_err(|error| FromSqlError::Other(Box::new(error))))
+    }
+}
@@ -27,25 +27,85 @@
 extern crate chrono;
 #[cfg(feature = "postgres")]
 extern crate postgres;
+#[cfg(feature = "regex")]
+extern crate regex;
 #[cfg(feature = "rusqlite")]
 extern crate rusqlite;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+
+use std::collections::HashMap;
 
 pub mod aggregates;
+pub mod functions;
+pub mod window_functions;
+mod json;
 mod methods;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod types;
 
-pub use types::{Date, DateTime, Time, ToTqlType};
+#[cfg(feature = "sqlite")]
+pub use sqlite::add_regexp_function;
+pub use json::Json;
+pub use types::{Date, DateTime, JsonText, Time, ToTqlType};
 use types::StdI32;
 pub use types::numbers::{i16, i32, i64, i8, u16, u32, u64, u8};
 
 /// The `ForeignKey` is optional.
 ///
 /// There is no value when the `join()` method is not called.
-pub type ForeignKey<T> = Option<T>;
+/// The value is boxed so that a table can refer to itself.
+/// A foreign key which can be NULL in the database is declared as an `Option<ForeignKey<T>>`.
+pub type ForeignKey<T> = Option<Box<T>>;
+
+/// A `ManyToMany` field contains the rows of another table associated via a junction table.
+///
+/// It is empty when the `with()` method is not called.
+/// The junction table is created with the table, unless an intermediate model is declared with
+/// `#[sql(through = "Table", source = "field", target = "field")]`.
+pub type ManyToMany<T> = Vec<T>;
 
 /// A `PrimaryKey` is a 4-byte integer.
 pub type PrimaryKey = StdI32;
 
+/// A `Cursor` is the position of a row in the rows sorted by the `sort()` method.
+///
+/// It is taken from a `Page` and given to the `after()` or `before()` methods to get the next rows
+/// without an offset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor<T> {
+    values: T,
+}
+
+impl<T> Cursor<T> {
+    #[doc(hidden)]
+    pub fn new(values: T) -> Self {
+        Cursor {
+            values,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn values(&self) -> &T {
+        &self.values
+    }
+}
+
+/// A `Page` contains the rows returned by the `paginate()`, `after()` or `before()` methods.
+#[derive(Debug)]
+pub struct Page<T, C> {
+    /// The position of the last fetched row, to get the next page in the same direction, or `None`
+    /// if the page is empty.
+    ///
+    /// For the `before()` method, this is the position of the first item since the rows are
+    /// fetched in reverse order.
+    pub cursor: Option<Cursor<C>>,
+    pub items: Vec<T>,
+}
+
 #[doc(hidden)]
 // Marker trait used for error reporting:
 // when a struct is used in a ForeignKey, but it is not annotated with #[derive(SqlTable)].
@@ -61,29 +121,205 @@ pub unsafe trait SqlTable {
     fn from_row(row: &::postgres::rows::Row) -> Self;
 
     #[cfg(feature = "postgres")]
-    fn from_related_row(row: &::postgres::rows::Row, delta: usize) -> Self;
+    fn from_related_row(row: &::postgres::rows::Row, delta: usize) -> Option<Self>;
 
     #[cfg(feature = "rusqlite")]
     fn from_row(row: &::rusqlite::Row) -> Self;
 
     #[cfg(feature = "rusqlite")]
-    fn from_related_row(row: &::rusqlite::Row, delta: StdI32) -> Self;
+    fn from_related_row(row: &::rusqlite::Row, delta: StdI32) -> Option<Self>;
+}
+
+#[doc(hidden)]
+// Marker trait used for error reporting:
+// when a struct is used in only(), but it is not annotated with #[derive(SqlRow)].
+pub unsafe trait SqlRow {
+    #[cfg(feature = "postgres")]
+    fn from_row(row: &::postgres::rows::Row) -> Self;
+
+    #[cfg(feature = "rusqlite")]
+    fn from_row(row: &::rusqlite::Row) -> Self;
+}
+
+/// A field referencing a row of another table: a `ForeignKey` or an `Option<ForeignKey>`.
+#[doc(hidden)]
+pub trait ForeignKeyField {
+    type Table: SqlTable;
+
+    /// Get the related row, which is missing when it is not joined or when the foreign key is NULL.
+    fn related_row(&mut self) -> Option<&mut Self::Table>;
+
+    /// Get the related row, filling the field with a default row if it is missing.
+    fn related_row_or_default(&mut self) -> &mut Self::Table;
+
+    fn set_related_row(&mut self, row: Option<Self::Table>);
+}
+
+impl<T: SqlTable> ForeignKeyField for ForeignKey<T> {
+    type Table = T;
+
+    fn related_row(&mut self) -> Option<&mut T> {
+        self.as_mut().map(|row| &mut **row)
+    }
+
+    fn related_row_or_default(&mut self) -> &mut T {
+        self.get_or_insert_with(|| Box::new(T::_tql_default()))
+    }
+
+    fn set_related_row(&mut self, row: Option<T>) {
+        *self = row.map(Box::new);
+    }
+}
+
+impl<T: SqlTable> ForeignKeyField for Option<ForeignKey<T>> {
+    type Table = T;
+
+    fn related_row(&mut self) -> Option<&mut T> {
+        self.as_mut().and_then(|foreign_key| foreign_key.related_row())
+    }
+
+    fn related_row_or_default(&mut self) -> &mut T {
+        self.get_or_insert_with(|| None).related_row_or_default()
+    }
+
+    fn set_related_row(&mut self, row: Option<T>) {
+        *self = row.map(|row| Some(Box::new(row)));
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[doc(hidden)]
+pub fn from_related_row<F: ForeignKeyField>(field: &mut F, row: &::postgres::rows::Row, delta: usize) -> usize
+{
+    field.set_related_row(F::Table::from_related_row(row, delta));
+    F::Table::FIELD_COUNT
 }
 
+/// Fill the `ForeignKey` selected by `get_field` in the `parent` joined table.
+/// The related columns are skipped when there is no parent, which happens for a left join without
+/// a match.
 #[cfg(feature = "postgres")]
 #[doc(hidden)]
-pub fn from_related_row<T: SqlTable>(field: &mut Option<T>, row: &::postgres::rows::Row, delta: usize) -> usize
+pub fn from_nested_related_row<P, F, G>(parent: Option<&mut P>, get_field: G, row: &::postgres::rows::Row, delta: usize)
+    -> usize
+where F: ForeignKeyField,
+      G: FnOnce(&mut P) -> &mut F,
+{
+    match parent {
+        Some(parent) => from_related_row(get_field(parent), row, delta),
+        None => F::Table::FIELD_COUNT,
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+#[doc(hidden)]
+pub fn from_related_row<F: ForeignKeyField>(field: &mut F, row: &::rusqlite::Row, delta: StdI32) -> StdI32
 {
-    *field = Some(T::from_related_row(row, delta));
-    T::FIELD_COUNT
+    field.set_related_row(F::Table::from_related_row(row, delta));
+    F::Table::FIELD_COUNT
 }
 
 #[cfg(feature = "rusqlite")]
 #[doc(hidden)]
-pub fn from_related_row<T: SqlTable>(field: &mut Option<T>, row: &::rusqlite::Row, delta: StdI32) -> StdI32
+pub fn from_nested_related_row<P, F, G>(parent: Option<&mut P>, get_field: G, row: &::rusqlite::Row, delta: StdI32)
+    -> StdI32
+where F: ForeignKeyField,
+      G: FnOnce(&mut P) -> &mut F,
+{
+    match parent {
+        Some(parent) => from_related_row(get_field(parent), row, delta),
+        None => F::Table::FIELD_COUNT,
+    }
+}
+
+/// Check if the `count` columns starting at `delta` are all NULL, which happens for the related
+/// row of a left join without a match.
+#[cfg(feature = "postgres")]
+#[doc(hidden)]
+pub fn is_null_row(row: &::postgres::rows::Row, delta: usize, count: usize) -> bool {
+    (delta..delta + count).all(|index| row.get_bytes(index).is_none())
+}
+
+#[cfg(feature = "rusqlite")]
+#[doc(hidden)]
+pub fn is_null_row(row: &::rusqlite::Row, delta: StdI32, count: StdI32) -> bool {
+    (delta..delta + count).all(|index|
+        match row.get_checked(index) {
+            Ok(::rusqlite::types::Value::Null) => true,
+            _ => false,
+        }
+    )
+}
+
+/// Add the `children` to the field selected by `get_children` of the parent having the primary key
+/// they come with.
+#[doc(hidden)]
+pub fn add_related_rows<P, C, K, F>(parents: &mut [P], children: Vec<(PrimaryKey, C)>, get_pk: K, get_children: F)
+where K: Fn(&P) -> PrimaryKey,
+      F: Fn(&mut P) -> &mut Vec<C>,
 {
-    *field = Some(T::from_related_row(row, delta));
-    T::FIELD_COUNT
+    let indexes: HashMap<PrimaryKey, usize> = parents.iter()
+        .enumerate()
+        .map(|(index, parent)| (get_pk(parent), index))
+        .collect();
+    for (pk, child) in children {
+        if let Some(&index) = indexes.get(&pk) {
+            get_children(&mut parents[index]).push(child);
+        }
+    }
+}
+
+/// Get the `value` of an aggregate or a window function, with the type returned by the `function`.
+#[doc(hidden)]
+pub fn aggregate_value<T, F: FnOnce() -> T>(_function: F, value: T) -> T {
+    value
+}
+
+/// The maximum number of parameters in an SQLite query, i.e. the default `SQLITE_MAX_VARIABLE_NUMBER`
+/// of the versions of SQLite older than 3.32.0.
+#[cfg(feature = "rusqlite")]
+#[doc(hidden)]
+pub const SQLITE_MAX_VARIABLES: usize = 999;
+
+/// Replace the parameters `$N` which are lists by as many parameters as there are elements in the
+/// list.
+/// `lists` contains the parameter numbers with the length of their list.
+#[cfg(feature = "rusqlite")]
+#[doc(hidden)]
+pub fn expand_list_params(sql: &str, lists: &[(usize, usize)]) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut in_string = false;
+    while let Some(character) = chars.next() {
+        if character == '\'' {
+            in_string = !in_string;
+        }
+        if character != '$' || in_string {
+            result.push(character);
+            continue;
+        }
+        let mut number = String::new();
+        while let Some(&digit) = chars.peek() {
+            if !digit.is_digit(10) {
+                break;
+            }
+            number.push(digit);
+            chars.next();
+        }
+        let list = number.parse().ok()
+            .and_then(|number: usize| lists.iter().find(|&&(param, _)| param == number));
+        if let Some(&(param, len)) = list {
+            let params: Vec<_> = (0..len)
+                .map(|index| format!("${}_{}", param, index))
+                .collect();
+            result.push_str(&params.join(", "));
+        }
+        else {
+            result.push('$');
+            result.push_str(&number);
+        }
+    }
+    result
 }
 
 // Stable implementation.
@@ -22,7 +22,14 @@
 //! These methods should not be used directly:
 //! they exist only for type checking.
 
-use types::{Date, DateTime, Time, TqlOption, TqlString};
+use types::{Date, DateTime, JsonText, Time, TqlArray, TqlJson, TqlOption, TqlString};
+
+impl<T> TqlArray<T> {
+    pub fn contains<U: ?Sized + PartialEq<T>>(&self, _value: &U) -> bool { false }
+    pub fn contains_all<U: PartialEq<T>>(&self, _values: &[U]) -> bool { false }
+    pub fn len(&self) -> usize { 0 }
+    pub fn overlaps<U: PartialEq<T>>(&self, _values: &[U]) -> bool { false }
+}
 
 impl Date {
     pub fn day(&self) -> i32 { 0 }
@@ -46,12 +53,19 @@ impl Time {
     pub fn second(&self) -> i32 { 0 }
 }
 
+impl TqlJson {
+    pub fn get<T: JsonText>(&self, _key: &str) -> T { unreachable!() }
+    pub fn has_key(&self, _key: &str) -> bool { false }
+}
+
 impl TqlString {
     pub fn contains(&self, _string: &str) -> bool { false }
     pub fn ends_with(&self, _string: &str) -> bool { false }
     pub fn iregex(&self, _string: &str) -> bool { false }
     pub fn len(&self) -> usize { 0 }
+    pub fn rank(&self, _terms: &str) -> f32 { 0.0 }
     pub fn regex(&self, _string: &str) -> bool { false }
+    pub fn search(&self, _terms: &str) -> bool { false }
     pub fn starts_with(&self, _string: &str) -> bool { false }
 }
 
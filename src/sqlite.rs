@@ -0,0 +1,48 @@
+/*
+ * Copyright (c) 2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Functions missing from SQLite.
+
+use regex::Regex;
+use rusqlite::{Connection, Error, Result};
+
+/// Add the `REGEXP` function, used by the `regex()` and `iregex()` methods, to the `connection`.
+///
+/// SQLite does not provide an implementation of this function, so it must be added to every
+/// connection on which these methods are called.
+pub fn add_regexp_function(connection: &Connection) -> Result<()> {
+    let mut last_regex: Option<(String, Regex)> = None;
+    connection.create_scalar_function("regexp", 2, true, move |context| {
+        let pattern: String = context.get(0)?;
+        let text: Option<String> = context.get(1)?;
+        // NOTE: the pattern is usually the same for every row, hence only the last one is compiled.
+        let same_pattern = last_regex.as_ref()
+            .map(|&(ref last_pattern, _)| *last_pattern == pattern)
+            .unwrap_or(false);
+        if !same_pattern {
+            let regex = Regex::new(&pattern)
+                .map_err(|error| Error::UserFunctionError(Box::new(error)))?;
+            last_regex = Some((pattern, regex));
+        }
+        let regex = &last_regex.as_ref().expect("compiled regex").1;
+        Ok(text.map(|text| regex.is_match(&text)))
+    })
+}
@@ -24,9 +24,13 @@
 
 #![allow(dead_code, non_camel_case_types)]
 
+use std::marker::PhantomData;
+
 #[cfg(feature = "chrono")]
 use chrono::{self, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
+use json::Json;
+
 type StdI8 = i8;
 
 pub mod numbers {
@@ -182,6 +186,30 @@ impl ToTqlType for NaiveTime {
     fn to_tql_type(&self) -> Self::Target { Time }
 }
 
+#[doc(hidden)]
+pub struct TqlArray<T>(PhantomData<T>);
+
+impl<T> ToTqlType for Vec<T> {
+    type Target = TqlArray<T>;
+    fn to_tql_type(&self) -> Self::Target { TqlArray(PhantomData) }
+}
+
+#[doc(hidden)]
+pub struct TqlJson;
+
+impl<T> ToTqlType for Json<T> {
+    type Target = TqlJson;
+    fn to_tql_type(&self) -> Self::Target { TqlJson }
+}
+
+/// A type to which the value of a key of a `Json` field, taken by the `get()` method, can be
+/// compared.
+pub trait JsonText {}
+
+impl<'a> JsonText for &'a str {}
+
+impl JsonText for String {}
+
 #[doc(hidden)]
 pub struct TqlOption;
 
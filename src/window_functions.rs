@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) 2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! The window functions, used to type check the annotations and get their result type.
+
+pub use aggregates::{avg, count, max, min, sum};
+
+pub fn dense_rank() -> i64 {
+    unreachable!()
+}
+
+/// The value is `None` for the rows without a previous row in the window.
+pub fn lag<T>(_field: &T) -> Option<T> {
+    unreachable!()
+}
+
+/// The value is `None` for the rows without a next row in the window.
+pub fn lead<T>(_field: &T) -> Option<T> {
+    unreachable!()
+}
+
+pub fn rank() -> i64 {
+    unreachable!()
+}
+
+pub fn row_number() -> i64 {
+    unreachable!()
+}
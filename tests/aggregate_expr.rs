@@ -119,4 +119,26 @@ fn test_aggregate() {
         .unwrap();
     assert_eq!(1, aggregates.len());
     assert_eq!(12.0, aggregates[0].average); // NOTE: round(12 / 1) = 12.
+
+    let aggregate = sql!(TableAggregateExpr.aggregate(count(field2), max(field2), min(field2), sum(field2))).unwrap();
+    let count: i64 = aggregate.field2_count;
+    let max: i32 = aggregate.field2_max;
+    let min: i32 = aggregate.field2_min;
+    let sum: i64 = aggregate.field2_sum;
+    assert_eq!(3, count);
+    assert_eq!(55, max);
+    assert_eq!(12, min);
+    assert_eq!(55 + 12 + 42, sum);
+
+    let mut aggregates = sql!(TableAggregateExpr
+        .values(field1)
+        .aggregate(total = sum(field2))
+        .filter(total > 50))
+        .unwrap();
+    assert_eq!(1, aggregates.len());
+    assert_eq!(55 + 42, aggregates.remove(0).total);
+
+    let aggregate = sql!(TableAggregateExpr.aggregate(double = sum(field2 * 2), shifted = avg(field2 - 1))).unwrap();
+    assert_eq!((55 + 12 + 42) * 2, aggregate.double);
+    assert_eq!((54.0 + 11.0 + 41.0) / 3.0, aggregate.shifted);
 }
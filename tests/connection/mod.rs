@@ -50,7 +50,9 @@ pub use rusqlite::{Connection, Error, Result};
 #[cfg(feature = "sqlite")]
 #[allow(dead_code)]
 pub fn get_connection() -> Connection {
-    Connection::open_in_memory().unwrap()
+    let connection = Connection::open_in_memory().unwrap();
+    ::tql::add_regexp_function(&connection).unwrap();
+    connection
 }
 
 #[cfg(feature = "postgres")]
@@ -23,6 +23,7 @@
 
 extern crate chrono;
 extern crate postgres;
+extern crate serde_json;
 extern crate tql;
 #[macro_use]
 extern crate tql_macros;
@@ -30,7 +31,8 @@ extern crate tql_macros;
 use chrono::DateTime;
 use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
 use chrono::offset::{Local, Utc};
-use tql::{ForeignKey, PrimaryKey};
+use serde_json::Value;
+use tql::{ForeignKey, Json, PrimaryKey};
 use tql_macros::to_sql;
 
 #[derive(SqlTable)]
@@ -50,6 +52,16 @@ struct RelatedTable {
     field1: String,
 }
 
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Category {
+    id: PrimaryKey,
+    name: String,
+    parent: Option<ForeignKey<Category>>,
+    #[sql(related = "Category.parent")]
+    children: Vec<Category>,
+}
+
 #[derive(SqlTable)]
 #[allow(dead_code)]
 struct Dates {
@@ -76,6 +88,22 @@ struct OtherTypes {
     int64: i64,
 }
 
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Product {
+    pk: PrimaryKey,
+    meta: Json<Value>,
+    optional_meta: Option<Json<Value>>,
+}
+
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Post {
+    pk: PrimaryKey,
+    tags: Vec<String>,
+    scores: Option<Vec<i32>>,
+}
+
 #[test]
 fn test_create() {
     assert_eq!(
@@ -86,6 +114,10 @@ fn test_create() {
         "CREATE TABLE RelatedTable (id SERIAL PRIMARY KEY NOT NULL, field1 CHARACTER VARYING NOT NULL)",
         to_sql!(RelatedTable.create())
     );
+    assert_eq!(
+        "CREATE TABLE Category (id SERIAL PRIMARY KEY NOT NULL, name CHARACTER VARYING NOT NULL, parent INTEGER REFERENCES Category(id))",
+        to_sql!(Category.create())
+    );
     assert_eq!(
         "CREATE TABLE Dates (pk SERIAL PRIMARY KEY NOT NULL, date1 TIMESTAMP NOT NULL, date2 TIMESTAMP WITH TIME ZONE NOT NULL, date3 TIMESTAMP WITH TIME ZONE NOT NULL, date4 DATE NOT NULL, date5 TIME NOT NULL)",
         to_sql!(Dates.create())
@@ -94,4 +126,12 @@ fn test_create() {
         "CREATE TABLE OtherTypes (pk SERIAL PRIMARY KEY NOT NULL, boolean BOOLEAN NOT NULL, bytestring BYTEA NOT NULL, float32 REAL NOT NULL, float64 DOUBLE PRECISION NOT NULL, int8 CHARACTER(1) NOT NULL, int16 SMALLINT NOT NULL, int32 INTEGER NOT NULL, int64 BIGINT NOT NULL)",
         to_sql!(OtherTypes.create())
     );
+    assert_eq!(
+        "CREATE TABLE Product (pk SERIAL PRIMARY KEY NOT NULL, meta JSONB NOT NULL, optional_meta JSONB)",
+        to_sql!(Product.create())
+    );
+    assert_eq!(
+        "CREATE TABLE Post (pk SERIAL PRIMARY KEY NOT NULL, tags CHARACTER VARYING[] NOT NULL, scores INTEGER[])",
+        to_sql!(Post.create())
+    );
 }
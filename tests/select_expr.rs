@@ -30,6 +30,7 @@ macro_rules! let_vec {
 use std::str::FromStr;
 
 extern crate chrono;
+extern crate serde_json;
 extern crate tql;
 #[macro_use]
 extern crate tql_macros;
@@ -42,7 +43,8 @@ backend_extern_crate!();
 
 use chrono::DateTime;
 use chrono::offset::Utc;
-use tql::{ForeignKey, PrimaryKey};
+use serde_json::Value;
+use tql::{ForeignKey, Json, ManyToMany, PrimaryKey};
 use tql_macros::sql;
 
 use connection::{get_connection, is_not_found};
@@ -64,6 +66,12 @@ struct RelatedTableSelectExpr {
     field1: i32,
 }
 
+#[derive(SqlRow)]
+struct TableSelectExprSummary {
+    id: PrimaryKey,
+    field2: i32,
+}
+
 #[derive(SqlTable)]
 struct Table1 {
     id: PrimaryKey,
@@ -100,7 +108,8 @@ struct Table5 {
     id: PrimaryKey,
     field1: i32,
     field2: i32,
-    //tables6: ManyToMany<Table6>,
+    #[sql(through = "Table5_Table6", source = "table5", target = "table6")]
+    tables6: ManyToMany<Table6>,
 }
 
 #[derive(SqlTable)]
@@ -108,7 +117,7 @@ struct Table6 {
     id: PrimaryKey,
     field1: i32,
     field2: i32,
-    //tables5: HasMany<Table5>,
+    tables5: ManyToMany<Table5>,
 }
 
 #[derive(SqlTable)]
@@ -117,6 +126,46 @@ struct Table5_Table6 {
     table6: ForeignKey<Table6>,
 }
 
+#[derive(SqlTable)]
+struct Table7 {
+    id: PrimaryKey,
+    amount: i32,
+    sender: ForeignKey<Table2>,
+    receiver: ForeignKey<Table2>,
+}
+
+#[derive(SqlTable)]
+struct CategorySelectExpr {
+    id: PrimaryKey,
+    name: String,
+    parent: Option<ForeignKey<CategorySelectExpr>>,
+    #[sql(related = "CategorySelectExpr.parent")]
+    children: Vec<CategorySelectExpr>,
+}
+
+#[derive(SqlTable)]
+struct ProductSelectExpr {
+    id: PrimaryKey,
+    name: String,
+    meta: Json<Value>,
+}
+
+#[cfg(feature = "postgres")]
+#[derive(SqlTable)]
+struct PostSelectExpr {
+    id: PrimaryKey,
+    title: String,
+    tags: Vec<String>,
+}
+
+#[derive(SqlTable)]
+struct ArticleSelectExpr {
+    id: PrimaryKey,
+    title: String,
+    #[sql(fulltext)]
+    body: String,
+}
+
 #[test]
 fn test_select() {
     let connection = get_connection();
@@ -125,12 +174,16 @@ fn test_select() {
         let _ = sql!(TableSelectExpr.drop());
         let _ = sql!(RelatedTableSelectExpr.drop());
         let _ = sql!(Table1.drop());
+        let _ = sql!(Table7.drop());
+        let _ = sql!(CategorySelectExpr.drop());
         let _ = sql!(Table2.drop());
         let _ = sql!(Table3.drop());
         let _ = sql!(Table4.drop());
         let _ = sql!(Table5.drop());
         let _ = sql!(Table6.drop());
         let _ = sql!(Table5_Table6.drop());
+        let _ = sql!(ArticleSelectExpr.drop());
+        let _ = sql!(ProductSelectExpr.drop());
     });
 
     let _ = sql!(RelatedTableSelectExpr.create());
@@ -142,6 +195,10 @@ fn test_select() {
     let _ = sql!(Table6.create());
     let _ = sql!(Table5_Table6.create());
     let _ = sql!(Table1.create());
+    let _ = sql!(Table7.create());
+    let _ = sql!(CategorySelectExpr.create());
+    let _ = sql!(ArticleSelectExpr.create());
+    let _ = sql!(ProductSelectExpr.create());
 
     let datetime: DateTime<Utc> = FromStr::from_str("2015-11-16T15:51:12-05:00").unwrap();
     let datetime2: DateTime<Utc> = FromStr::from_str("2013-11-15T15:51:12-05:00").unwrap();
@@ -305,6 +362,19 @@ fn test_select() {
     let_vec!(table1 = tables);
     assert_eq!(id4, table1.id);
 
+    let tables = sql!(TableSelectExpr.filter(datetime < now() - years(1))).unwrap();
+    assert_eq!(5, tables.len());
+
+    let mut tables = sql!(TableSelectExpr.filter(datetime > datetime2 + days(365))).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id4, table1.id);
+
+    let mut tables = sql!(TableSelectExpr.filter(trunc_year(datetime) == trunc_year(datetime2) + years(2))).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id4, table1.id);
+
     let mut tables = sql!(TableSelectExpr.filter(field1.contains("value1"))).unwrap();
     assert_eq!(1, tables.len());
     let_vec!(table1 = tables);
@@ -358,18 +428,61 @@ fn test_select() {
     assert_eq!(id4, table4.id);
     assert_eq!(id5, table5.id);
 
-    #[cfg(feature = "postgres")]
-    {
-        let mut tables = sql!(TableSelectExpr.filter(field1.regex("%3"))).unwrap();
-        assert_eq!(1, tables.len());
-        let_vec!(table1 = tables);
-        assert_eq!(id3, table1.id);
+    let ids = vec![id1, id3];
+    let mut tables = sql!(TableSelectExpr.filter(id.is_in(&ids))).unwrap();
+    assert_eq!(2, tables.len());
+    let_vec!(table1, table2 = tables);
+    assert_eq!(id1, table1.id);
+    assert_eq!(id3, table2.id);
 
-        let tables = sql!(TableSelectExpr.filter(field1.regex("%E3"))).unwrap();
-        assert_eq!(0, tables.len());
-    }
+    let mut tables = sql!(TableSelectExpr.filter(field2.not_in(&[55, 42, 12]) && field1 != "value5")).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id4, table1.id);
+
+    let ids: Vec<i32> = vec![];
+    let tables = sql!(TableSelectExpr.filter(id.is_in(ids))).unwrap();
+    assert_eq!(0, tables.len());
+
+    // The parameters following a list are numbered after the elements of the list.
+    let ids = vec![id1, id3, id4];
+    let value = "value3".to_string();
+    let mut tables = sql!(TableSelectExpr.filter(id.is_in(&ids) && field1 == value)).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id3, table1.id);
+
+    let mut tables = sql!(TableSelectExpr.filter(id.is_in(&ids) && field1 != value && id.not_in(&[id4]))).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id1, table1.id);
+
+    let mut tables = sql!(TableSelectExpr.filter(field2 * 2 + 10 < 100)).unwrap();
+    assert_eq!(3, tables.len());
+    let_vec!(table1, table2, table3 = tables);
+    assert_eq!(id2, table1.id);
+    assert_eq!(id3, table2.id);
+    assert_eq!(id4, table3.id);
+
+    let divisor = 11;
+    let mut tables = sql!(TableSelectExpr.filter(field2 % {divisor} == 0 && (field2 - 20) * 2 > 0)).unwrap();
+    assert_eq!(2, tables.len());
+    let_vec!(table1, table2 = tables);
+    assert_eq!(id1, table1.id);
+    assert_eq!(id4, table2.id);
+
+    let mut tables = sql!(TableSelectExpr.filter(field1.regex("3$"))).unwrap();
+    assert_eq!(1, tables.len());
+    let_vec!(table1 = tables);
+    assert_eq!(id3, table1.id);
 
-    let mut tables = sql!(TableSelectExpr.filter(field1.iregex("%E3"))).unwrap();
+    let tables = sql!(TableSelectExpr.filter(field1.regex("E3$"))).unwrap();
+    assert_eq!(0, tables.len());
+
+    let tables = sql!(TableSelectExpr.filter(field1.regex("^value[2-4]$"))).unwrap();
+    assert_eq!(vec![id2, id3, id4], tables.iter().map(|table| table.id).collect::<Vec<_>>());
+
+    let mut tables = sql!(TableSelectExpr.filter(field1.iregex("E3$"))).unwrap();
     assert_eq!(1, tables.len());
     let_vec!(table1 = tables);
     assert_eq!(id3, table1.id);
@@ -462,6 +575,34 @@ fn test_select() {
     assert_eq!(id5, table5.id);
     assert_eq!(related_field2.id, table5.related_field.unwrap().id);
 
+    let mut tables = sql!(TableSelectExpr.left_join(related_field).filter(field2 < 50)).unwrap();
+    assert_eq!(3, tables.len());
+    let_vec!(table1, table2, table3 = tables);
+    assert_eq!(id2, table1.id);
+    assert_eq!(related_field.id, table1.related_field.unwrap().id);
+    assert_eq!(id3, table2.id);
+    assert_eq!(related_field2.id, table2.related_field.unwrap().id);
+    assert_eq!(id4, table3.id);
+    assert_eq!(related_field2.id, table3.related_field.unwrap().id);
+
+    let related_value = 24;
+    let mut tables = sql!(TableSelectExpr.join(related_field).filter(related_field.field1 == related_value)
+                          .sort(-field2)).unwrap();
+    assert_eq!(3, tables.len());
+    let_vec!(table1, table2, table3 = tables);
+    assert_eq!(id5, table1.id);
+    assert_eq!(id4, table2.id);
+    assert_eq!(id3, table3.id);
+
+    let mut tables = sql!(TableSelectExpr.join(related_field).sort(related_field.field1, field2)).unwrap();
+    assert_eq!(5, tables.len());
+    let_vec!(table1, table2, table3, table4, table5 = tables);
+    assert_eq!(id3, table1.id);
+    assert_eq!(id4, table2.id);
+    assert_eq!(id5, table3.id);
+    assert_eq!(id2, table4.id);
+    assert_eq!(id1, table5.id);
+
     let mut tables = sql!(TableSelectExpr.all()[..2]).unwrap();
     assert_eq!(2, tables.len());
     let_vec!(table1, table2 = tables);
@@ -532,6 +673,75 @@ fn test_select() {
     let table = sql!(TableSelectExpr[i64::from(-index)]).unwrap();
     assert_eq!(id3, table.id);
 
+    let mut values = sql!(TableSelectExpr.filter(field2 < 50).only(id, field1)).unwrap();
+    assert_eq!(3, values.len());
+    let_vec!(value1, value2, value3 = values);
+    assert_eq!((id2, "value2".to_string()), value1);
+    assert_eq!((id3, "value3".to_string()), value2);
+    assert_eq!((id4, "value4".to_string()), value3);
+
+    let field2_values = sql!(TableSelectExpr.only(field2).sort(field2)).unwrap();
+    assert_eq!(vec![12, 22, 42, 55, 134], field2_values);
+
+    let ids = sql!(TableSelectExpr.only(id).sort(optional_field.nulls_first(), id)).unwrap();
+    assert_eq!(vec![id1, id2, id3, id5, id4], ids);
+    let ids = sql!(TableSelectExpr.only(id).sort(-optional_field.nulls_last(), id)).unwrap();
+    assert_eq!(vec![id4, id1, id2, id3, id5], ids);
+    let field1_values = sql!(TableSelectExpr.filter(field2 < 50).only(field1).sort(field1.len(), -field2)).unwrap();
+    assert_eq!(vec!["value2".to_string(), "value4".to_string(), "value3".to_string()], field1_values);
+
+    let page = sql!(TableSelectExpr.sort(-field2, id).paginate()[..2]).unwrap();
+    assert_eq!(vec![id5, id1], page.items.iter().map(|table| table.id).collect::<Vec<_>>());
+    let cursor = page.cursor.unwrap();
+    let page = sql!(TableSelectExpr.sort(-field2, id).after(cursor)[..2]).unwrap();
+    assert_eq!(vec![id2, id4], page.items.iter().map(|table| table.id).collect::<Vec<_>>());
+    let cursor = page.cursor.unwrap();
+    let page = sql!(TableSelectExpr.sort(-field2, id).after(cursor)[..2]).unwrap();
+    assert_eq!(vec![id3], page.items.iter().map(|table| table.id).collect::<Vec<_>>());
+    let cursor = page.cursor.unwrap();
+    let page = sql!(TableSelectExpr.sort(-field2, id).before(cursor)[..2]).unwrap();
+    assert_eq!(vec![id2, id4], page.items.iter().map(|table| table.id).collect::<Vec<_>>());
+    let cursor = page.cursor.unwrap();
+    let page = sql!(TableSelectExpr.sort(-field2, id).before(cursor)[..2]).unwrap();
+    assert_eq!(vec![id5, id1], page.items.iter().map(|table| table.id).collect::<Vec<_>>());
+    let page = sql!(TableSelectExpr.filter(field2 > 1000).sort(id).paginate()).unwrap();
+    assert!(page.items.is_empty());
+    assert_eq!(None, page.cursor);
+
+    let summary = sql!(TableSelectExpr.filter(field1 == "value5").only::<TableSelectExprSummary>().get()).unwrap();
+    assert_eq!(id5, summary.id);
+    assert_eq!(134, summary.field2);
+
+    let datetimes = sql!(TableSelectExpr.filter(field2 < 100).distinct().only(datetime).sort(datetime)).unwrap();
+    assert_eq!(vec![datetime2, datetime], datetimes);
+
+    let field2_values = sql!(TableSelectExpr.filter(field2 < 20).only(field2)
+        .union(TableSelectExpr.filter(field2 > 100).only(field2)).sort(-field2)).unwrap();
+    assert_eq!(vec![134, 12], field2_values);
+
+    let field2_values = sql!(TableSelectExpr.filter(field2 < 50).only(field2)
+        .intersect(TableSelectExpr.filter(field2 > 20).only(field2)).sort(field2)).unwrap();
+    assert_eq!(vec![22, 42], field2_values);
+
+    let tables = sql!(TableSelectExpr.except(TableSelectExpr.filter(field2 < 100)).sort(id)[..1]).unwrap();
+    assert_eq!(1, tables.len());
+    assert_eq!(id5, tables[0].id);
+
+    let count = sql!(TableSelectExpr.filter(field2 < 50).count()).unwrap();
+    assert_eq!(3, count);
+    let count = sql!(TableSelectExpr.filter(field2 < 100)[..2].count()).unwrap();
+    assert_eq!(2, count);
+    assert!(sql!(TableSelectExpr.filter(field1 == "value5").exists()).unwrap());
+    assert!(!sql!(TableSelectExpr.filter(field2 > 1000).exists()).unwrap());
+
+    let tables = sql!(TableSelectExpr.annotate(position = rank().over(order = -field2),
+        previous = lag(field2).over(order = field2)).sort(field2)).unwrap();
+    let positions: Vec<_> = tables.iter().map(|table| table.position).collect();
+    assert_eq!(vec![5, 4, 3, 2, 1], positions);
+    let previous: Vec<_> = tables.iter().map(|table| table.previous).collect();
+    assert_eq!(vec![None, Some(12), Some(22), Some(42), Some(55)], previous);
+    assert_eq!(id3, tables[0].item.id);
+
     let table2_id = sql!(Table2.insert(field1 = 24, field2 = 42)).unwrap();
     let related1 = sql!(Table2.get(table2_id)).unwrap();
     let table2_id = sql!(Table3.insert(field1 = 25, field2 = 43)).unwrap();
@@ -558,22 +768,191 @@ fn test_select() {
     assert_eq!(table_related3.field1, 26);
     assert_eq!(table_related3.field2, 44);
 
+    let sender_id = sql!(Table2.insert(field1 = 1, field2 = 2)).unwrap();
+    let sender = sql!(Table2.get(sender_id)).unwrap();
+    let receiver_id = sql!(Table2.insert(field1 = 3, field2 = 4)).unwrap();
+    let receiver = sql!(Table2.get(receiver_id)).unwrap();
+    let id7 = sql!(Table7.insert(amount = 100, sender = sender, receiver = receiver)).unwrap();
+    let transfer = sql!(Table7.get(id7).join(sender, receiver)).unwrap();
+    assert_eq!(transfer.amount, 100);
+    assert_eq!(transfer.sender.unwrap().field1, 1);
+    assert_eq!(transfer.receiver.unwrap().field1, 3);
+    let transfers = sql!(Table7.join(sender, receiver).filter(receiver.field2 == 4)).unwrap();
+    assert_eq!(transfers.len(), 1);
+    assert_eq!(transfers[0].id, id7);
+
+    let min_field2 = 3;
+    let tables = sql!(Table2.filter(id.is_in(Table2.filter(field2 > min_field2).only(id)))).unwrap();
+    assert!(tables.iter().all(|table| table.field2 > min_field2));
+    assert!(tables.iter().any(|table| table.id == receiver_id));
+    let tables = sql!(Table2.filter(id.not_in(Table2.filter(field2 > min_field2).only(id)))).unwrap();
+    assert!(tables.iter().any(|table| table.id == sender_id));
+    let transfers = sql!(Table7.filter(Exists(Table2.filter(field1 == 3)))).unwrap();
+    assert_eq!(transfers.len(), 1);
+    let transfers = sql!(Table7.filter(!Exists(Table2.filter(field1 == 3)))).unwrap();
+    assert_eq!(transfers.len(), 0);
+
+    let root_id = sql!(CategorySelectExpr.insert(name = "root")).unwrap();
+    let root = sql!(CategorySelectExpr.get(root_id)).unwrap();
+    let child_id = sql!(CategorySelectExpr.insert(name = "child", parent = root)).unwrap();
+    let categories = sql!(CategorySelectExpr.join(parent).filter(parent.name == "root")).unwrap();
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0].id, child_id);
+    assert_eq!(categories[0].name, "child");
+    let mut categories = sql!(CategorySelectExpr.left_join(parent).sort(name)).unwrap();
+    let_vec!(child, root = categories);
+    assert_eq!(child.parent.unwrap().unwrap().id, root_id);
+    assert!(root.parent.is_none());
+
+    let child = sql!(CategorySelectExpr.get(child_id)).unwrap();
+    let grandchild_id = sql!(CategorySelectExpr.insert(name = "grandchild", parent = child)).unwrap();
+    let grandchild = sql!(CategorySelectExpr.get(grandchild_id).join(parent.parent)).unwrap();
+    let child = grandchild.parent.unwrap().unwrap();
+    assert_eq!(child.name, "child");
+    assert_eq!(child.parent.unwrap().unwrap().name, "root");
+    let mut categories = sql!(CategorySelectExpr.left_join(parent.parent).sort(name)).unwrap();
+    let_vec!(child, grandchild, root = categories);
+    assert!(child.parent.unwrap().unwrap().parent.is_none());
+    assert_eq!(grandchild.parent.unwrap().unwrap().parent.unwrap().unwrap().id, root_id);
+    assert!(root.parent.is_none());
+
+    let root = sql!(CategorySelectExpr.get(root_id).with(children)).unwrap();
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(root.children[0].id, child_id);
+    let mut categories = sql!(CategorySelectExpr.with(children).sort(name)).unwrap();
+    let_vec!(child, grandchild, root = categories);
+    assert_eq!(child.children.len(), 1);
+    assert_eq!(child.children[0].name, "grandchild");
+    assert!(grandchild.children.is_empty());
+    assert_eq!(root.children[0].name, "child");
+
+    // More categories than the number of parameters allowed in an SQLite query.
+    for index in 0..1000 {
+        let name = format!("category{}", index);
+        sql!(CategorySelectExpr.insert(name = name)).unwrap();
+    }
+    let categories = sql!(CategorySelectExpr.with(children)).unwrap();
+    assert_eq!(categories.len(), 1003);
+    let root = categories.iter().find(|category| category.id == root_id).unwrap();
+    assert_eq!(root.children.len(), 1);
+    assert_eq!(root.children[0].name, "child");
+
     let table5_id = sql!(Table5.insert(field1 = 24, field2 = 42)).unwrap();
     let table6_id = sql!(Table6.insert(field1 = 24, field2 = 42)).unwrap();
     let table5 = Table5 {
         id: table5_id,
         field1: 24,
         field2: 42,
-        //tables6: ManyToMany::new(),
+        tables6: ManyToMany::new(),
     };
     let table6 = Table6 {
         id: table6_id,
         field1: 24,
         field2: 42,
-        //tables5: HasMany::new(),
+        tables5: ManyToMany::new(),
     };
     sql!(Table5_Table6.insert(table5 = table5, table6 = table6)).unwrap();
-    //table5.tables6.add(table6);
-    //table5.tables6.all();
     sql!(Table5_Table6.all().join(table5));
+
+    let table5 = sql!(Table5.get(table5_id).with(tables6)).unwrap();
+    assert_eq!(table5.tables6.len(), 1);
+    assert_eq!(table5.tables6[0].id, table6_id);
+
+    let tables5 = sql!(Table5.filter(tables6.any(field1 == 24))).unwrap();
+    assert_eq!(tables5.len(), 1);
+    assert_eq!(tables5[0].id, table5_id);
+
+    let tables5 = sql!(Table5.filter(tables6.any(field1 == 42))).unwrap();
+    assert!(tables5.is_empty());
+
+    let table6 = sql!(Table6.get(table6_id)).unwrap();
+    table6.add_tables5(&connection, &table5).unwrap();
+    let table6 = sql!(Table6.get(table6_id).with(tables5)).unwrap();
+    assert_eq!(table6.tables5.len(), 1);
+    assert_eq!(table6.tables5[0].id, table5_id);
+
+    let tables6 = sql!(Table6.filter(tables5.any(field2 == 42) && field1 == 24)).unwrap();
+    assert_eq!(tables6.len(), 1);
+
+    table6.remove_tables5(&connection, &table5).unwrap();
+    let table6 = sql!(Table6.get(table6_id).with(tables5)).unwrap();
+    assert!(table6.tables5.is_empty());
+
+    sql!(ArticleSelectExpr.insert(title = "Rust", body = "Rust is a systems programming language")).unwrap();
+    sql!(ArticleSelectExpr.insert(title = "Python", body = "Python is a programming language")).unwrap();
+    sql!(ArticleSelectExpr.insert(title = "Pasta", body = "Boil the pasta for ten minutes")).unwrap();
+
+    let articles = sql!(ArticleSelectExpr.filter(body.search("programming")).sort(title)).unwrap();
+    assert_eq!(articles.len(), 2);
+    assert_eq!(articles[0].title, "Python");
+    assert_eq!(articles[1].title, "Rust");
+
+    let terms = "pasta";
+    let articles = sql!(ArticleSelectExpr.filter(body.search(terms))).unwrap();
+    assert_eq!(articles.len(), 1);
+    assert_eq!(articles[0].title, "Pasta");
+
+    let articles = sql!(ArticleSelectExpr.filter(body.search("language")).sort(-body.rank("rust language")))
+        .unwrap();
+    assert_eq!(articles.len(), 2);
+    assert_eq!(articles[0].title, "Rust");
+
+    let meta = Json(Value::from_str(r#"{"plan": "pro", "seats": 5}"#).unwrap());
+    sql!(ProductSelectExpr.insert(name = "Team", meta = meta)).unwrap();
+    let meta = Json(Value::from_str(r#"{"plan": "free", "trial": true}"#).unwrap());
+    sql!(ProductSelectExpr.insert(name = "Personal", meta = meta)).unwrap();
+
+    let products = sql!(ProductSelectExpr.filter(meta.get("plan") == "pro")).unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name, "Team");
+    assert_eq!(products[0].meta["seats"], 5);
+
+    let products = sql!(ProductSelectExpr.filter(meta.get("seats") == "5")).unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name, "Team");
+
+    let products = sql!(ProductSelectExpr.filter(meta.has_key("trial"))).unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name, "Personal");
+    assert_eq!(products[0].meta["trial"], true);
+}
+
+#[cfg(feature = "postgres")]
+#[test]
+fn test_select_array() {
+    let connection = get_connection();
+
+    let _teardown = TearDown::new(|| {
+        let _ = sql!(PostSelectExpr.drop());
+    });
+
+    let _ = sql!(PostSelectExpr.create());
+
+    let tags = vec!["rust".to_string(), "sql".to_string()];
+    sql!(PostSelectExpr.insert(title = "Compile-time SQL", tags = tags)).unwrap();
+    let tags = vec!["rust".to_string()];
+    sql!(PostSelectExpr.insert(title = "Rust macros", tags = tags)).unwrap();
+    let tags: Vec<String> = vec![];
+    sql!(PostSelectExpr.insert(title = "Untagged", tags = tags)).unwrap();
+
+    let posts = sql!(PostSelectExpr.filter(tags.contains("rust")).sort(title)).unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Compile-time SQL");
+    assert_eq!(posts[0].tags, vec!["rust", "sql"]);
+    assert_eq!(posts[1].title, "Rust macros");
+
+    let wanted = vec!["sql".to_string(), "rust".to_string()];
+    let posts = sql!(PostSelectExpr.filter(tags.contains_all(&wanted))).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Compile-time SQL");
+
+    let wanted = vec!["sql".to_string(), "python".to_string()];
+    let posts = sql!(PostSelectExpr.filter(tags.overlaps(&wanted))).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Compile-time SQL");
+
+    let posts = sql!(PostSelectExpr.filter(title.contains("macros") || tags.len() == 0).sort(title)).unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Rust macros");
+    assert_eq!(posts[1].title, "Untagged");
 }
@@ -68,3 +68,39 @@ fn test_aggregate() {
         to_sql!(Table.filter(field2 > 10).values(field1).aggregate(average = avg(field2)).filter(average < 20))
     );
 }
+
+#[test]
+fn test_aggregate_functions() {
+    assert_eq!(
+        "SELECT COUNT(field2), MAX(field2), MIN(field2) FROM Table",
+        to_sql!(Table.aggregate(count(field2), max(field2), min(field2)))
+    );
+    assert_eq!(
+        "SELECT SUM(field2) FROM Table GROUP BY field1",
+        to_sql!(Table.values(field1).aggregate(sum(field2)))
+    );
+    assert_eq!(
+        "SELECT SUM(field2) FROM Table GROUP BY field1 HAVING SUM(field2) > 20",
+        to_sql!(Table.values(field1).aggregate(total = sum(field2)).filter(total > 20))
+    );
+}
+
+#[test]
+fn test_aggregate_expression() {
+    assert_eq!(
+        "SELECT AVG(field2 - id) FROM Table",
+        to_sql!(Table.aggregate(difference = avg(field2 - id)))
+    );
+    assert_eq!(
+        "SELECT SUM(field2 * (id + 1)) FROM Table GROUP BY field1 HAVING SUM(field2 * (id + 1)) > 20",
+        to_sql!(Table.values(field1).aggregate(total = sum(field2 * (id + 1))).filter(total > 20))
+    );
+}
+
+#[test]
+fn test_aggregate_annotate() {
+    assert_eq!(
+        "SELECT SUM(field2), RANK() OVER (ORDER BY SUM(field2) DESC) FROM Table GROUP BY field1",
+        to_sql!(Table.values(field1).aggregate(total = sum(field2)).annotate(position = rank().over(order = -total)))
+    );
+}
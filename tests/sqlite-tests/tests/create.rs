@@ -23,6 +23,7 @@
 
 extern crate chrono;
 extern crate rusqlite;
+extern crate serde_json;
 extern crate tql;
 #[macro_use]
 extern crate tql_macros;
@@ -30,7 +31,8 @@ extern crate tql_macros;
 use chrono::DateTime;
 use chrono::naive::{NaiveDate, NaiveDateTime, NaiveTime};
 use chrono::offset::{Local, Utc};
-use tql::{ForeignKey, PrimaryKey};
+use serde_json::Value;
+use tql::{ForeignKey, Json, PrimaryKey};
 use tql_macros::to_sql;
 
 #[derive(SqlTable)]
@@ -50,6 +52,16 @@ struct RelatedTable {
     field1: String,
 }
 
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Category {
+    id: PrimaryKey,
+    name: String,
+    parent: Option<ForeignKey<Category>>,
+    #[sql(related = "Category.parent")]
+    children: Vec<Category>,
+}
+
 #[derive(SqlTable)]
 #[allow(dead_code)]
 struct Dates {
@@ -75,6 +87,14 @@ struct OtherTypes {
     int64: i64,
 }
 
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Product {
+    pk: PrimaryKey,
+    meta: Json<Value>,
+    optional_meta: Option<Json<Value>>,
+}
+
 #[test]
 fn test_create() {
     assert_eq!(
@@ -85,6 +105,10 @@ fn test_create() {
         "CREATE TABLE RelatedTable (id INTEGER PRIMARY KEY NOT NULL, field1 CHARACTER VARYING NOT NULL)",
         to_sql!(RelatedTable.create())
     );
+    assert_eq!(
+        "CREATE TABLE Category (id INTEGER PRIMARY KEY NOT NULL, name CHARACTER VARYING NOT NULL, parent INTEGER REFERENCES Category(id))",
+        to_sql!(Category.create())
+    );
     assert_eq!(
         "CREATE TABLE Dates (pk INTEGER PRIMARY KEY NOT NULL, date1 TIMESTAMP NOT NULL, date2 TIMESTAMP WITH TIME ZONE NOT NULL, date3 TIMESTAMP WITH TIME ZONE NOT NULL, date4 DATE NOT NULL, date5 TIME NOT NULL)",
         to_sql!(Dates.create())
@@ -93,4 +117,8 @@ fn test_create() {
         "CREATE TABLE OtherTypes (pk INTEGER PRIMARY KEY NOT NULL, boolean BOOLEAN NOT NULL, bytestring BYTEA NOT NULL, float64 DOUBLE PRECISION NOT NULL, int8 CHARACTER(1) NOT NULL, int16 SMALLINT NOT NULL, int32 INTEGER NOT NULL, int64 BIGINT NOT NULL)",
         to_sql!(OtherTypes.create())
     );
+    assert_eq!(
+        "CREATE TABLE Product (pk INTEGER PRIMARY KEY NOT NULL, meta TEXT NOT NULL, optional_meta TEXT)",
+        to_sql!(Product.create())
+    );
 }
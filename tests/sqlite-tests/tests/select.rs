@@ -23,13 +23,15 @@
 
 extern crate chrono;
 extern crate rusqlite;
+extern crate serde_json;
 extern crate tql;
 #[macro_use]
 extern crate tql_macros;
 
 use chrono::DateTime;
 use chrono::offset::Utc;
-use tql::{ForeignKey, PrimaryKey};
+use serde_json::Value;
+use tql::{ForeignKey, Json, ManyToMany, PrimaryKey};
 use tql_macros::to_sql;
 
 #[derive(SqlTable)]
@@ -41,6 +43,7 @@ struct Table {
     field3: Option<i32>,
     related_field: ForeignKey<RelatedTable>,
     date: DateTime<Utc>,
+    related_tables: ManyToMany<RelatedTable>,
 }
 
 #[derive(SqlTable)]
@@ -50,6 +53,38 @@ struct RelatedTable {
     field1: String,
 }
 
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Category {
+    id: PrimaryKey,
+    name: String,
+    parent: Option<ForeignKey<Category>>,
+}
+
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Transfer {
+    id: PrimaryKey,
+    amount: i32,
+    sender: ForeignKey<RelatedTable>,
+    receiver: ForeignKey<RelatedTable>,
+}
+
+#[derive(SqlTable)]
+#[allow(dead_code)]
+struct Product {
+    id: PrimaryKey,
+    name: String,
+    meta: Json<Value>,
+}
+
+#[derive(SqlRow)]
+#[allow(dead_code)]
+struct Summary {
+    id: PrimaryKey,
+    field2: i32,
+}
+
 const SELECT: &str = "SELECT Table.id, Table.field1, Table.field2, Table.field3, Table.date";
 
 #[test]
@@ -60,6 +95,18 @@ fn test_all() {
     );
 }
 
+#[test]
+fn test_distinct() {
+    assert_eq!(
+        "SELECT DISTINCT Table.id, Table.field1, Table.field2, Table.field3, Table.date FROM Table",
+        to_sql!(Table.distinct())
+    );
+    assert_eq!(
+        "SELECT DISTINCT Table.field1 FROM Table WHERE Table.field2 > 10",
+        to_sql!(Table.filter(field2 > 10).distinct().only(field1))
+    );
+}
+
 #[test]
 fn test_filter() {
     assert_eq!(
@@ -151,8 +198,132 @@ fn test_filter_method_call() {
         to_sql!(Table.filter(field1.len() == 6))
     );
     assert_eq!(
-        format!("{} FROM Table WHERE field1 LIKE '%E3'", SELECT),
-        to_sql!(Table.filter(field1.iregex(r"%E3")))
+        format!("{} FROM Table WHERE field1 REGEXP '3$'", SELECT),
+        to_sql!(Table.filter(field1.regex(r"3$")))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE field1 REGEXP '^v.*E[0-9]'", SELECT),
+        to_sql!(Table.filter(field1.regex(r"^v.*E[0-9]")))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE field1 REGEXP '(?i)' || 'E3$'", SELECT),
+        to_sql!(Table.filter(field1.iregex(r"E3$")))
+    );
+}
+
+#[test]
+fn test_filter_in() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.id IN ($1)", SELECT),
+        to_sql!(Table.filter(id.is_in(ids)))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.id IN ($1) AND Table.field2 NOT IN ($2)", SELECT),
+        to_sql!(Table.filter(id.is_in(&ids) && field2.not_in(values)))
+    );
+}
+
+#[test]
+fn test_filter_subquery() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 IN (SELECT Transfer.amount FROM Transfer WHERE Transfer.amount > 100)", SELECT),
+        to_sql!(Table.filter(field2.is_in(Transfer.filter(amount > 100).only(amount))))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field1 = $1 AND Table.field2 NOT IN (SELECT Transfer.amount FROM Transfer WHERE Transfer.amount > $2 LIMIT $3) LIMIT $4", SELECT),
+        to_sql!(Table.filter(field1 == value && field2.not_in(Transfer.filter(amount > min).only(amount)[..count]))[..limit])
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE EXISTS (SELECT RelatedTable.id, RelatedTable.field1 FROM RelatedTable WHERE RelatedTable.field1 = 'value')", SELECT),
+        to_sql!(Table.filter(Exists(RelatedTable.filter(field1 == "value"))))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE NOT EXISTS (SELECT RelatedTable.id, RelatedTable.field1 FROM RelatedTable WHERE RelatedTable.field1 = $1)", SELECT),
+        to_sql!(Table.filter(!Exists(RelatedTable.filter(field1 == value))))
+    );
+}
+
+#[test]
+fn test_filter_many_to_many() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.id IN (SELECT Table_related_tables_junction.source FROM Table_related_tables AS Table_related_tables_junction INNER JOIN RelatedTable AS Table_related_tables ON Table_related_tables_junction.target = Table_related_tables.id WHERE Table_related_tables.field1 = 'value')", SELECT),
+        to_sql!(Table.filter(related_tables.any(field1 == "value")))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 > $1 AND NOT Table.id IN (SELECT Table_related_tables_junction.source FROM Table_related_tables AS Table_related_tables_junction INNER JOIN RelatedTable AS Table_related_tables ON Table_related_tables_junction.target = Table_related_tables.id WHERE Table_related_tables.field1 = $2)", SELECT),
+        to_sql!(Table.filter(field2 > min && !related_tables.any(field1 == value)))
+    );
+}
+
+#[test]
+fn test_filter_field_reference() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 > Table.id", SELECT),
+        to_sql!(Table.filter(field2 > $id))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 < (Table.id + $1) * 2 AND Table.field1 = 'value'", SELECT),
+        to_sql!(Table.filter(field2 < ($id + value) * 2 && field1 == "value"))
+    );
+}
+
+#[test]
+fn test_filter_arithmetic() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 * Table.id > 100", SELECT),
+        to_sql!(Table.filter(field2 * id > 100))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE (Table.field2 + 1) * 2 < Table.id - $1", SELECT),
+        to_sql!(Table.filter((field2 + 1) * 2 < $id - value))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 % $1 = 0 AND Table.field1 = 'value'", SELECT),
+        to_sql!(Table.filter(field2 % {divisor} == 0 && field1 == "value"))
+    );
+}
+
+#[test]
+fn test_filter_date_function() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.date > CASE WHEN LENGTH(STRFTIME('%Y-%m-%dT%H:%M:%S', 'now')) = 10 \
+                 THEN DATE(STRFTIME('%Y-%m-%dT%H:%M:%S', 'now'), -7 || ' days') \
+                 ELSE STRFTIME('%Y-%m-%dT%H:%M:%S', STRFTIME('%Y-%m-%dT%H:%M:%S', 'now'), -7 || ' days') END", SELECT),
+        to_sql!(Table.filter(date > now() - days(7)))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.date < CASE WHEN LENGTH($1) = 10 THEN DATE($1, +$2 || ' hours') \
+                 ELSE STRFTIME('%Y-%m-%dT%H:%M:%S', $1, +$2 || ' hours') END", SELECT),
+        to_sql!(Table.filter(date < start + hours(count)))
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE CASE WHEN LENGTH(Table.date) = 10 THEN STRFTIME('%Y-%m-%d', Table.date) \
+                 ELSE STRFTIME('%Y-%m-%dT00:00:00', Table.date) END = DATE('now')", SELECT),
+        to_sql!(Table.filter(trunc_day(date) == current_date()))
+    );
+}
+
+#[test]
+fn test_filter_search() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.rowid IN (SELECT rowid FROM Table_field1_fts WHERE Table_field1_fts MATCH $1) AND Table.field2 > 10", SELECT),
+        to_sql!(Table.filter(field1.search(terms) && field2 > 10))
+    );
+    assert_eq!(
+        format!("{} FROM Table ORDER BY (SELECT -rank FROM Table_field1_fts WHERE Table_field1_fts MATCH 'rust' AND rowid = Table.rowid) DESC", SELECT),
+        to_sql!(Table.sort(-field1.rank("rust")))
+    );
+}
+
+#[test]
+fn test_filter_json() {
+    assert_eq!(
+        "SELECT Product.id, Product.name, Product.meta FROM Product WHERE CAST(JSON_EXTRACT(meta, '$.' || 'plan') AS TEXT) = 'pro'",
+        to_sql!(Product.filter(meta.get("plan") == "pro"))
+    );
+    assert_eq!(
+        "SELECT Product.id, Product.name, Product.meta FROM Product WHERE CAST(JSON_EXTRACT(meta, '$.' || 'plan') AS TEXT) = $1 AND JSON_TYPE(meta, '$.' || 'trial') IS NOT NULL",
+        to_sql!(Product.filter(meta.get("plan") == plan && meta.has_key("trial")))
     );
 }
 
@@ -180,6 +351,23 @@ fn test_filter_sort() {
     );
 }
 
+#[test]
+fn test_sort_expression() {
+    assert_eq!(
+        format!("{} FROM Table ORDER BY CAST(STRFTIME('%Y', date) AS INT) DESC, LENGTH(field1)", SELECT),
+        to_sql!(Table.sort(-date.year(), field1.len()))
+    );
+    assert_eq!(
+        format!("{} FROM Table ORDER BY field3 IS NULL, field3 DESC, field3 IS NULL DESC, field3", SELECT),
+        to_sql!(Table.sort(-field3.nulls_last(), field3.nulls_first()))
+    );
+    assert_eq!(
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table INNER JOIN RelatedTable AS Table_related_field \
+                 ON Table.related_field = Table_related_field.id ORDER BY LENGTH(Table.field1) IS NULL, LENGTH(Table.field1)", SELECT),
+        to_sql!(Table.join(related_field).sort(field1.len().nulls_last()))
+    );
+}
+
 #[test]
 fn test_filter_sort_limit() {
     assert_eq!(
@@ -223,15 +411,94 @@ fn test_get() {
 #[test]
 fn test_join() {
     assert_eq!(
-        format!("{}, RelatedTable.id, RelatedTable.field1 FROM Table INNER JOIN RelatedTable ON Table.related_field = RelatedTable.id", SELECT),
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table INNER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id", SELECT),
         to_sql!(Table.join(related_field))
     );
     assert_eq!(
-        format!("{}, RelatedTable.id, RelatedTable.field1 FROM Table INNER JOIN RelatedTable ON Table.related_field = RelatedTable.id", SELECT),
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table INNER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id", SELECT),
         to_sql!(Table.all().join(related_field))
     );
 }
 
+#[test]
+fn test_join_related_fields() {
+    assert_eq!(
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table INNER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id \
+                 WHERE Table_related_field.field1 = 'value1'", SELECT),
+        to_sql!(Table.join(related_field).filter(related_field.field1 == "value1"))
+    );
+    assert_eq!(
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table INNER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id \
+                 ORDER BY Table_related_field.field1 DESC, Table.field2", SELECT),
+        to_sql!(Table.join(related_field).sort(-related_field.field1, field2))
+    );
+}
+
+#[test]
+fn test_left_join() {
+    assert_eq!(
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table LEFT OUTER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id", SELECT),
+        to_sql!(Table.left_join(related_field))
+    );
+    assert_eq!(
+        format!("{}, Table_related_field.id, Table_related_field.field1 FROM Table LEFT OUTER JOIN RelatedTable AS Table_related_field ON Table.related_field = Table_related_field.id \
+                 WHERE Table.field2 > 10", SELECT),
+        to_sql!(Table.filter(field2 > 10).left_join(related_field))
+    );
+}
+
+#[test]
+fn test_join_same_table() {
+    assert_eq!(
+        "SELECT Transfer.id, Transfer.amount, Transfer_sender.id, Transfer_sender.field1, Transfer_receiver.id, \
+         Transfer_receiver.field1 FROM Transfer \
+         INNER JOIN RelatedTable AS Transfer_sender ON Transfer.sender = Transfer_sender.id \
+         INNER JOIN RelatedTable AS Transfer_receiver ON Transfer.receiver = Transfer_receiver.id",
+        to_sql!(Transfer.join(sender, receiver))
+    );
+    assert_eq!(
+        "SELECT Transfer.id, Transfer.amount, Transfer_sender.id, Transfer_sender.field1, Transfer_receiver.id, \
+         Transfer_receiver.field1 FROM Transfer \
+         INNER JOIN RelatedTable AS Transfer_sender ON Transfer.sender = Transfer_sender.id \
+         INNER JOIN RelatedTable AS Transfer_receiver ON Transfer.receiver = Transfer_receiver.id \
+         WHERE Transfer_sender.field1 = 'value1' ORDER BY Transfer_receiver.field1",
+        to_sql!(Transfer.join(sender, receiver).filter(sender.field1 == "value1").sort(receiver.field1))
+    );
+}
+
+#[test]
+fn test_join_self() {
+    assert_eq!(
+        "SELECT Category.id, Category.name, Category_parent.id, Category_parent.name FROM Category \
+         INNER JOIN Category AS Category_parent ON Category.parent = Category_parent.id",
+        to_sql!(Category.join(parent))
+    );
+    assert_eq!(
+        "SELECT Category.id, Category.name, Category_parent.id, Category_parent.name FROM Category \
+         LEFT OUTER JOIN Category AS Category_parent ON Category.parent = Category_parent.id \
+         WHERE Category_parent.name = 'root' ORDER BY Category.name",
+        to_sql!(Category.left_join(parent).filter(parent.name == "root").sort(name))
+    );
+}
+
+#[test]
+fn test_join_nested() {
+    assert_eq!(
+        "SELECT Category.id, Category.name, Category_parent.id, Category_parent.name, Category_parent_parent.id, \
+         Category_parent_parent.name FROM Category \
+         INNER JOIN Category AS Category_parent ON Category.parent = Category_parent.id \
+         INNER JOIN Category AS Category_parent_parent ON Category_parent.parent = Category_parent_parent.id",
+        to_sql!(Category.join(parent.parent))
+    );
+    assert_eq!(
+        "SELECT Category.id, Category.name, Category_parent.id, Category_parent.name, Category_parent_parent.id, \
+         Category_parent_parent.name FROM Category \
+         INNER JOIN Category AS Category_parent ON Category.parent = Category_parent.id \
+         LEFT OUTER JOIN Category AS Category_parent_parent ON Category_parent.parent = Category_parent_parent.id",
+        to_sql!(Category.join(parent).left_join(parent.parent))
+    );
+}
+
 #[test]
 fn test_limit() {
     assert_eq!(
@@ -307,3 +574,95 @@ fn test_limit() {
         to_sql!(Table.all()[-index as i64])
     );
 }
+
+#[test]
+fn test_only() {
+    assert_eq!(
+        "SELECT Table.id, Table.field1 FROM Table",
+        to_sql!(Table.only(id, field1))
+    );
+    assert_eq!(
+        "SELECT Table.field1 FROM Table WHERE Table.field2 > 10 ORDER BY id DESC LIMIT 2",
+        to_sql!(Table.filter(field2 > 10).only(field1).sort(-id)[..2])
+    );
+    assert_eq!(
+        "SELECT Table.id, Table.field2 FROM Table",
+        to_sql!(Table.only::<Summary>())
+    );
+}
+
+#[test]
+fn test_union() {
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field1 = 'value' UNION {} FROM Table WHERE Table.field2 > $1 ORDER BY date DESC LIMIT 10", SELECT, SELECT),
+        to_sql!(Table.filter(field1 == "value").union(Table.filter(field2 > min)).sort(-date)[..10])
+    );
+    assert_eq!(
+        "SELECT Table.id, Table.field1 FROM Table WHERE Table.field2 > $1 UNION SELECT RelatedTable.id, RelatedTable.field1 FROM RelatedTable WHERE RelatedTable.field1 = $2 ORDER BY field1 LIMIT $3",
+        to_sql!(Table.filter(field2 > min).only(id, field1).union(RelatedTable.filter(field1 == value).only(id, field1)).sort(field1)[..limit])
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE Table.field2 > 10 INTERSECT {} FROM Table WHERE Table.field2 < $1 EXCEPT {} FROM Table WHERE Table.field1 = 'value' UNION {} FROM Table", SELECT, SELECT, SELECT, SELECT),
+        to_sql!(Table.filter(field2 > 10).intersect(Table.filter(field2 < max)).except(Table.filter(field1 == "value")).union(Table.all()))
+    );
+}
+
+#[test]
+fn test_count() {
+    assert_eq!(
+        "SELECT COUNT(*) FROM Table WHERE Table.field2 > $1",
+        to_sql!(Table.filter(field2 > min).count())
+    );
+    assert_eq!(
+        "SELECT COUNT(*) FROM (SELECT DISTINCT Table.field1 FROM Table WHERE Table.field2 > 10) AS Table_rows",
+        to_sql!(Table.filter(field2 > 10).only(field1).distinct().count())
+    );
+    assert_eq!(
+        format!("SELECT COUNT(*) FROM ({} FROM Table LIMIT 5) AS Table_rows", SELECT),
+        to_sql!(Table[..5].count())
+    );
+}
+
+#[test]
+fn test_exists() {
+    assert_eq!(
+        format!("SELECT EXISTS({} FROM Table WHERE Table.field1 = $1)", SELECT),
+        to_sql!(Table.filter(field1 == value).exists())
+    );
+}
+
+#[test]
+fn test_annotate() {
+    assert_eq!(
+        format!("{}, RANK() OVER (PARTITION BY field1 ORDER BY field2 DESC) FROM Table", SELECT),
+        to_sql!(Table.annotate(position = rank().over(partition = field1, order = -field2)))
+    );
+    assert_eq!(
+        format!("{}, LAG(field2, 2) OVER (ORDER BY date, id DESC), SUM(field2) OVER (PARTITION BY field1) FROM Table WHERE Table.field2 > $1", SELECT),
+        to_sql!(Table.filter(field2 > min).annotate(previous = lag(field2, 2).over(order = (date, -id)), total = sum(field2).over(partition = field1)))
+    );
+    assert_eq!(
+        "SELECT Table.id, Table.field2, ROW_NUMBER() OVER (ORDER BY field2) FROM Table",
+        to_sql!(Table.only(id, field2).annotate(number = row_number().over(order = field2)))
+    );
+}
+
+#[test]
+fn test_pagination() {
+    assert_eq!(
+        format!("{} FROM Table ORDER BY id LIMIT 10", SELECT),
+        to_sql!(Table.sort(id).paginate()[..10])
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE id > $1 ORDER BY id LIMIT 10", SELECT),
+        to_sql!(Table.sort(id).after(cursor)[..10])
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE (Table.field2 > 10) AND (field2, id) < ($1, $2) ORDER BY field2 DESC, id DESC LIMIT 10", SELECT),
+        to_sql!(Table.filter(field2 > 10).sort(-field2, -id).after(cursor)[..10])
+    );
+    assert_eq!(
+        format!("{} FROM Table WHERE (field2 < $1 OR field2 = $1 AND id > $2) ORDER BY field2 DESC, id LIMIT 10", SELECT),
+        to_sql!(Table.sort(field2, -id).before(cursor)[..10])
+    );
+}
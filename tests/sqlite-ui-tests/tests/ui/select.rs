@@ -46,6 +46,10 @@ struct Table {
 fn main() {
     let connection = get_connection();
 
-    sql!(Table.filter(field1.regex(r"%3")));
-    sql!(Table.filter(field1.regex(r"%E3")));
+    let values = vec!["a".to_string()];
+    sql!(Table.filter(field1.contains_all(&values)));
+    sql!(Table.filter(field1.overlaps(&values)));
+
+    sql!(Table.distinct_on(field1));
+    sql!(Table.all().union(Table.all()).sort(field1.nulls_first()));
 }
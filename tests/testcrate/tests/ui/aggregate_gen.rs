@@ -47,7 +47,7 @@ fn main() {
     let connection = get_connection();
     if let Ok(aggregate) = sql!(Table.aggregate(average = avg(field2))) {
         println!("{}", aggregate.averag);
-        //~^ ERROR no field `averag` on type `main::Aggregate`
+        //~^ ERROR no field `averag` on type `main::Aggregate<f64>`
         //~| did you mean `average`?
     }
 
@@ -55,6 +55,10 @@ fn main() {
     sql!(Table.values(i32_field).aggregate(average = avg(i32_field)).filter(avg < 20));
     //~^ ERROR no aggregate field named `avg` found
 
+    sql!(Table.aggregate(avg(i32_field - 1)));
+    //~^ ERROR the aggregate of an expression must be named
+    //~| HELP give it a name: `name = avg(...)`
+
     //sql!(Table.values(i32_field).aggregate(average = avg(i32_field)).filter(avrage < 20));
     // TODO: propose similar names.
 
@@ -155,4 +155,22 @@ fn main() {
     //to_sql!(Table.all().join(address, address)); // TODO: should span an error.
 
     sql!(Table.get(1));
+
+    sql!(Table.only(field1).union(Table.only(i32_field)));
+    //~^ ERROR the query combined with union() must select the same columns
+    //~| HELP use only() to select the same fields in both queries
+
+    sql!(Table.all().union(Table.filter(i32_field > 10).sort(field1)));
+    //~^ ERROR cannot sort a query combined with union()
+    //~| HELP call sort() after the combination to sort all the rows
+
+    sql!(Table.all().except(Table.filter(i32_field > 10)[..10]));
+    //~^ ERROR cannot limit a query combined with except()
+    //~| HELP index the combination to limit all the rows
+
+    sql!(Table.only(field1).intersect(Table.only(field1)).sort(i32_field));
+    //~^ ERROR cannot sort the combined rows by the field `i32_field` which is not selected
+
+    sql!(Table.all().union(Table.all()).sort(field1.len()));
+    //~^ ERROR cannot sort the combined rows by a method call
 }
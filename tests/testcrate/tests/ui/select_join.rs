@@ -32,7 +32,7 @@ mod connection;
 backend_extern_crate!();
 
 use connection::{Connection, get_connection};
-use tql::PrimaryKey;
+use tql::{ForeignKey, PrimaryKey};
 use tql_macros::sql;
 
 #[derive(SqlTable)]
@@ -47,9 +47,19 @@ struct RelatedTable {
     id: PrimaryKey,
 }
 
+#[derive(SqlTable)]
+struct OtherTable {
+    id: PrimaryKey,
+    related_field: ForeignKey<RelatedTable>,
+}
+
 fn main() {
     let connection = get_connection();
 
+    sql!(OtherTable.filter(related_field.id == 1));
+    //~^ ERROR cannot use the fields of `related_field` without joining it
+    //~| HELP call join(related_field) in this query
+
     sql!(Table.all().join(field1, i32_field));
     //~^ ERROR mismatched types:
     //~| expected `ForeignKey<_>`,
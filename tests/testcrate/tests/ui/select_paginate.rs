@@ -0,0 +1,76 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+
+//! Tests of the after(), before() and paginate() methods.
+
+#![feature(proc_macro_hygiene)]
+
+extern crate tql;
+#[macro_use]
+extern crate tql_macros;
+
+#[macro_use]
+mod connection;
+backend_extern_crate!();
+
+use tql::PrimaryKey;
+use tql_macros::sql;
+
+use connection::{Connection, get_connection};
+
+#[derive(SqlTable)]
+struct Table {
+    id: PrimaryKey,
+    field1: String,
+    i32_field: i32,
+    nullable_field: Option<i32>,
+}
+
+fn main() {
+    let connection = get_connection();
+    let cursor = sql!(Table.sort(id).paginate()).unwrap().cursor.unwrap();
+
+    sql!(Table.all().paginate());
+    //~^ ERROR cannot call the paginate() method on rows which are not sorted
+    //~| HELP sort the rows by unique fields with the sort() method
+
+    sql!(Table.sort(id).after());
+    //~^ ERROR this method takes 1 parameter but 0 parameters were supplied
+
+    sql!(Table.sort(id.nulls_last()).paginate());
+    //~^ ERROR cannot call the paginate() method on rows sorted by `id`
+
+    sql!(Table.sort(field1.len()).after(cursor));
+    //~^ ERROR cannot call the after() method on rows sorted by a method call
+
+    sql!(Table.only(field1, id).sort(id).paginate());
+    //~^ ERROR cannot call the paginate() method with the only() method
+
+    sql!(Table.annotate(number = row_number().over(order = id)).sort(id).before(cursor));
+    //~^ ERROR cannot call the before() method with the annotate() method
+
+    sql!(Table.sort(id).paginate()[2]);
+    //~^ ERROR cannot call the paginate() method when getting a single row
+
+    sql!(Table.sort(nullable_field, id).paginate());
+    //~^ ERROR cannot paginate the rows sorted by the nullable field `nullable_field`
+}
@@ -57,8 +57,8 @@ struct Table<'a> {
     //~^ ERROR use of unsupported type name `Option`
     vector: Vec,
     //~^ ERROR use of unsupported type name `Vec`
-    vector_i32: Vec<i32>,
-    //~^ ERROR use of unsupported type name `Vec<i32>`
+    vector_connection: Vec<Connection>,
+    //~^ ERROR use of unsupported type name `Vec<Connection>`
 }
 
 fn main() {
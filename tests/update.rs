@@ -89,3 +89,19 @@ fn test_update_operation() {
         to_sql!(Table.get(1).update(field2 += 10, field3 /= 3))
     );
 }
+
+#[test]
+fn test_update_field_reference() {
+    assert_eq!(
+        "UPDATE Table SET field2 = Table.field3 WHERE Table.id = 1",
+        to_sql!(Table.get(1).update(field2 = $field3))
+    );
+    assert_eq!(
+        "UPDATE Table SET field2 = Table.field3 * 2 + $1 WHERE Table.id = 1",
+        to_sql!(Table.get(1).update(field2 = $field3 * 2 + value))
+    );
+    assert_eq!(
+        "UPDATE Table SET field2 = field2 * (Table.field3 + 1) WHERE Table.id = 1",
+        to_sql!(Table.get(1).update(field2 *= $field3 + 1))
+    );
+}
@@ -193,3 +193,44 @@ fn test_update_operation() {
     assert_eq!(0, table.field2);
     assert_eq!(3, table.field3);
 }
+
+#[test]
+fn test_update_field_reference() {
+    let connection = LOCK.lock().unwrap();
+
+    let _teardown = TearDown::new(|| {
+        let _ = sql!(TableUpdateExpr.drop());
+        let _ = sql!(RelatedTable.drop());
+    });
+
+    let _ = sql!(RelatedTable.create());
+    let _ = sql!(TableUpdateExpr.create());
+
+    let id = sql!(RelatedTable.insert(field1 = "")).unwrap();
+    let related_field = sql!(RelatedTable.get(id)).unwrap();
+
+    let id = sql!(TableUpdateExpr.insert(field1 = "", field2 = 2, field3 = 5, related_field = related_field)).unwrap();
+
+    let num_updated = sql!(TableUpdateExpr.get(id).update(field2 = $field3)).unwrap();
+    assert_eq!(1, num_updated);
+
+    let table = sql!(TableUpdateExpr.get(id)).unwrap();
+    assert_eq!(5, table.field2);
+    assert_eq!(5, table.field3);
+
+    let num_updated = sql!(TableUpdateExpr.get(id).update(field2 *= $field3 - 3, field3 = $field3 + 1)).unwrap();
+    assert_eq!(1, num_updated);
+
+    let table = sql!(TableUpdateExpr.get(id)).unwrap();
+    assert_eq!(10, table.field2);
+    assert_eq!(6, table.field3);
+
+    let num_updated = sql!(TableUpdateExpr.filter(field2 > $field3 * 2).update(field1 = "updated")).unwrap();
+    assert_eq!(0, num_updated);
+
+    let num_updated = sql!(TableUpdateExpr.filter(field2 > $field3).update(field1 = "updated")).unwrap();
+    assert_eq!(1, num_updated);
+
+    let table = sql!(TableUpdateExpr.get(id)).unwrap();
+    assert_eq!("updated", table.field1);
+}
@@ -37,6 +37,8 @@ use ast::{
     AggregateFilterExpression,
     AggregateFilters,
     Expression,
+    FieldPath,
+    Operand,
     Query,
     WithSpan,
     first_token_span,
@@ -46,14 +48,16 @@ use plugin::new_ident;
 use state::aggregates_singleton;
 use super::{
     check_argument_count,
+    expr_to_field_path,
     path_expr_to_identifier,
     path_expr_to_string,
     propose_similar_name,
 };
 use super::filter::{binop_to_logical_operator, binop_to_relational_operator, is_logical_operator, is_relational_operator};
+use super::operand::{check_intervals, check_numeric_literal, field_expression_to_operand};
 
 /// Convert an `Expression` to an `Aggregate`.
-pub fn argument_to_aggregate(arg: &Expression) -> Result<Aggregate> {
+pub fn argument_to_aggregate(arg: &Expression, table_name: &str) -> Result<Aggregate> {
     let mut errors = vec![];
     let mut aggregate = Aggregate::default();
     let aggregates = aggregates_singleton();
@@ -80,26 +84,29 @@ pub fn argument_to_aggregate(arg: &Expression) -> Result<Aggregate> {
         }
 
         if check_argument_count(&call.args, 1, arg.span(), &mut errors) {
-            if let Expr::Path(ref path) = **call.args.first().expect("first argument").value() {
-                let path_ident = &path.path.segments.first().unwrap().into_value().ident;
-                aggregate.field = Some(path_ident.clone());
+            let argument = call.args.first().expect("first argument").into_value();
+            let operand = field_expression_to_operand(argument, table_name, &mut errors);
+            check_aggregate_argument(&operand, &mut errors);
 
-                if aggregate.result_name.is_none() {
-                    let result_name = aggregate.field.clone().expect("Aggregate identifier").to_string() + "_" +
-                        &aggregate.sql_function.to_lowercase();
+            if aggregate.result_name.is_none() {
+                if let Operand::Field(_, ref field) = operand {
+                    let result_name = field.to_string() + "_" + &aggregate.sql_function.to_lowercase();
                     let mut ident = new_ident(&result_name);
                     // NOTE: violate the hygiene by assigning a known context to this new
                     // identifier.
-                    ident.set_span(path_ident.span());
+                    ident.set_span(field.span());
                     aggregate.result_name = Some(ident);
                 }
                 else {
-                    aggregate.has_name_in_query = true;
+                    let mut error = Error::new("the aggregate of an expression must be named", argument.span());
+                    error.add_help(&format!("give it a name: `name = {}(...)`", aggregate.function));
+                    errors.push(error);
                 }
             }
             else {
-                // TODO: allow expressions (field2 / field1).
+                aggregate.has_name_in_query = true;
             }
+            aggregate.argument = Some(operand);
         }
     }
     else {
@@ -112,13 +119,38 @@ pub fn argument_to_aggregate(arg: &Expression) -> Result<Aggregate> {
     res(aggregate, errors)
 }
 
+/// Check that the argument of an aggregate function only contains fields and number literals.
+pub fn check_aggregate_argument(operand: &Operand, errors: &mut Vec<Error>) {
+    fn check_leaves(operand: &Operand, errors: &mut Vec<Error>) {
+        for leaf in operand.leaves() {
+            match *leaf {
+                Operand::Expression(ref expr) =>
+                    if let Expr::Lit(_) = *expr {
+                        check_numeric_literal(expr, errors);
+                    }
+                    else {
+                        errors.push(Error::new("expected a field or a number in the aggregate argument", expr.span()));
+                    },
+                Operand::Function(ref function) =>
+                    for argument in &function.arguments {
+                        check_leaves(argument, errors);
+                    },
+                Operand::Arithmetic(_, _, _) | Operand::Field(_, _) | Operand::Paren(_) => (),
+            }
+        }
+    }
+
+    check_intervals(operand, errors);
+    check_leaves(operand, errors);
+}
+
 /// Convert an `Expression` to a group `Ident`.
-pub fn argument_to_group(arg: &Expression) -> Result<Ident> {
+pub fn argument_to_group(arg: &Expression) -> Result<FieldPath> {
     let mut errors = vec![];
-    let mut group = Ident::new("__tql_dummy_ident", Span::call_site());
+    let mut group = FieldPath::new(Ident::new("__tql_dummy_ident", Span::call_site()));
 
-    if let Some(identifier) = path_expr_to_identifier(arg, &mut errors) {
-        group = identifier;
+    if let Some(field) = expr_to_field_path(arg, &mut errors) {
+        group = field;
     }
     else {
         errors.push(Error::new(
@@ -236,7 +268,7 @@ fn get_call_from_aggregate<'a>(arg: &'a Expression, aggregate: &mut Aggregate, e
 }
 
 /// Get the identifier in the group by clause to be able to check that they exist.
-pub fn get_values_idents(query: &Query) -> Vec<Ident> {
+pub fn get_values_idents(query: &Query) -> Vec<FieldPath> {
     let mut idents = vec![];
     if let Query::Aggregate { ref groups, ..} = *query {
         for group in groups {
@@ -246,19 +278,23 @@ pub fn get_values_idents(query: &Query) -> Vec<Ident> {
     idents
 }
 
-/// Get the aggregate function calls to typecheck the query.
-pub fn get_aggregate_calls(query: &Query) -> Vec<(String, Expr)> {
+/// Get the aggregate function calls with their argument and the value they are compared to, to
+/// typecheck the query.
+pub fn get_aggregate_calls(query: &Query) -> Vec<(String, Operand, Expr)> {
     if let Query::Aggregate { ref aggregate_filter, ..} = *query {
         return get_calls_from_aggregate_filter(aggregate_filter);
     }
     vec![]
 }
 
-fn get_calls_from_aggregate_filter(filter: &AggregateFilterExpression) -> Vec<(String, Expr)> {
+fn get_calls_from_aggregate_filter(filter: &AggregateFilterExpression) -> Vec<(String, Operand, Expr)> {
     let mut calls = vec![];
     match *filter {
-        AggregateFilterExpression::Filter(ref filter) =>
-            calls.push((filter.operand1.function.clone(), filter.operand2.clone())),
+        AggregateFilterExpression::Filter(ref filter) => {
+            if let Some(ref argument) = filter.operand1.argument {
+                calls.push((filter.operand1.function.clone(), argument.clone(), filter.operand2.clone()));
+            }
+        },
         AggregateFilterExpression::Filters(ref filters) => {
             calls.extend(get_calls_from_aggregate_filter(&filters.operand1));
             calls.extend(get_calls_from_aggregate_filter(&filters.operand2));
@@ -0,0 +1,245 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the annotate() method.
+
+use syn::{
+    Expr,
+    ExprCall,
+    ExprLit,
+    Lit,
+};
+use syn::spanned::Spanned;
+
+use ast::{
+    Aggregate,
+    Annotation,
+    Expression,
+    FieldPath,
+    Order,
+    OrderValue,
+    Query,
+};
+use error::{Error, Result, res};
+use state::aggregates_singleton;
+use string::plural_verb;
+use super::{expr_to_field_path, path_expr_to_identifier, path_expr_to_string, propose_similar_name};
+use super::aggregate::check_aggregate_argument;
+use super::operand::field_expression_to_operand;
+use super::sort::argument_to_order;
+
+/// The window functions which are not aggregate functions, with their SQL name.
+const WINDOW_FUNCTIONS: [(&str, &str); 5] = [
+    ("dense_rank", "DENSE_RANK"),
+    ("lag", "LAG"),
+    ("lead", "LEAD"),
+    ("rank", "RANK"),
+    ("row_number", "ROW_NUMBER"),
+];
+
+/// Convert an `Expression` to an `Annotation`.
+pub fn argument_to_annotation(arg: &Expression, table_name: &str) -> Result<Annotation> {
+    let mut errors = vec![];
+    let mut annotation = Annotation {
+        function: Aggregate::default(),
+        offset: None,
+        order: vec![],
+        partition: vec![],
+    };
+
+    // The result of the window function must be named, since it has no field to be named after.
+    let call =
+        if let Expr::Assign(ref assign) = *arg {
+            annotation.function.result_name = path_expr_to_identifier(&assign.left, &mut errors);
+            annotation.function.has_name_in_query = true;
+            &*assign.right
+        }
+        else {
+            let mut error = Error::new("the annotation must be named", arg.span());
+            error.add_help("give it a name: `name = rank().over(...)`");
+            errors.push(error);
+            arg
+        };
+
+    match *call {
+        Expr::MethodCall(ref over_call) if over_call.method == "over" => {
+            if let Expr::Call(ref function_call) = *over_call.receiver {
+                function_call_to_annotation(function_call, table_name, &mut annotation, &mut errors);
+            }
+            else {
+                errors.push(Error::new("expected a window function call", over_call.receiver.span()));
+            }
+            for window_arg in &over_call.args {
+                add_window_argument(window_arg, &mut annotation, &mut errors);
+            }
+        },
+        _ => {
+            let mut error = Error::new("expected a window function call", call.span());
+            error.add_help("call over() on the function: `rank().over(order = field)`");
+            errors.push(error);
+        },
+    }
+
+    res(annotation, errors)
+}
+
+/// Add the `partition = fields` or `order = fields` argument of the over() method to the
+/// `annotation`.
+fn add_window_argument(arg: &Expression, annotation: &mut Annotation, errors: &mut Vec<Error>) {
+    if let Expr::Assign(ref assign) = *arg {
+        if let Some(name) = path_expr_to_identifier(&assign.left, errors) {
+            let values: Vec<&Expression> =
+                if let Expr::Tuple(ref tuple) = *assign.right {
+                    tuple.elems.iter().collect()
+                }
+                else {
+                    vec![&assign.right]
+                };
+            match name.to_string().as_str() {
+                "order" =>
+                    for value in values {
+                        match argument_to_order(value) {
+                            Ok(order) => annotation.order.push(order),
+                            Err(errs) => errors.extend(errs),
+                        }
+                    },
+                "partition" =>
+                    for value in values {
+                        if let Some(field) = expr_to_field_path(value, errors) {
+                            annotation.partition.push(field);
+                        }
+                    },
+                _ => {
+                    let mut error = Error::new(&format!("unknown window parameter `{}`", name), name.span());
+                    if !propose_similar_name(&name.to_string(), ["order", "partition"].iter().cloned(), &mut error) {
+                        error.add_help("expected `partition` or `order`");
+                    }
+                    errors.push(error);
+                },
+            }
+        }
+    }
+    else {
+        errors.push(Error::new("expected `partition = fields` or `order = fields`", arg.span()));
+    }
+}
+
+/// Check that the `call` is a window function call and add it to the `annotation`.
+fn function_call_to_annotation(call: &ExprCall, table_name: &str, annotation: &mut Annotation,
+                               errors: &mut Vec<Error>)
+{
+    let identifier =
+        match path_expr_to_string(&call.func, errors) {
+            Some(identifier) => identifier,
+            None => return,
+        };
+    let aggregates = aggregates_singleton();
+    let window_function = WINDOW_FUNCTIONS.iter().find(|&&(name, _)| name == identifier);
+    let (sql_function, (min_count, max_count)) =
+        if let Some(&(_, sql_function)) = window_function {
+            let count =
+                if identifier == "lag" || identifier == "lead" {
+                    (1, 2)
+                }
+                else {
+                    (0, 0)
+                };
+            (sql_function.to_string(), count)
+        }
+        else if let Some(sql_function) = aggregates.get(&identifier) {
+            (sql_function.clone(), (1, 1))
+        }
+        else {
+            let mut error = Error::new_with_code(
+                &format!("unresolved name `{}`", identifier),
+                call.func.span(),
+                "E0425",
+            );
+            let names = WINDOW_FUNCTIONS.iter().map(|&(name, _)| name)
+                .chain(aggregates.keys().map(String::as_ref));
+            propose_similar_name(&identifier, names, &mut error);
+            errors.push(error);
+            return;
+        };
+    annotation.function.function = identifier;
+    annotation.function.sql_function = sql_function;
+
+    let count = call.args.len();
+    if count < min_count || count > max_count {
+        let expected =
+            if min_count == max_count {
+                format!("{} parameter{}", min_count, if min_count == 1 { "" } else { "s" })
+            }
+            else {
+                format!("{} or {} parameters", min_count, max_count)
+            };
+        errors.push(Error::new_with_code(
+            &format!("this function takes {} but {} parameter{} supplied", expected, count, plural_verb(count)),
+            call.span(),
+            "E0061",
+        ));
+        return;
+    }
+
+    let mut args = call.args.iter();
+    if let Some(argument) = args.next() {
+        let operand = field_expression_to_operand(argument, table_name, errors);
+        check_aggregate_argument(&operand, errors);
+        annotation.function.argument = Some(operand);
+    }
+    if let Some(offset) = args.next() {
+        if let Expr::Lit(ExprLit { lit: Lit::Int(_), .. }) = *offset {
+            annotation.offset = Some(offset.clone());
+        }
+        else {
+            errors.push(Error::new("expected an integer literal for the offset", offset.span()));
+        }
+    }
+}
+
+/// Get the identifiers in the window clauses to be able to check that they exist.
+/// In an aggregate query, the window can also be ordered by the result of an aggregate.
+pub fn get_annotation_idents(query: &Query) -> Vec<FieldPath> {
+    let (annotations, aggregates) =
+        match *query {
+            Query::Aggregate { ref aggregates, ref annotations, .. } => (annotations, &aggregates[..]),
+            Query::Select { ref annotations, .. } => (annotations, &[][..]),
+            _ => return vec![],
+        };
+    let is_aggregate = |field: &FieldPath|
+        field.foreign_key.is_none() &&
+            aggregates.iter().any(|aggregate| aggregate.result_name.as_ref() == Some(&field.field));
+    let mut idents = vec![];
+    for annotation in annotations {
+        idents.extend(annotation.partition.iter().cloned());
+        for order in &annotation.order {
+            match *order {
+                Order::Ascending(OrderValue::Field(ref field), _) | Order::Descending(OrderValue::Field(ref field), _)
+                    if !is_aggregate(field) => idents.push(field.clone()),
+                Order::Ascending(OrderValue::MethodCall(ref call), _) |
+                    Order::Descending(OrderValue::MethodCall(ref call), _) =>
+                    idents.push(FieldPath::new(call.object_name.clone())),
+                _ => (),
+            }
+        }
+    }
+    idents
+}
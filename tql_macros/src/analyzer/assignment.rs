@@ -32,16 +32,23 @@ use ast::{
     Assignment,
     AssignmentOperator,
     Expression,
+    Operand,
     WithSpan,
 };
 use error::{Error, Result, res};
 use plugin::number_literal;
+use super::operand::expression_to_operand;
 use super::path_expr_to_identifier;
 
 /// Convert an `Expression` to an `Assignment`.
-pub fn argument_to_assignment(arg: &Expression) -> Result<Assignment> {
-    fn assign_values(assignment: &mut Assignment, expr1: &Expression, expr2: &Expression, errors: &mut Vec<Error>) {
-        assignment.value = expr2.clone();
+pub fn argument_to_assignment(arg: &Expression, table_name: &str) -> Result<Assignment> {
+    fn assign_values(assignment: &mut Assignment, expr1: &Expression, expr2: &Expression, table_name: &str,
+        errors: &mut Vec<Error>)
+    {
+        match expression_to_operand(expr2, table_name) {
+            Ok(value) => assignment.value = value,
+            Err(operand_errors) => errors.extend(operand_errors),
+        }
         if let Some(identifier) = path_expr_to_identifier(expr1, errors) {
             assignment.identifier = Some(identifier);
         }
@@ -54,11 +61,11 @@ pub fn argument_to_assignment(arg: &Expression) -> Result<Assignment> {
             node: AssignmentOperator::Equal,
             span: arg.span(),
         },
-        value: number_literal(0),
+        value: Operand::Expression(number_literal(0)),
     };
     match *arg {
         Expr::Assign(ref oper) => {
-            assign_values(&mut assignment, &oper.left, &oper.right, &mut errors);
+            assign_values(&mut assignment, &oper.left, &oper.right, table_name, &mut errors);
         },
         Expr::AssignOp(ref oper) => {
             let (node, span) = binop_to_assignment_operator(&oper.op);
@@ -66,7 +73,7 @@ pub fn argument_to_assignment(arg: &Expression) -> Result<Assignment> {
                 node,
                 span,
             };
-            assign_values(&mut assignment, &oper.left, &oper.right, &mut errors);
+            assign_values(&mut assignment, &oper.left, &oper.right, table_name, &mut errors);
         },
         _ => {
             errors.push(Error::new(
@@ -0,0 +1,176 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the union(), intersect() and except() methods.
+
+use syn::spanned::Spanned;
+
+use ast::{
+    Combination,
+    Join,
+    Limit,
+    Nulls,
+    Order,
+    OrderValue,
+    Projection,
+    Query,
+    SetOperator,
+};
+use error::{Error, Result, res};
+use parser::MethodCall;
+use string::plural_verb;
+use super::subquery::{expression_to_subquery, is_subquery};
+
+/// Convert the argument of the union(), intersect() or except() method to a `Combination`.
+pub fn method_call_to_combination(method_call: &MethodCall) -> Result<Combination> {
+    let mut errors = vec![];
+    let operator =
+        match method_call.name.to_string().as_str() {
+            "except" => SetOperator::Except,
+            "intersect" => SetOperator::Intersect,
+            _ => SetOperator::Union,
+        };
+    if method_call.args.len() != 1 {
+        let length = method_call.args.len();
+        errors.push(Error::new_with_code(
+            &format!("this method takes 1 parameter but {} parameter{} supplied", length, plural_verb(length)),
+            method_call.position,
+            "E0061",
+        ));
+        return Err(errors);
+    }
+    let arg = &method_call.args[0];
+    if !is_subquery(arg) {
+        errors.push(Error::new("expected a query like `Table.filter(...)`", arg.span()));
+        return Err(errors);
+    }
+    let query = expression_to_subquery(arg)?;
+    if let Query::Select { ref limit, ref order, ref related, .. } = *query.query {
+        let method = &method_call.name;
+        let position = |name: &str| query.calls.calls.iter()
+            .find(|call| call.name == name)
+            .map_or(query.calls.position, |call| call.name.span());
+        if !order.is_empty() {
+            let mut error = Error::new(&format!("cannot sort a query combined with {}()", method), position("sort"));
+            error.add_help("call sort() after the combination to sort all the rows");
+            errors.push(error);
+        }
+        match *limit {
+            Limit::NoLimit => (),
+            _ => {
+                let mut error = Error::new(&format!("cannot limit a query combined with {}()", method),
+                    position("limit"));
+                error.add_help("index the combination to limit all the rows");
+                errors.push(error);
+            },
+        }
+        if !related.is_empty() {
+            errors.push(Error::new(&format!("cannot call the with() method in a query combined with {}()", method),
+                position("with")));
+        }
+    }
+    res(Combination {
+        operator,
+        query,
+    }, errors)
+}
+
+/// Check that the queries of the `combinations` select the same columns as the main query and that
+/// the rows are sorted by selected columns.
+pub fn check_combinations(combinations: &[Combination], table: &str, projection: &Projection, joins: &[Join],
+    order: &[Order], errors: &mut Vec<Error>)
+{
+    for combination in combinations {
+        if let Query::Select { joins: ref other_joins, projection: ref other_projection, .. } = *combination.query.query {
+            let other_table = combination.query.table.to_string();
+            if !same_columns(table, projection, joins, &other_table, other_projection, other_joins) {
+                let mut error = Error::new(
+                    &format!("the query combined with {}() must select the same columns",
+                        method_name(combination.operator)),
+                    combination.query.calls.position,
+                );
+                error.add_help("use only() to select the same fields in both queries");
+                errors.push(error);
+            }
+        }
+    }
+
+    if combinations.is_empty() {
+        return;
+    }
+    // NOTE: the ORDER BY clause of a compound query can only refer to the selected columns.
+    for order in order {
+        let field =
+            match *order {
+                Order::Ascending(OrderValue::Field(ref field), _) | Order::Descending(OrderValue::Field(ref field), _) =>
+                    field,
+                Order::Ascending(OrderValue::MethodCall(ref call), _) |
+                    Order::Descending(OrderValue::MethodCall(ref call), _) => {
+                    errors.push(Error::new("cannot sort the combined rows by a method call", call.position));
+                    continue;
+                },
+                Order::NoOrder => continue,
+            };
+        // NOTE: the emulation of NULLS FIRST/LAST sorts by an expression, which SQLite does not
+        // allow in a compound query.
+        if let Order::Ascending(_, nulls) | Order::Descending(_, nulls) = *order {
+            if nulls != Nulls::Unspecified && cfg!(feature = "rusqlite") {
+                errors.push(Error::new("cannot place the NULL values of the combined rows with SQLite",
+                    field.field.span()));
+            }
+        }
+        if field.foreign_key.is_some() {
+            errors.push(Error::new("cannot sort the combined rows by the field of a joined table", field.field.span()));
+        }
+        else if let Projection::Fields(ref fields) = *projection {
+            if !fields.contains(&field.field) {
+                errors.push(Error::new(
+                    &format!("cannot sort the combined rows by the field `{}` which is not selected", field.field),
+                    field.field.span(),
+                ));
+            }
+        }
+    }
+}
+
+/// Get the name of the method combining the queries with the `operator`.
+fn method_name(operator: SetOperator) -> &'static str {
+    match operator {
+        SetOperator::Except => "except",
+        SetOperator::Intersect => "intersect",
+        SetOperator::Union => "union",
+    }
+}
+
+/// Check whether two queries select the same columns.
+/// Fields with the same names can be selected from different tables: their types are checked later.
+fn same_columns(table1: &str, projection1: &Projection, joins1: &[Join], table2: &str, projection2: &Projection,
+    joins2: &[Join]) -> bool
+{
+    match (projection1, projection2) {
+        (Projection::All, Projection::All) =>
+            table1 == table2 && joins1.len() == joins2.len() &&
+                joins1.iter().zip(joins2).all(|(join1, join2)| join1.path() == join2.path()),
+        (Projection::Fields(fields1), Projection::Fields(fields2)) => fields1 == fields2,
+        (Projection::Struct(struct1), Projection::Struct(struct2)) => table1 == table2 && struct1 == struct2,
+        _ => false,
+    }
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the distinct() and distinct_on() methods.
+
+use syn::Ident;
+
+use ast::{Distinct, Order, OrderValue, Query};
+use error::{Error, Result, res};
+use parser::MethodCall;
+use super::path_expr_to_identifier;
+
+/// Convert the arguments of the distinct_on() method to a `Distinct`.
+pub fn method_call_to_distinct_on(method_call: &MethodCall) -> Result<Distinct> {
+    let mut errors = vec![];
+    if cfg!(not(feature = "postgres")) {
+        errors.push(Error::new(
+            "the distinct_on() method is only supported by PostgreSQL",
+            method_call.name.span(),
+        ));
+    }
+    if method_call.args.is_empty() {
+        errors.push(Error::new_with_code(
+            "this method takes at least 1 parameter but 0 parameters were supplied",
+            method_call.position,
+            "E0061",
+        ));
+    }
+    let fields = method_call.args.iter()
+        .filter_map(|arg| path_expr_to_identifier(arg, &mut errors))
+        .collect();
+    res(Distinct::On(fields), errors)
+}
+
+/// Check that the sort fields start with the fields of the distinct_on() method, as required by
+/// PostgreSQL.
+pub fn check_distinct_on_order(fields: &[Ident], order: &[Order], errors: &mut Vec<Error>) {
+    for order in order.iter().take(fields.len()) {
+        let (found, position) =
+            match *order {
+                Order::Ascending(OrderValue::Field(ref field), _) | Order::Descending(OrderValue::Field(ref field), _) => {
+                    if field.foreign_key.is_none() && fields.contains(&field.field) {
+                        continue;
+                    }
+                    (field.field.to_string(), field.field.span())
+                },
+                Order::Ascending(OrderValue::MethodCall(ref call), _) |
+                    Order::Descending(OrderValue::MethodCall(ref call), _) =>
+                    (format!("{}.{}()", call.object_name, call.method_name), call.position),
+                Order::NoOrder => continue,
+            };
+        let mut error = Error::new(
+            &format!("the sort() method must start with the fields of the distinct_on() method, found `{}`",
+                     found),
+            position,
+        );
+        let fields: Vec<_> = fields.iter()
+            .map(|field| format!("`{}`", field))
+            .collect();
+        error.add_help(&format!("sort by {} first", fields.join(", ")));
+        errors.push(error);
+        return;
+    }
+}
+
+/// Get the fields in the distinct_on() method to be able to check that they exist.
+pub fn get_distinct_idents(query: &Query) -> Vec<Ident> {
+    if let Query::Select { distinct: Distinct::On(ref fields), .. } = *query {
+        return fields.clone();
+    }
+    vec![]
+}
@@ -22,9 +22,12 @@
 /// Analyzer for the filter() method.
 
 use proc_macro2::Span;
+use quote::ToTokens;
 use syn::{
     BinOp,
     Expr,
+    ExprCall,
+    ExprMethodCall,
     ExprUnary,
     Ident,
     Path,
@@ -37,23 +40,52 @@ use syn::token::Comma;
 use ast::{
     self,
     Expression,
+    FieldPath,
     Filter,
     FilterExpression,
     Filters,
     FilterValue,
     LogicalOperator,
+    ManyToManyFilter,
+    Operand,
     Query,
     RelationalOperator,
+    SubqueryFilter,
     WithSpan,
 };
 use error::{Error, Result, res};
+use super::{check_argument_count, expr_to_field_path, get_type, mismatched_types};
+use super::subquery::{expression_to_subquery, get_selected_field, is_subquery};
+use super::operand::{
+    analyze_operand_types,
+    check_numeric_literal,
+    check_intervals,
+    expression_to_operand,
+    field_expression_to_operand,
+    is_arithmetic_expression,
+    is_function_call,
+};
 
 /// Analyze the types of the `FilterExpression`.
 pub fn analyze_filter_types(filter: &FilterExpression, table_name: &str, errors: &mut Vec<Error>) {
     // TODO: check that operators are used with the good types (perhaps not necessary because all
     // types may support all operators)?
     match *filter {
-        FilterExpression::Filter(_) => (),
+        FilterExpression::Filter(ref filter) => {
+            if let FilterValue::Arithmetic(ref operand) = filter.operand1 {
+                analyze_operand_types(operand, errors);
+                // The arithmetic expression is compared to a number.
+                if let Operand::Expression(ref expr) = filter.operand2 {
+                    check_numeric_literal(expr, errors);
+                }
+            }
+            analyze_operand_types(&filter.operand2, errors);
+        },
+        // The subquery was analyzed when it was converted.
+        FilterExpression::Exists(_) | FilterExpression::InSubquery(_) => (),
+        FilterExpression::ManyToMany(ref filter) => {
+            analyze_filter_types(&filter.filter, table_name, errors);
+        },
         FilterExpression::Filters(ref filters) => {
             analyze_filter_types(&*filters.operand1, table_name, errors);
             analyze_filter_types(&*filters.operand2, table_name, errors);
@@ -65,7 +97,11 @@ pub fn analyze_filter_types(filter: &FilterExpression, table_name: &str, errors:
         FilterExpression::ParenFilter(ref filter) => {
             analyze_filter_types(filter, table_name, errors);
         },
-        FilterExpression::FilterValue(_) => (),
+        FilterExpression::FilterValue(ref filter_value) => {
+            if let FilterValue::Arithmetic(_) = filter_value.node {
+                mismatched_types("bool", &"number", filter_value.span, errors);
+            }
+        },
     }
 }
 
@@ -75,7 +111,7 @@ fn binary_expression_to_filter_expression(expr1: &Expression, op: &BinOp, expr2:
 {
     // TODO: accumulate the errors instead of stopping when the first one is encountered.
     let filter1 = expression_to_filter_expression(expr1, table_name)?;
-    // TODO: return errors instead of dummy.
+    let mut errors = vec![];
     let dummy = FilterExpression::NoFilters;
 
     let filter =
@@ -92,17 +128,25 @@ fn binary_expression_to_filter_expression(expr1: &Expression, op: &BinOp, expr2:
                 FilterExpression::Filter(Filter {
                     operand1: filter1.node,
                     operator: binop_to_relational_operator(op),
-                    operand2: expr2.clone(),
+                    operand2: expression_to_operand(expr2, table_name)?,
                 })
             }
             else {
+                errors.push(Error::new(
+                    "expected field, method call or arithmetic expression on the left side of the comparison",
+                    expr1.span(),
+                ));
                 dummy
             }
         }
         else {
+            errors.push(Error::new(
+                &format!("unsupported operator `{}` in filter", op.into_token_stream()),
+                op.span(),
+            ));
             dummy
         };
-    Ok(filter)
+    res(filter, errors)
 }
 
 /// Convert a `BinOp` to an SQL `LogicalOperator`.
@@ -142,13 +186,30 @@ pub fn expression_to_filter_expression(arg: &Expression, table_name: &str) -> Re
 
     let filter =
         match *arg {
+            _ if is_arithmetic_expression(arg) || is_function_call(arg) => {
+                let operand = field_expression_to_operand(arg, table_name, &mut errors);
+                check_intervals(&operand, &mut errors);
+                FilterExpression::FilterValue(WithSpan {
+                    node: FilterValue::Arithmetic(operand),
+                    span: arg.span(),
+                })
+            },
             Expr::Binary(ref bin) => {
                 binary_expression_to_filter_expression(&bin.left, &bin.op, &bin.right, table_name)?
             },
+            Expr::Call(ref call) if is_exists_call(call) => {
+                exists_call_to_filter(call, &mut errors)
+            },
+            Expr::MethodCall(ref call) if is_list_method(&call.method) => {
+                list_method_call_to_filter(call, table_name, &mut errors)
+            },
+            Expr::MethodCall(ref call) if call.method == "any" => {
+                many_to_many_call_to_filter(call, table_name, &mut errors)
+            },
             Expr::MethodCall(ref call) => {
                 FilterExpression::FilterValue(WithSpan {
                     node: method_call_expression_to_filter_expression(call.method.clone(), &call.receiver, &call.args,
-                                                                      call.span(), &mut errors),
+                                                                      call.span(), table_name, &mut errors),
                     span: arg.span(),
                 })
             },
@@ -159,6 +220,18 @@ pub fn expression_to_filter_expression(arg: &Expression, table_name: &str) -> Re
                     span: arg.span(),
                 })
             },
+            Expr::Field(_) => {
+                let node =
+                    match expr_to_field_path(arg, &mut errors) {
+                        Some(FieldPath { field, foreign_key: Some(foreign_key) }) =>
+                            FilterValue::RelatedIdentifier(table_name.to_string(), foreign_key, field),
+                        _ => FilterValue::None,
+                    };
+                FilterExpression::FilterValue(WithSpan {
+                    node,
+                    span: arg.span(),
+                })
+            },
             Expr::Paren(ref paren) => {
                 let filter = expression_to_filter_expression(&paren.expr, table_name)?;
                 FilterExpression::ParenFilter(Box::new(filter))
@@ -179,6 +252,44 @@ pub fn expression_to_filter_expression(arg: &Expression, table_name: &str) -> Re
     res(filter, errors)
 }
 
+/// Convert an `Exists(Table.filter(...))` call to a filter expression.
+fn exists_call_to_filter(call: &ExprCall, errors: &mut Vec<Error>) -> FilterExpression {
+    if !check_argument_count(&call.args, 1, call.span(), errors) {
+        return FilterExpression::NoFilters;
+    }
+    let query = call.args.first().unwrap().into_value();
+    if !is_subquery(query) {
+        errors.push(Error::new(
+            "expected a query like `Table.filter(...)`",
+            query.span(),
+        ));
+        return FilterExpression::NoFilters;
+    }
+    match expression_to_subquery(query) {
+        Ok(subquery) => FilterExpression::Exists(subquery),
+        Err(subquery_errors) => {
+            errors.extend(subquery_errors);
+            FilterExpression::NoFilters
+        },
+    }
+}
+
+/// Check if the function call is `Exists(query)`.
+fn is_exists_call(call: &ExprCall) -> bool {
+    if let Expr::Path(ref path) = *call.func {
+        path.qself.is_none() && path.path.segments.len() == 1 &&
+            path.path.segments.first().expect("first segment in path").value().ident == "Exists"
+    }
+    else {
+        false
+    }
+}
+
+/// Check if the method is `is_in()` or `not_in()`.
+fn is_list_method(method: &Ident) -> bool {
+    method == "is_in" || method == "not_in"
+}
+
 /// Check if a `BinOp` is a `LogicalOperator`.
 pub fn is_logical_operator(binop: &BinOp) -> bool {
     match *binop {
@@ -195,14 +306,121 @@ pub fn is_relational_operator(binop: &BinOp) -> bool {
     }
 }
 
+/// Convert a `field.is_in(values)` or `field.not_in(values)` call to a filter expression.
+/// The `values` can also be a subquery selecting a single field.
+fn list_method_call_to_filter(call: &ExprMethodCall, table_name: &str, errors: &mut Vec<Error>) -> FilterExpression {
+    let identifier =
+        if let Expr::Path(ref path) = *call.receiver {
+            path.path.segments.first().unwrap().into_value().ident.clone()
+        }
+        else {
+            errors.push(Error::new(
+                "expected identifier", // TODO: improve this message.
+                call.receiver.span(),
+            ));
+            return FilterExpression::NoFilters;
+        };
+    if !check_argument_count(&call.args, 1, call.span(), errors) {
+        return FilterExpression::NoFilters;
+    }
+    let values = call.args.first().unwrap().into_value().clone();
+    let operator =
+        if call.method == "is_in" {
+            RelationalOperator::In
+        }
+        else {
+            RelationalOperator::NotIn
+        };
+    let operand1 = FilterValue::Identifier(table_name.to_string(), identifier);
+    if is_subquery(&values) {
+        return match expression_to_subquery(&values) {
+            Ok(subquery) => {
+                get_selected_field(&subquery, errors);
+                FilterExpression::InSubquery(SubqueryFilter {
+                    operand1,
+                    operator,
+                    subquery,
+                })
+            },
+            Err(subquery_errors) => {
+                errors.extend(subquery_errors);
+                FilterExpression::NoFilters
+            },
+        };
+    }
+    if let Expr::Lit(_) = values {
+        mismatched_types("slice or Vec", &get_type(&values), values.span(), errors);
+    }
+    FilterExpression::Filter(Filter {
+        operand1,
+        operator,
+        operand2: Operand::Expression(values),
+    })
+}
+
+/// Convert a `field.any(filter)` call, where `field` is a `ManyToMany`, to a filter expression.
+/// The fields used in `filter` are those of the related table.
+fn many_to_many_call_to_filter(call: &ExprMethodCall, table_name: &str, errors: &mut Vec<Error>) -> FilterExpression {
+    let field =
+        if let Expr::Path(ref path) = *call.receiver {
+            path.path.segments.first().unwrap().into_value().ident.clone()
+        }
+        else {
+            errors.push(Error::new(
+                "expected identifier", // TODO: improve this message.
+                call.receiver.span(),
+            ));
+            return FilterExpression::NoFilters;
+        };
+    if !check_argument_count(&call.args, 1, call.span(), errors) {
+        return FilterExpression::NoFilters;
+    }
+    let arg = call.args.first().unwrap().into_value();
+    // NOTE: the related table is aliased like a table joined via the field.
+    let alias = format!("{}_{}", table_name, field);
+    match expression_to_filter_expression(arg, &alias) {
+        Ok(filter) => {
+            check_many_to_many_filter(&filter, arg, errors);
+            FilterExpression::ManyToMany(ManyToManyFilter {
+                field,
+                filter: Box::new(filter),
+                table: table_name.to_string(),
+            })
+        },
+        Err(filter_errors) => {
+            errors.extend(filter_errors);
+            FilterExpression::NoFilters
+        },
+    }
+}
+
+/// Check that the `filter` of a `field.any(filter)` call does not need a join.
+fn check_many_to_many_filter(filter: &FilterExpression, arg: &Expression, errors: &mut Vec<Error>) {
+    match *filter {
+        FilterExpression::Filter(Filter { operand1: FilterValue::RelatedIdentifier(..), .. }) |
+            FilterExpression::FilterValue(WithSpan { node: FilterValue::RelatedIdentifier(..), .. }) =>
+            errors.push(Error::new("cannot use the fields of a joined table in any()", arg.span())),
+        FilterExpression::Filters(ref filters) => {
+            check_many_to_many_filter(&filters.operand1, arg, errors);
+            check_many_to_many_filter(&filters.operand2, arg, errors);
+        },
+        FilterExpression::ManyToMany(_) =>
+            errors.push(Error::new("cannot nest any() calls", arg.span())),
+        FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) =>
+            check_many_to_many_filter(filter, arg, errors),
+        FilterExpression::Exists(_) | FilterExpression::Filter(_) | FilterExpression::FilterValue(_) |
+            FilterExpression::InSubquery(_) | FilterExpression::NoFilters => (),
+    }
+}
+
 /// Convert a method call expression to a filter expression.
 fn method_call_expression_to_filter_expression(identifier: Ident, expr: &Expression, args: &Punctuated<Expr, Comma>,
-    position: Span, errors: &mut Vec<Error>) -> FilterValue
+    position: Span, table_name: &str, errors: &mut Vec<Error>) -> FilterValue
 {
     let dummy = FilterValue::None;
     match *expr {
         Expr::Path(ref path) => {
-            path_method_call_to_filter(&path.path, identifier, args, position)
+            path_method_call_to_filter(&path.path, identifier, args, position, table_name)
         },
         _ => {
             errors.push(Error::new(
@@ -215,14 +433,15 @@ fn method_call_expression_to_filter_expression(identifier: Ident, expr: &Express
 }
 
 /// Convert a method call where the object is an identifier to a filter expression.
-fn path_method_call_to_filter(path: &Path, identifier: Ident, args: &Punctuated<Expr, Comma>, position: Span) -> FilterValue
+fn path_method_call_to_filter(path: &Path, identifier: Ident, args: &Punctuated<Expr, Comma>, position: Span,
+                              table_name: &str) -> FilterValue
 {
     let object_name = path.segments.first().unwrap().into_value().ident.clone();
     let arguments: Vec<Expression> = args.iter()
         .cloned()
         .collect();
 
-    FilterValue::MethodCall(ast::MethodCall {
+    FilterValue::MethodCall(table_name.to_string(), ast::MethodCall {
         arguments: arguments.clone(),
         method_name: identifier,
         object_name,
@@ -230,7 +449,7 @@ fn path_method_call_to_filter(path: &Path, identifier: Ident, args: &Punctuated<
     })
 }
 
-pub fn get_method_calls(query: &Query) -> Vec<(ast::MethodCall, Option<Expression>)> {
+pub fn get_method_calls(query: &Query) -> Vec<(ast::MethodCall, Option<Operand>)> {
     match *query {
         Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } | Query::Select { ref filter, .. } |
             Query::Update { ref filter, .. } =>
@@ -240,11 +459,11 @@ pub fn get_method_calls(query: &Query) -> Vec<(ast::MethodCall, Option<Expressio
     }
 }
 
-fn get_methods_from_filter(filter: &FilterExpression) -> Vec<(ast::MethodCall, Option<Expression>)> {
+fn get_methods_from_filter(filter: &FilterExpression) -> Vec<(ast::MethodCall, Option<Operand>)> {
     let mut calls = vec![];
     match *filter {
         FilterExpression::Filter(ref filter) => {
-            if let FilterValue::MethodCall(ref call) = filter.operand1 {
+            if let FilterValue::MethodCall(_, ref call) = filter.operand1 {
                 calls.push((call.clone(), Some(filter.operand2.clone())));
             }
         },
@@ -253,13 +472,48 @@ fn get_methods_from_filter(filter: &FilterExpression) -> Vec<(ast::MethodCall, O
             calls.extend(get_methods_from_filter(&filters.operand2));
         },
         FilterExpression::FilterValue(ref filter_value) => {
-            if let FilterValue::MethodCall(ref call) = filter_value.node {
+            if let FilterValue::MethodCall(_, ref call) = filter_value.node {
                 calls.push((call.clone(), None));
             }
         },
         FilterExpression::NegFilter(ref filter) => calls.extend(get_methods_from_filter(filter)),
-        FilterExpression::NoFilters => (),
+        // The methods of the subqueries and of any() are called on the fields of another table.
+        FilterExpression::Exists(_) | FilterExpression::InSubquery(_) | FilterExpression::ManyToMany(_) |
+            FilterExpression::NoFilters => (),
         FilterExpression::ParenFilter(ref filter) => calls.extend(get_methods_from_filter(filter)),
     }
     calls
 }
+
+/// Get the arithmetic expressions on the left side of the comparisons with the operand they are
+/// compared to.
+pub fn get_arithmetic_filters(query: &Query) -> Vec<(Operand, Operand)> {
+    match *query {
+        Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } | Query::Select { ref filter, .. } |
+            Query::Update { ref filter, .. } =>
+            get_arithmetic_filters_from_filter(filter),
+        Query::CreateTable { .. } | Query::Drop { .. } | Query::Insert { .. } =>
+            vec![],
+    }
+}
+
+fn get_arithmetic_filters_from_filter(filter: &FilterExpression) -> Vec<(Operand, Operand)> {
+    let mut filters = vec![];
+    match *filter {
+        FilterExpression::Filter(ref filter) => {
+            if let FilterValue::Arithmetic(ref operand) = filter.operand1 {
+                filters.push((operand.clone(), filter.operand2.clone()));
+            }
+        },
+        FilterExpression::Filters(ref filters_expr) => {
+            filters.extend(get_arithmetic_filters_from_filter(&filters_expr.operand1));
+            filters.extend(get_arithmetic_filters_from_filter(&filters_expr.operand2));
+        },
+        FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) =>
+            filters.extend(get_arithmetic_filters_from_filter(filter)),
+        // NOTE: the fields in any() are those of another table.
+        FilterExpression::Exists(_) | FilterExpression::FilterValue(_) | FilterExpression::InSubquery(_) |
+            FilterExpression::ManyToMany(_) | FilterExpression::NoFilters => (),
+    }
+    filters
+}
@@ -29,6 +29,7 @@ use ast::{
     FilterExpression,
     FilterValue,
     Limit,
+    Operand,
     RelationalOperator,
 };
 use error::{Result, res};
@@ -44,7 +45,7 @@ pub fn get_expression_to_filter_expression(arg: &Expression, table_name: &str) -
             let filter = FilterExpression::Filter(Filter {
                 operand1: FilterValue::PrimaryKey(table_name.to_string()),
                 operator: RelationalOperator::Equal,
-                operand2: arg.clone(),
+                operand2: Operand::Expression(arg.clone()),
             });
             res((filter, true, Limit::NoLimit), vec![])
         },
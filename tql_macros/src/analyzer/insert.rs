@@ -43,11 +43,15 @@ pub fn check_insert_arguments(assignments: &[Assignment], errors: &mut Vec<Error
 
     // Check the assignment operators.
     for assignment in assignments {
-        fields.insert(assignment.identifier.clone().expect("Assignment identifier"));
+        let identifier = assignment.identifier.clone().expect("Assignment identifier");
         let operator = &assignment.operator.node;
         if *operator != AssignmentOperator::Equal {
             errors.push(Error::new(&format!("expected = but got {}", *operator), assignment.operator.span));
         }
+        if assignment.value.has_field() {
+            errors.push(Error::new("cannot reference a field in insert()", identifier.span()));
+        }
+        fields.insert(identifier);
     }
 
     // TODO: check if the primary key is not in the inserted field?
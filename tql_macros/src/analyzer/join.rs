@@ -21,21 +21,26 @@
 
 /// Analyzer for the join() method.
 
+use syn::{Expr, ExprField, Ident, Member};
 use syn::spanned::Spanned;
 
-use ast::{Expression, Join};
+use ast::{Expression, FilterExpression, FilterValue, Join, JoinType, Query};
 use error::{Error, Result, res};
 use super::path_expr_to_identifier;
 
 /// Convert an `Expression` to a `Join`
-pub fn argument_to_join(arg: &Expression, table_name: &str) -> Result<Join> {
+pub fn argument_to_join(arg: &Expression, table_name: &str, join_type: JoinType) -> Result<Join> {
     let mut errors = vec![];
     let join;
 
-    if let Some(identifier) = path_expr_to_identifier(&arg, &mut errors) {
+    if let Some(mut path) = expr_to_join_path(arg, &mut errors) {
+        let base_field = path.pop().expect("join path");
         join = Some(Join {
-            base_field: identifier,
+            base_field,
             base_table: table_name.to_string(),
+            implicit: false,
+            join_type,
+            parent_fields: path,
         });
         // NOTE: if the field type is not an SQL table, an error is thrown.
     }
@@ -45,3 +50,107 @@ pub fn argument_to_join(arg: &Expression, table_name: &str) -> Result<Join> {
 
     res(join.expect("join"), errors)
 }
+
+/// Convert an `Expression` like `author.company` to the list of `ForeignKey` fields to follow.
+fn expr_to_join_path(expression: &Expression, errors: &mut Vec<Error>) -> Option<Vec<Ident>> {
+    if let Expr::Field(ExprField { ref base, member: Member::Named(ref field), .. }) = *expression {
+        return expr_to_join_path(base, errors)
+            .map(|mut path| {
+                path.push(field.clone());
+                path
+            });
+    }
+    path_expr_to_identifier(expression, errors)
+        .map(|identifier| vec![identifier])
+}
+
+/// Add the `join` to `joins`, preceded by the joins of its parent tables which are not already
+/// joined.
+pub fn add_join(joins: &mut Vec<Join>, join: Join) {
+    for index in 0..join.parent_fields.len() {
+        let parent_join = Join {
+            base_field: join.parent_fields[index].clone(),
+            base_table: join.base_table.clone(),
+            implicit: true,
+            join_type: join.join_type,
+            parent_fields: join.parent_fields[..index].to_vec(),
+        };
+        if !joins.iter().any(|existing_join| existing_join.path() == parent_join.path()) {
+            joins.push(parent_join);
+        }
+    }
+    let existing_join = joins.iter_mut()
+        .find(|existing_join| existing_join.path() == join.path());
+    match existing_join {
+        Some(existing_join) => {
+            existing_join.implicit = false;
+            existing_join.join_type = join.join_type;
+        },
+        None => joins.push(join),
+    }
+}
+
+/// Check that the tables of the fields like `author.name` are joined.
+pub fn check_related_fields(query: &Query, errors: &mut Vec<Error>) {
+    let joins: &[Join] =
+        match *query {
+            Query::Aggregate { ref joins, .. } | Query::Select { ref joins, .. } => joins,
+            _ => &[],
+        };
+    for foreign_key in get_related_foreign_keys(query) {
+        if !joins.iter().any(|join| join.base_field == foreign_key && join.parent_fields.is_empty()) {
+            let mut error = Error::new(
+                &format!("cannot use the fields of `{}` without joining it", foreign_key),
+                foreign_key.span(),
+            );
+            error.add_help(&format!("call join({}) in this query", foreign_key));
+            errors.push(error);
+        }
+    }
+}
+
+/// Get the `ForeignKey` fields used in paths like `author.name`.
+fn get_related_foreign_keys(query: &Query) -> Vec<Ident> {
+    let mut foreign_keys = vec![];
+    match *query {
+        Query::Aggregate { ref filter, ref groups, .. } => {
+            add_filter_foreign_keys(filter, &mut foreign_keys);
+            foreign_keys.extend(groups.iter().filter_map(|group| group.foreign_key.clone()));
+        },
+        Query::Delete { ref filter, .. } | Query::Update { ref filter, .. } => {
+            add_filter_foreign_keys(filter, &mut foreign_keys);
+        },
+        Query::Select { ref filter, ref order, .. } => {
+            add_filter_foreign_keys(filter, &mut foreign_keys);
+            for order in order {
+                if let Some(field) = order.field() {
+                    foreign_keys.extend(field.foreign_key.clone());
+                }
+            }
+        },
+        Query::CreateTable { .. } | Query::Drop { .. } | Query::Insert { .. } => (),
+    }
+    foreign_keys
+}
+
+fn add_filter_foreign_keys(filter: &FilterExpression, foreign_keys: &mut Vec<Ident>) {
+    let filter_value =
+        match *filter {
+            FilterExpression::Filter(ref filter) => &filter.operand1,
+            FilterExpression::Filters(ref filters) => {
+                add_filter_foreign_keys(&filters.operand1, foreign_keys);
+                add_filter_foreign_keys(&filters.operand2, foreign_keys);
+                return;
+            },
+            FilterExpression::FilterValue(ref filter_value) => &filter_value.node,
+            FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) => {
+                add_filter_foreign_keys(filter, foreign_keys);
+                return;
+            },
+            FilterExpression::Exists(_) | FilterExpression::InSubquery(_) | FilterExpression::ManyToMany(_) |
+                FilterExpression::NoFilters => return,
+        };
+    if let FilterValue::RelatedIdentifier(_, ref foreign_key, _) = *filter_value {
+        foreign_keys.push(foreign_key.clone());
+    }
+}
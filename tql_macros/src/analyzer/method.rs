@@ -22,13 +22,15 @@
 use ast::Query;
 use error::{Error, Result, res};
 use state::methods_singleton;
-use super::get_method_calls;
+use super::{get_method_calls, get_order_method_calls};
 
 pub fn analyze_methods(query: &Query) -> Result<()> {
     let methods = methods_singleton();
-    let calls = get_method_calls(query);
+    let calls = get_method_calls(query).into_iter()
+        .map(|(call, _)| call)
+        .chain(get_order_method_calls(query));
     let mut errors = vec![];
-    for (call, _) in calls {
+    for call in calls {
         let name = call.method_name.to_string();
         if let Some(method) = methods.get(&name) {
             if method.template.is_none() {
@@ -22,14 +22,22 @@
 //! Semantic analyzer.
 
 mod aggregate;
+mod annotation;
 mod assignment;
+mod combination;
+mod distinct;
 mod filter;
 mod get;
 mod insert;
 mod method;
 mod join;
 mod limit;
+mod operand;
+mod pagination;
+mod projection;
+mod related;
 mod sort;
+mod subquery;
 
 use std::fmt::Display;
 use std::result;
@@ -37,10 +45,12 @@ use std::result;
 use proc_macro2::Span;
 use syn::{
     Expr,
+    ExprField,
     FloatSuffix,
     Ident,
     IntSuffix,
     Lit,
+    Member,
 };
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
@@ -49,34 +59,54 @@ use syn::token::Comma;
 use ast::{
     Aggregate,
     AggregateFilterExpression,
+    Annotation,
     Assignment,
+    Combination,
+    Distinct,
     Expression,
+    FieldPath,
     FilterExpression,
     Groups,
     Join,
+    JoinType,
     Limit,
     Order,
+    Pagination,
+    Projection,
     Query,
+    SelectResult,
 };
 use error::{Error, Result, res};
 use parser::{MethodCall, MethodCalls};
 use plugin::number_literal;
 use self::aggregate::{argument_to_aggregate, argument_to_group, expression_to_aggregate_filter_expression};
 pub use self::aggregate::{get_aggregate_calls, get_values_idents};
+use self::annotation::argument_to_annotation;
+pub use self::annotation::get_annotation_idents;
 use self::assignment::argument_to_assignment;
+use self::combination::{check_combinations, method_call_to_combination};
+use self::distinct::{check_distinct_on_order, method_call_to_distinct_on};
+pub use self::distinct::get_distinct_idents;
 use self::filter::{analyze_filter_types, expression_to_filter_expression};
-pub use self::filter::get_method_calls;
+pub use self::filter::{get_arithmetic_filters, get_method_calls};
 use self::get::get_expression_to_filter_expression;
 use self::insert::check_insert_arguments;
 pub use self::insert::get_insert_idents;
 #[cfg(feature = "unstable")]
 pub use self::insert::get_insert_position;
-use self::join::argument_to_join;
+use self::join::{add_join, argument_to_join, check_related_fields};
 use self::limit::{analyze_limit_types, argument_to_limit};
 pub use self::limit::get_limit_args;
 pub use self::method::analyze_methods;
+pub use self::operand::{get_field_references, get_function_operands};
+use self::pagination::{check_pagination, method_call_to_pagination};
+pub use self::pagination::get_cursor_fields;
+use self::projection::method_call_to_projection;
+pub use self::projection::get_projection_idents;
+use self::related::argument_to_related_field;
 use self::sort::argument_to_order;
-pub use self::sort::get_sort_idents;
+pub use self::sort::{get_order_method_calls, get_sort_idents};
+pub use self::subquery::get_subquery_fields;
 use string::{find_near, plural_verb};
 use types::Type;
 
@@ -84,9 +114,11 @@ use types::Type;
 #[derive(PartialEq)]
 enum SqlQueryType {
     Aggregate,
+    Count,
     CreateTable,
     Delete,
     Drop,
+    Exists,
     Insert,
     Select,
     SelectOne,
@@ -112,12 +144,18 @@ struct QueryData {
     // Aggregate, Delete, Select, Update
     filter: FilterExpression,
     // Aggregate / Select
+    annotations: Vec<Annotation>,
     joins: Vec<Join>,
     // Insert / Update
     assignments: Vec<Assignment>,
     // Select
+    combinations: Vec<Combination>,
+    distinct: Distinct,
     limit: Limit,
     order: Vec<Order>,
+    pagination: Pagination,
+    projection: Projection,
+    related: Vec<Ident>,
     use_pk: bool,
     // All
     query_type: SqlQueryType,
@@ -141,6 +179,7 @@ pub fn analyze(method_calls: &MethodCalls) -> Result<Query> {
     let query = new_query(query_data, table_name);
 
     check_delete_without_filters(&query, delete_position, &mut errors);
+    check_related_fields(&query, &mut errors);
 
     res(query, errors)
 }
@@ -178,7 +217,8 @@ fn check_argument_count(arguments: &Punctuated<Expr, Comma>, expected_count: usi
     else {
         let length = arguments.len();
         errors.push(Error::new_with_code(
-            &format!("this function takes 1 parameter but {} parameter{} supplied", length, plural_verb(length)),
+            &format!("this function takes {} parameter{} but {} parameter{} supplied", expected_count,
+                     if expected_count == 1 { "" } else { "s" }, length, plural_verb(length)),
             position,
             "E0061",
         ));
@@ -203,11 +243,14 @@ fn check_delete_without_filters(query: &Query, delete_position: Option<Span>, er
 fn check_method_calls_validity(method_calls: &MethodCalls, errors: &mut Vec<Error>) {
     let method_map =
         hashmap!{
-            "aggregate" => vec!["filter", "join", "values"],
-            "all" => vec!["filter", "get", "join", "limit", "sort"],
+            "aggregate" => vec!["annotate", "filter", "join", "left_join", "values"],
+            "all" => vec!["after", "annotate", "before", "distinct", "distinct_on", "except", "filter", "get", "intersect", "join",
+                "left_join", "limit", "only", "paginate", "sort", "union", "with"],
+            "count" => vec!["distinct", "except", "filter", "intersect", "join", "left_join", "limit", "only", "union"],
             "create" => vec![],
             "delete" => vec!["filter", "get"],
             "drop" => vec![],
+            "exists" => vec!["distinct", "except", "filter", "intersect", "join", "left_join", "limit", "only", "union"],
             "insert" => vec![],
             "update" => vec!["filter", "get"],
         };
@@ -306,19 +349,33 @@ fn convert_arguments<F, Type>(arguments: &[Expression], convert_argument: F) ->
 // TODO: return Vec<&'static str> instead?
 fn get_methods() -> Vec<String> {
     vec![
+        "after".to_string(),
         "aggregate".to_string(),
         "all".to_string(),
+        "annotate".to_string(),
+        "before".to_string(),
+        "count".to_string(),
         "create".to_string(),
         "delete".to_string(),
+        "distinct".to_string(),
+        "distinct_on".to_string(),
         "drop".to_string(),
+        "except".to_string(),
+        "exists".to_string(),
         "filter".to_string(),
         "get".to_string(),
         "insert".to_string(),
+        "intersect".to_string(),
         "join".to_string(),
+        "left_join".to_string(),
         "limit".to_string(),
+        "only".to_string(),
+        "paginate".to_string(),
         "sort".to_string(),
+        "union".to_string(),
         "update".to_string(),
         "values".to_string(),
+        "with".to_string(),
     ]
 }
 
@@ -378,14 +435,16 @@ fn mismatched_types<S: Display, T: Display>(expected_type: S, actual_type: &T, p
 }
 
 /// Create a new query from all the data gathered by the method calls.
-fn new_query(QueryData { filter, joins, limit, order, assignments, aggregates, groups,
-    aggregate_filter, query_type, use_pk }: QueryData, table_name: String) -> Query
+fn new_query(QueryData { filter, joins, limit, order, assignments, aggregates, annotations, groups,
+    aggregate_filter, combinations, distinct, pagination, projection, query_type, related, use_pk }: QueryData,
+    table_name: String) -> Query
 {
     match query_type {
         SqlQueryType::Aggregate =>
             Query::Aggregate {
                 aggregates,
                 aggregate_filter,
+                annotations,
                 filter,
                 groups,
                 joins,
@@ -410,13 +469,26 @@ fn new_query(QueryData { filter, joins, limit, order, assignments, aggregates, g
                 assignments,
                 table: table_name,
             },
-        SqlQueryType::Select | SqlQueryType::SelectOne => {
+        SqlQueryType::Count | SqlQueryType::Exists | SqlQueryType::Select | SqlQueryType::SelectOne => {
+            let result =
+                match query_type {
+                    SqlQueryType::Count => SelectResult::Count,
+                    SqlQueryType::Exists => SelectResult::Exists,
+                    _ => SelectResult::Rows,
+                };
             Query::Select {
+                annotations,
+                combinations,
+                distinct,
                 filter,
                 get: query_type == SqlQueryType::SelectOne,
                 joins,
                 limit,
                 order,
+                pagination,
+                projection,
+                related,
+                result,
                 table: table_name,
                 use_pk,
             }
@@ -447,6 +519,23 @@ fn path_expr_to_identifier(expression: &Expression, errors: &mut Vec<Error>) ->
     }
 }
 
+/// Convert an `Expression` to a `FieldPath` if `expression` is an `ExprPath` or a field of a
+/// joined table like `author.name`.
+/// It adds an error to `errors` otherwise.
+fn expr_to_field_path(expression: &Expression, errors: &mut Vec<Error>) -> Option<FieldPath> {
+    if let Expr::Field(ExprField { ref base, member: Member::Named(ref field), .. }) = *expression {
+        if let Expr::Path(_) = **base {
+            return path_expr_to_identifier(base, errors)
+                .map(|foreign_key| FieldPath {
+                    field: field.clone(),
+                    foreign_key: Some(foreign_key),
+                });
+        }
+    }
+    path_expr_to_identifier(expression, errors)
+        .map(FieldPath::new)
+}
+
 /// Convert an `Expression` to a `String` if `expression` is an `ExprPath`.
 /// It adds an error to `errors` if `expression` is not an `ExprPath`.
 fn path_expr_to_string(expression: &Expression, errors: &mut Vec<Error>) -> Option<String> {
@@ -459,18 +548,44 @@ fn path_expr_to_string(expression: &Expression, errors: &mut Vec<Error>) -> Opti
 fn process_methods(calls: &[MethodCall], table_name: &str, delete_position: &mut Option<Span>) -> Result<QueryData> {
     let mut errors = vec![];
     let mut query_data = QueryData::default();
+    let mut annotate_position = None;
+    let mut only_position = None;
+    let mut pagination_call = None;
 
     for method_call in calls {
         match method_call.name.to_string().as_str() {
+            "after" | "before" | "paginate" => {
+                if pagination_call.is_some() {
+                    errors.push(Error::new("cannot call the after(), before() or paginate() methods more than once",
+                        method_call.name.span()));
+                }
+                if method_call.name == "paginate" {
+                    check_no_arguments(method_call, &mut errors);
+                }
+                query_data.pagination = method_call_to_pagination(method_call, &mut errors);
+                pagination_call = Some((method_call.name.to_string(), method_call.name.span()));
+            },
             "aggregate" => {
-                try(convert_arguments(&method_call.args, argument_to_aggregate), &mut errors, |aggrs| {
-                    query_data.aggregates = aggrs;
-                });
+                try(convert_arguments(&method_call.args, |expr| argument_to_aggregate(expr, table_name)), &mut errors,
+                    |aggrs| {
+                        query_data.aggregates = aggrs;
+                    });
                 query_data.query_type = SqlQueryType::Aggregate;
             },
             "all" => {
                 check_no_arguments(&method_call, &mut errors);
             },
+            "annotate" => {
+                try(convert_arguments(&method_call.args, |expr| argument_to_annotation(expr, table_name)), &mut errors,
+                    |annotations| {
+                        query_data.annotations.extend(annotations);
+                    });
+                annotate_position = Some(method_call.name.span());
+            },
+            "count" => {
+                check_no_arguments(method_call, &mut errors);
+                query_data.query_type = SqlQueryType::Count;
+            },
             "create" => {
                 check_no_arguments(&method_call, &mut errors);
                 query_data.query_type = SqlQueryType::CreateTable;
@@ -480,10 +595,28 @@ fn process_methods(calls: &[MethodCall], table_name: &str, delete_position: &mut
                 query_data.query_type = SqlQueryType::Delete;
                 *delete_position = Some(method_call.name.span());
             },
+            "distinct" => {
+                check_no_arguments(method_call, &mut errors);
+                query_data.distinct = Distinct::All;
+            },
+            "distinct_on" => {
+                try(method_call_to_distinct_on(method_call), &mut errors, |distinct| {
+                    query_data.distinct = distinct;
+                });
+            },
             "drop" => {
                 check_no_arguments(&method_call, &mut errors);
                 query_data.query_type = SqlQueryType::Drop;
             },
+            "except" | "intersect" | "union" => {
+                try(method_call_to_combination(method_call), &mut errors, |combination| {
+                    query_data.combinations.push(combination);
+                });
+            },
+            "exists" => {
+                check_no_arguments(method_call, &mut errors);
+                query_data.query_type = SqlQueryType::Exists;
+            },
             "filter" => {
                 if query_data.aggregates.is_empty() {
                     // If the aggregate() method was not called, filter() filters on the values
@@ -515,20 +648,28 @@ fn process_methods(calls: &[MethodCall], table_name: &str, delete_position: &mut
                 query_data.query_type = SqlQueryType::SelectOne;
             },
             "insert" => {
-                try(convert_arguments(&method_call.args, argument_to_assignment), &mut errors, |assigns| {
-                    query_data.assignments = assigns;
-                });
+                try(convert_arguments(&method_call.args, |expr| argument_to_assignment(expr, table_name)), &mut errors,
+                    |assigns| {
+                        query_data.assignments = assigns;
+                    });
                 if !query_data.assignments.is_empty() {
                     // TODO: check even if there are errors in the assignation types.
                     check_insert_arguments(&query_data.assignments, &mut errors);
                 }
                 query_data.query_type = SqlQueryType::Insert;
             },
-            "join" => {
-                try(convert_arguments(&method_call.args, |expr| argument_to_join(expr, table_name)), &mut errors,
-                    |result| {
+            "join" | "left_join" => {
+                let join_type =
+                    if method_call.name == "join" {
+                        JoinType::Inner
+                    }
+                    else {
+                        JoinType::Left
+                    };
+                try(convert_arguments(&method_call.args, |expr| argument_to_join(expr, table_name, join_type)),
+                    &mut errors, |result| {
                         for new_join in result {
-                            query_data.joins.push(new_join);
+                            add_join(&mut query_data.joins, new_join);
                         }
                     });
             },
@@ -537,15 +678,25 @@ fn process_methods(calls: &[MethodCall], table_name: &str, delete_position: &mut
                     query_data.limit = new_limit;
                 });
             },
+            "only" => {
+                if only_position.is_some() {
+                    errors.push(Error::new("cannot call the only() method more than once", method_call.name.span()));
+                }
+                try(method_call_to_projection(method_call), &mut errors, |projection| {
+                    query_data.projection = projection;
+                });
+                only_position = Some(method_call.name.span());
+            },
             "sort" => {
                 try(convert_arguments(&method_call.args, argument_to_order), &mut errors, |new_order| {
                     query_data.order = new_order;
                 });
             },
             "update" => {
-                try(convert_arguments(&method_call.args, argument_to_assignment), &mut errors, |assigns| {
-                    query_data.assignments = assigns;
-                });
+                try(convert_arguments(&method_call.args, |expr| argument_to_assignment(expr, table_name)), &mut errors,
+                    |assigns| {
+                        query_data.assignments = assigns;
+                    });
                 query_data.query_type = SqlQueryType::Update;
             },
             "values" => {
@@ -553,9 +704,48 @@ fn process_methods(calls: &[MethodCall], table_name: &str, delete_position: &mut
                     query_data.groups = new_groups;
                 });
             },
+            "with" => {
+                try(convert_arguments(&method_call.args, argument_to_related_field), &mut errors, |fields| {
+                    query_data.related.extend(fields);
+                });
+            },
             _ => (), // NOTE: Nothing to do since check_methods() check for unknown method.
         }
     }
+    if let Distinct::On(ref fields) = query_data.distinct {
+        check_distinct_on_order(fields, &query_data.order, &mut errors);
+    }
+    check_combinations(&query_data.combinations, table_name, &query_data.projection, &query_data.joins,
+        &query_data.order, &mut errors);
+    if let Some(position) = annotate_position {
+        // The annotations are not part of the compared columns.
+        if !query_data.combinations.is_empty() {
+            errors.push(Error::new("cannot call the annotate() method with the union(), intersect() or except() methods",
+                position));
+        }
+        // The number of columns read by a struct selected by only() is unknown.
+        if let Projection::Struct(_) = query_data.projection {
+            errors.push(Error::new("cannot call the annotate() method with the only() method selecting a struct",
+                position));
+        }
+        // The related rows are added to the table structs, which are wrapped in the annotated structs.
+        if !query_data.related.is_empty() {
+            errors.push(Error::new("cannot call the annotate() method with the with() method", position));
+        }
+    }
+    if let Some((ref method, position)) = pagination_call {
+        check_pagination(method, position, &query_data, &mut errors);
+    }
+    if let Some(position) = only_position {
+        // The related fields are only selected with all the fields of the table.
+        if !query_data.joins.is_empty() {
+            errors.push(Error::new("cannot call the only() method with the join() method", position));
+        }
+        // The related rows are added to the table structs.
+        if !query_data.related.is_empty() {
+            errors.push(Error::new("cannot call the only() method with the with() method", position));
+        }
+    }
     res(query_data, errors)
 }
 
@@ -0,0 +1,355 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the operands of filter(), update(), … which can reference fields.
+
+use syn::{BinOp, Expr, ExprCall, Ident};
+use syn::spanned::Spanned;
+
+use ast::{
+    ArithmeticOperator,
+    Expression,
+    FilterExpression,
+    FilterValue,
+    Operand,
+    Query,
+    SqlFunction,
+};
+use error::{Error, Result, res};
+use parser::{field_reference, has_field_reference};
+use state::functions_singleton;
+use types::Type;
+use super::{check_argument_count, get_type, mismatched_types};
+
+/// Check that the literals used in an arithmetic `operand` are numbers.
+pub fn analyze_operand_types(operand: &Operand, errors: &mut Vec<Error>) {
+    if let Operand::Arithmetic(_, _, _) = *operand {
+        for leaf in operand.leaves() {
+            if let Operand::Expression(ref expr) = *leaf {
+                check_numeric_literal(expr, errors);
+            }
+        }
+    }
+}
+
+/// Convert a `BinOp` to an SQL `ArithmeticOperator`.
+fn binop_to_arithmetic_operator(binop: &BinOp) -> ArithmeticOperator {
+    match *binop {
+        BinOp::Add(_) => ArithmeticOperator::Add,
+        BinOp::Div(_) => ArithmeticOperator::Divide,
+        BinOp::Rem(_) => ArithmeticOperator::Modulo,
+        BinOp::Mul(_) => ArithmeticOperator::Mul,
+        BinOp::Sub(_) => ArithmeticOperator::Sub,
+        BinOp::AddEq(_) | BinOp::SubEq(_) | BinOp::MulEq(_) | BinOp::DivEq(_) | BinOp::RemEq(_) | BinOp::And(_) |
+            BinOp::Or(_) | BinOp::BitXor(_) | BinOp::BitXorEq(_) | BinOp::BitAnd(_) | BinOp::BitAndEq(_) |
+            BinOp::BitOr(_) | BinOp::BitOrEq(_) | BinOp::Shl(_) | BinOp::ShlEq(_) | BinOp::Shr(_) | BinOp::ShrEq(_) |
+            BinOp::Eq(_) | BinOp::Lt(_) | BinOp::Le(_) | BinOp::Ne(_) | BinOp::Ge(_) | BinOp::Gt(_) =>
+            unreachable!("binop_to_arithmetic_operator"),
+    }
+}
+
+/// Convert a call to a function evaluated by the database to an `Operand`.
+/// The arguments of the function are converted with `convert`.
+fn call_to_function<F>(call: &ExprCall, errors: &mut Vec<Error>, convert: F) -> Operand
+where F: Fn(&Expression, &mut Vec<Error>) -> Operand
+{
+    let name = function_name(call).expect("function name");
+    let argument_count = functions_singleton()[&name.to_string()].argument_types.len();
+    check_argument_count(&call.args, argument_count, call.span(), errors);
+    let mut arguments = vec![];
+    for arg in &call.args {
+        arguments.push(convert(arg, errors));
+    }
+    Operand::Function(SqlFunction {
+        arguments,
+        name,
+    })
+}
+
+/// Check that the intervals are only added to or subtracted from a date.
+pub fn check_intervals(operand: &Operand, errors: &mut Vec<Error>) {
+    match *operand {
+        Operand::Arithmetic(ref operand1, operator, ref operand2) => {
+            check_intervals(operand1, errors);
+            match (operator, &**operand2) {
+                (ArithmeticOperator::Add, &Operand::Function(ref function)) |
+                    (ArithmeticOperator::Sub, &Operand::Function(ref function)) if operand2.is_interval() =>
+                    for argument in &function.arguments {
+                        check_intervals(argument, errors);
+                    },
+                _ => check_intervals(operand2, errors),
+            }
+        },
+        Operand::Expression(_) | Operand::Field(_, _) => (),
+        Operand::Function(ref function) => {
+            if operand.is_interval() {
+                errors.push(Error::new(
+                    "an interval can only be added to or subtracted from a date",
+                    function.name.span(),
+                ));
+            }
+            for argument in &function.arguments {
+                check_intervals(argument, errors);
+            }
+        },
+        Operand::Paren(ref operand) => check_intervals(operand, errors),
+    }
+}
+
+/// Convert a Rust expression to an `Operand`.
+pub fn expression_to_operand(expr: &Expression, table_name: &str) -> Result<Operand> {
+    let mut errors = vec![];
+    let operand = to_operand(expr, table_name, &mut errors);
+    check_intervals(&operand, &mut errors);
+    res(operand, errors)
+}
+
+/// Get the name of the function called in `call` if it is evaluated by the database.
+fn function_name(call: &ExprCall) -> Option<Ident> {
+    if let Expr::Path(ref path) = *call.func {
+        if path.qself.is_none() && path.path.segments.len() == 1 {
+            let ident = &path.path.segments.first().expect("first segment in path").value().ident;
+            if functions_singleton().contains_key(&ident.to_string()) {
+                return Some(ident.clone());
+            }
+        }
+    }
+    None
+}
+
+/// Check if the expression contains a call to a function evaluated by the database, possibly in
+/// an arithmetic expression.
+fn has_function_call(expr: &Expression) -> bool {
+    match *expr {
+        Expr::Binary(ref bin) if is_arithmetic_operator(&bin.op) =>
+            has_function_call(&bin.left) || has_function_call(&bin.right),
+        Expr::Paren(ref paren) => has_function_call(&paren.expr),
+        _ => is_function_call(expr),
+    }
+}
+
+/// Check if the expression is a call to a function evaluated by the database.
+pub fn is_function_call(expr: &Expression) -> bool {
+    if let Expr::Call(ref call) = *expr {
+        function_name(call).is_some()
+    }
+    else {
+        false
+    }
+}
+
+/// Convert a Rust expression to an `Operand`.
+/// The expression is kept as is (and sent as a parameter) when it does not reference any field
+/// nor call any function evaluated by the database.
+fn to_operand(expr: &Expression, table_name: &str, errors: &mut Vec<Error>) -> Operand {
+    if !has_field_reference(expr) && !has_function_call(expr) {
+        return Operand::Expression(expr.clone());
+    }
+    match *expr {
+        Expr::Binary(ref bin) if is_arithmetic_operator(&bin.op) => {
+            let operand1 = to_operand(&bin.left, table_name, errors);
+            let operand2 = to_operand(&bin.right, table_name, errors);
+            Operand::Arithmetic(Box::new(operand1), binop_to_arithmetic_operator(&bin.op), Box::new(operand2))
+        },
+        Expr::Paren(ref paren) => Operand::Paren(Box::new(to_operand(&paren.expr, table_name, errors))),
+        Expr::Call(ref call) if is_function_call(expr) =>
+            call_to_function(call, errors, |arg, errors| to_operand(arg, table_name, errors)),
+        _ =>
+            if let Some(field) = field_reference(expr) {
+                Operand::Field(table_name.to_string(), field)
+            }
+            else {
+                errors.push(Error::new(
+                    "field references can only be combined with arithmetic operators",
+                    expr.span(),
+                ));
+                Operand::Expression(expr.clone())
+            },
+    }
+}
+
+/// Check that the `expr` is a number if it is a literal.
+pub fn check_numeric_literal(expr: &Expression, errors: &mut Vec<Error>) {
+    let numeric_types = [Type::I8, Type::I16, Type::I32, Type::I64, Type::F32, Type::F64];
+    if let Expr::Lit(_) = *expr {
+        if !numeric_types.iter().any(|typ| typ == expr) {
+            mismatched_types("number", &get_type(expr), expr.span(), errors);
+        }
+    }
+}
+
+/// Convert a Rust expression where the identifiers are fields, like the left side of a
+/// comparison or the argument of an aggregate, to an `Operand`.
+/// The other non-literal expressions, like `{variable}`, are sent as parameters.
+pub fn field_expression_to_operand(expr: &Expression, table_name: &str, errors: &mut Vec<Error>) -> Operand {
+    match *expr {
+        Expr::Binary(ref bin) if is_arithmetic_operator(&bin.op) => {
+            let operand1 = field_expression_to_operand(&bin.left, table_name, errors);
+            let operand2 = field_expression_to_operand(&bin.right, table_name, errors);
+            Operand::Arithmetic(Box::new(operand1), binop_to_arithmetic_operator(&bin.op), Box::new(operand2))
+        },
+        Expr::Paren(ref paren) =>
+            Operand::Paren(Box::new(field_expression_to_operand(&paren.expr, table_name, errors))),
+        Expr::Call(ref call) if is_function_call(expr) =>
+            call_to_function(call, errors, |arg, errors| field_expression_to_operand(arg, table_name, errors)),
+        Expr::Path(ref path) if path.qself.is_none() && path.path.segments.len() == 1 => {
+            let field = path.path.segments.first().expect("first segment in path").value().ident.clone();
+            Operand::Field(table_name.to_string(), field)
+        },
+        _ => {
+            if has_field_reference(expr) {
+                let mut error = Error::new(
+                    "field references are not allowed in this expression",
+                    expr.span(),
+                );
+                error.add_help("remove the $ before the field name");
+                errors.push(error);
+            }
+            Operand::Expression(expr.clone())
+        },
+    }
+}
+
+/// Get the fields referenced in the operand.
+fn get_fields_from_operand(operand: &Operand) -> Vec<Ident> {
+    operand.leaves().into_iter()
+        .filter_map(|leaf|
+            if let Operand::Field(_, ref field) = *leaf {
+                Some(field.clone())
+            }
+            else {
+                None
+            }
+        )
+        .collect()
+}
+
+/// Get the fields referenced in the filter with the field they are compared to.
+fn get_field_references_from_filter(filter: &FilterExpression) -> Vec<(Ident, Ident)> {
+    let mut references = vec![];
+    match *filter {
+        FilterExpression::Filter(ref filter) => {
+            if let Some(identifier) = filter.operand1.identifier() {
+                for field in get_fields_from_operand(&filter.operand2) {
+                    references.push((identifier.clone(), field));
+                }
+            }
+        },
+        FilterExpression::Filters(ref filters) => {
+            references.extend(get_field_references_from_filter(&filters.operand1));
+            references.extend(get_field_references_from_filter(&filters.operand2));
+        },
+        FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) =>
+            references.extend(get_field_references_from_filter(filter)),
+        FilterExpression::Exists(_) | FilterExpression::FilterValue(_) | FilterExpression::InSubquery(_) |
+            FilterExpression::ManyToMany(_) | FilterExpression::NoFilters => (),
+    }
+    references
+}
+
+/// Get the fields referenced in the query with the field they are compared or assigned to.
+pub fn get_field_references(query: &Query) -> Vec<(Ident, Ident)> {
+    let mut references = vec![];
+    match *query {
+        Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } | Query::Select { ref filter, .. } =>
+            references.extend(get_field_references_from_filter(filter)),
+        Query::Update { ref assignments, ref filter, .. } => {
+            references.extend(get_field_references_from_filter(filter));
+            for assignment in assignments {
+                if let Some(ref identifier) = assignment.identifier {
+                    for field in get_fields_from_operand(&assignment.value) {
+                        references.push((identifier.clone(), field));
+                    }
+                }
+            }
+        },
+        Query::CreateTable { .. } | Query::Drop { .. } | Query::Insert { .. } => (),
+    }
+    references
+}
+
+/// Get the fields compared to or assigned a value computed by a function evaluated by the database
+/// with this value.
+fn get_function_operands_from_filter(filter: &FilterExpression) -> Vec<(Operand, Operand)> {
+    let mut operands = vec![];
+    match *filter {
+        FilterExpression::Filter(ref filter) => {
+            if let FilterValue::Identifier(ref table, ref identifier) = filter.operand1 {
+                if has_function(&filter.operand2) {
+                    operands.push((Operand::Field(table.clone(), identifier.clone()), filter.operand2.clone()));
+                }
+            }
+        },
+        FilterExpression::Filters(ref filters) => {
+            operands.extend(get_function_operands_from_filter(&filters.operand1));
+            operands.extend(get_function_operands_from_filter(&filters.operand2));
+        },
+        FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) =>
+            operands.extend(get_function_operands_from_filter(filter)),
+        FilterExpression::Exists(_) | FilterExpression::FilterValue(_) | FilterExpression::InSubquery(_) |
+            FilterExpression::ManyToMany(_) | FilterExpression::NoFilters => (),
+    }
+    operands
+}
+
+/// Get the fields compared to or assigned a value computed by a function evaluated by the database
+/// with this value.
+/// These pairs are type-checked like the arithmetic filters.
+pub fn get_function_operands(query: &Query) -> Vec<(Operand, Operand)> {
+    let mut operands = vec![];
+    match *query {
+        Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } | Query::Select { ref filter, .. } =>
+            operands.extend(get_function_operands_from_filter(filter)),
+        Query::Insert { ref assignments, ref table } | Query::Update { ref assignments, ref table, .. } => {
+            if let Query::Update { ref filter, .. } = *query {
+                operands.extend(get_function_operands_from_filter(filter));
+            }
+            for assignment in assignments {
+                if let Some(ref identifier) = assignment.identifier {
+                    if has_function(&assignment.value) {
+                        operands.push((Operand::Field(table.clone(), identifier.clone()), assignment.value.clone()));
+                    }
+                }
+            }
+        },
+        Query::CreateTable { .. } | Query::Drop { .. } => (),
+    }
+    operands
+}
+
+/// Check if the operand calls a function evaluated by the database.
+fn has_function(operand: &Operand) -> bool {
+    operand.leaves().into_iter().any(|leaf| matches!(*leaf, Operand::Function(_)))
+}
+
+/// Check if the expression is an arithmetic operation, possibly in parentheses.
+pub fn is_arithmetic_expression(expr: &Expression) -> bool {
+    match *expr {
+        Expr::Binary(ref bin) => is_arithmetic_operator(&bin.op),
+        Expr::Paren(ref paren) => is_arithmetic_expression(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Check if a `BinOp` is an `ArithmeticOperator`.
+fn is_arithmetic_operator(binop: &BinOp) -> bool {
+    matches!(*binop, BinOp::Add(_) | BinOp::Div(_) | BinOp::Rem(_) | BinOp::Mul(_) | BinOp::Sub(_))
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the after(), before() and paginate() methods.
+
+use proc_macro2::Span;
+use syn::Ident;
+
+use ast::{
+    Limit,
+    Nulls,
+    Order,
+    OrderValue,
+    Pagination,
+    Projection,
+    Query,
+};
+use error::Error;
+use parser::MethodCall;
+use string::plural_verb;
+use super::QueryData;
+
+/// Convert the `method_call` to a `Pagination`.
+pub fn method_call_to_pagination(method_call: &MethodCall, errors: &mut Vec<Error>) -> Pagination {
+    let name = method_call.name.to_string();
+    if name == "paginate" {
+        return Pagination::First;
+    }
+    if method_call.args.len() != 1 {
+        let length = method_call.args.len();
+        errors.push(Error::new_with_code(
+            &format!("this method takes 1 parameter but {} parameter{} supplied", length, plural_verb(length)),
+            method_call.name.span(), "E0061"
+        ));
+        return Pagination::Unpaginated;
+    }
+    let cursor = method_call.args[0].clone();
+    if name == "after" {
+        Pagination::After(cursor)
+    }
+    else {
+        Pagination::Before(cursor)
+    }
+}
+
+/// Check that the rows paginated by the `method` are sorted by fields of the table and that the
+/// other methods allow to get the cursor from the last row.
+pub fn check_pagination(method: &str, position: Span, query_data: &QueryData, errors: &mut Vec<Error>) {
+    if query_data.order.is_empty() {
+        let mut error = Error::new(&format!("cannot call the {}() method on rows which are not sorted", method),
+            position);
+        error.add_help("sort the rows by unique fields with the sort() method");
+        errors.push(error);
+    }
+    // NOTE: the cursor is compared to the values of the fields, which is only possible with the
+    // fields of the table since NULL values cannot be compared.
+    // The nullable fields of the table are rejected by the code generator since their type is unknown here.
+    for order in &query_data.order {
+        match *order {
+            Order::Ascending(OrderValue::Field(ref field), nulls) |
+                Order::Descending(OrderValue::Field(ref field), nulls) =>
+                if field.foreign_key.is_some() || nulls != Nulls::Unspecified {
+                    errors.push(Error::new(
+                        &format!("cannot call the {}() method on rows sorted by `{}`", method, field.field),
+                        field.field.span(),
+                    ));
+                },
+            Order::Ascending(OrderValue::MethodCall(ref call), _) |
+                Order::Descending(OrderValue::MethodCall(ref call), _) =>
+                errors.push(Error::new(&format!("cannot call the {}() method on rows sorted by a method call", method),
+                    call.position)),
+            Order::NoOrder => (),
+        }
+    }
+    // The cursor is taken from the fields of the table struct.
+    match query_data.projection {
+        Projection::All => (),
+        Projection::Fields(_) | Projection::Struct(_) =>
+            errors.push(Error::new(&format!("cannot call the {}() method with the only() method", method), position)),
+    }
+    if !query_data.annotations.is_empty() {
+        errors.push(Error::new(&format!("cannot call the {}() method with the annotate() method", method), position));
+    }
+    if !query_data.combinations.is_empty() {
+        errors.push(Error::new(
+            &format!("cannot call the {}() method with the union(), intersect() or except() methods", method),
+            position,
+        ));
+    }
+    if let Limit::Index(_) = query_data.limit {
+        errors.push(Error::new(&format!("cannot call the {}() method when getting a single row", method), position));
+    }
+}
+
+/// Get the fields whose values are in the cursor of a paginated query.
+pub fn get_cursor_fields(query: &Query) -> Vec<Ident> {
+    match *query {
+        Query::Select { pagination: Pagination::Unpaginated, .. } => vec![],
+        Query::Select { ref order, .. } =>
+            order.iter()
+                .filter_map(|order| order.field())
+                .map(|field| field.field.clone())
+                .collect(),
+        _ => vec![],
+    }
+}
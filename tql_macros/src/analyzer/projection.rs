@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the only() method.
+
+use syn::{GenericMethodArgument, Ident, Type, TypePath};
+use syn::spanned::Spanned;
+
+use ast::{Projection, Query};
+use error::{Error, Result, res};
+use parser::MethodCall;
+use super::path_expr_to_identifier;
+
+/// Convert the arguments of the only() method to a `Projection`.
+pub fn method_call_to_projection(method_call: &MethodCall) -> Result<Projection> {
+    let mut errors = vec![];
+    let projection =
+        if let Some(ref turbofish) = method_call.turbofish {
+            if !method_call.args.is_empty() {
+                errors.push(Error::new(
+                    "only() takes either a struct type or fields, not both",
+                    method_call.args[0].span(),
+                ));
+            }
+            let struct_ident =
+                if turbofish.args.len() == 1 {
+                    match **turbofish.args.first().expect("first type argument").value() {
+                        GenericMethodArgument::Type(Type::Path(TypePath { qself: None, ref path }))
+                            if path.segments.len() == 1 =>
+                            Some(path.segments.first().expect("first segment in path").value().ident.clone()),
+                        _ => None,
+                    }
+                }
+                else {
+                    None
+                };
+            match struct_ident {
+                Some(ident) => Projection::Struct(ident),
+                None => {
+                    errors.push(Error::new("expected struct name", turbofish.args.span()));
+                    Projection::All
+                },
+            }
+        }
+        else if method_call.args.is_empty() {
+            errors.push(Error::new_with_code(
+                "this method takes at least 1 parameter but 0 parameters were supplied",
+                method_call.position,
+                "E0061",
+            ));
+            Projection::All
+        }
+        else {
+            let fields = method_call.args.iter()
+                .filter_map(|arg| path_expr_to_identifier(arg, &mut errors))
+                .collect();
+            Projection::Fields(fields)
+        };
+    res(projection, errors)
+}
+
+/// Get the fields selected by only() to be able to check that they exist.
+pub fn get_projection_idents(query: &Query) -> Vec<Ident> {
+    if let Query::Select { projection: Projection::Fields(ref fields), .. } = *query {
+        return fields.clone();
+    }
+    vec![]
+}
@@ -23,67 +23,156 @@
 
 use syn::{
     Expr,
+    ExprMethodCall,
     ExprUnary,
-    Ident,
     UnOp,
 };
+use syn::spanned::Spanned;
 
 use ast::{
+    self,
     Expression,
+    FieldPath,
+    Nulls,
     Order,
+    OrderValue,
     Query,
     first_token_span,
 };
 use error::{Error, Result, res};
-use super::path_expr_to_identifier;
+use string::plural_verb;
+use super::expr_to_field_path;
 
 /// Convert an `Expression` to an `Order`.
 pub fn argument_to_order(arg: &Expression) -> Result<Order> {
     let mut errors = vec![];
-    let order =
+    let (expr, descending) =
         match *arg {
-            Expr::Unary(ExprUnary { op: UnOp::Neg(_), ref expr, .. }) => {
-                let ident = get_identifier(expr)?;
-                Order::Descending(ident)
-            }
-            Expr::Path(ref path) => {
-                let identifier = path.path.segments.first().unwrap().into_value().ident.clone();
-                Order::Ascending(identifier)
-            }
+            Expr::Unary(ExprUnary { op: UnOp::Neg(_), ref expr, .. }) => (&**expr, true),
+            _ => (arg, false),
+        };
+    let (expr, nulls) =
+        match *expr {
+            Expr::MethodCall(ref call) if call.method == "nulls_first" || call.method == "nulls_last" => {
+                if !call.args.is_empty() {
+                    let length = call.args.len();
+                    errors.push(Error::new_with_code(
+                        &format!("this method takes 0 parameters but {} parameter{} supplied", length,
+                                 plural_verb(length)),
+                        call.method.span(), "E0061"
+                    ));
+                }
+                let nulls =
+                    if call.method == "nulls_first" {
+                        Nulls::First
+                    }
+                    else {
+                        Nulls::Last
+                    };
+                (&*call.receiver, nulls)
+            },
+            _ => (expr, Nulls::Unspecified),
+        };
+    let value =
+        match *expr {
+            Expr::Field(_) | Expr::Path(_) => Some(OrderValue::Field(get_field_path(expr)?)),
+            Expr::MethodCall(ref call) => method_call_to_order_value(call, &mut errors),
             _ => {
                 errors.push(Error::new(
                     "Expected - or identifier",
                     first_token_span(arg),
                 ));
-                Order::NoOrder
+                None
             }
         };
+    let order =
+        match value {
+            Some(value) =>
+                if descending {
+                    Order::Descending(value, nulls)
+                }
+                else {
+                    Order::Ascending(value, nulls)
+                },
+            None => Order::NoOrder,
+        };
     res(order, errors)
 }
 
-/// Get the identifier from an `Expression`.
-fn get_identifier(identifier_expr: &Expression) -> Result<Ident> {
+/// Get the field from an `Expression`.
+fn get_field_path(identifier_expr: &Expression) -> Result<FieldPath> {
     let mut errors = vec![];
-    if let Some(identifier) = path_expr_to_identifier(identifier_expr, &mut errors) {
-        res(identifier, errors)
+    if let Some(field) = expr_to_field_path(identifier_expr, &mut errors) {
+        res(field, errors)
     }
     else {
         Err(errors)
     }
 }
 
+/// Convert a method call like `field.year()` to an `OrderValue`.
+fn method_call_to_order_value(call: &ExprMethodCall, errors: &mut Vec<Error>) -> Option<OrderValue> {
+    let object_name =
+        match *call.receiver {
+            Expr::Path(ref path) if path.path.segments.len() == 1 =>
+                path.path.segments.first().unwrap().into_value().ident.clone(),
+            _ => {
+                errors.push(Error::new("expected a field of the table", call.receiver.span()));
+                return None;
+            },
+        };
+    // NOTE: the arguments are not sent as query parameters since they are in the ORDER BY clause.
+    for argument in &call.args {
+        match *argument {
+            Expr::Lit(_) => (),
+            _ => errors.push(Error::new("expected a literal argument in the sort() method", argument.span())),
+        }
+    }
+    Some(OrderValue::MethodCall(ast::MethodCall {
+        arguments: call.args.iter().cloned().collect(),
+        method_name: call.method.clone(),
+        object_name,
+        position: call.span(),
+    }))
+}
+
+/// Get the method calls in the order by clauses of the query and of its windows to be able to check
+/// their types.
+pub fn get_order_method_calls(query: &Query) -> Vec<ast::MethodCall> {
+    let (order, annotations) =
+        match *query {
+            Query::Aggregate { ref annotations, .. } => (&[][..], annotations),
+            Query::Select { ref annotations, ref order, .. } => (&order[..], annotations),
+            _ => return vec![],
+        };
+    let orders = order.iter()
+        .chain(annotations.iter().flat_map(|annotation| annotation.order.iter()));
+    let mut calls = vec![];
+    for order in orders {
+        if let Order::Ascending(OrderValue::MethodCall(ref call), _) |
+            Order::Descending(OrderValue::MethodCall(ref call), _) = *order
+        {
+            calls.push(call.clone());
+        }
+    }
+    calls
+}
+
 /// Get the identifier in the order by clause to be able to check that they exist.
-pub fn get_sort_idents(query: &Query) -> Vec<Ident> {
+pub fn get_sort_idents(query: &Query) -> Vec<FieldPath> {
     let mut idents = vec![];
     if let Query::Select { ref order, ..} = *query {
         for order in order {
             let ident =
                 match *order {
-                    Order::Ascending(ref ident) => ident,
-                    Order::Descending(ref ident) => ident,
+                    Order::Ascending(OrderValue::Field(ref ident), _) |
+                        Order::Descending(OrderValue::Field(ref ident), _) => ident.clone(),
+                    Order::Ascending(OrderValue::MethodCall(ref call), _) |
+                        Order::Descending(OrderValue::MethodCall(ref call), _) =>
+                        FieldPath::new(call.object_name.clone()),
                     Order::NoOrder => continue,
                 };
-            idents.push(ident.clone());
+            idents.push(ident);
         }
     }
     idents
@@ -0,0 +1,144 @@
+/*
+ * Copyright (c) 2017-2018 Boucher, Antoni <bouanto@zoho.com>
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of
+ * this software and associated documentation files (the "Software"), to deal in
+ * the Software without restriction, including without limitation the rights to
+ * use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+ * the Software, and to permit persons to whom the Software is furnished to do so,
+ * subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+ * FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+ * COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+ * IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+ * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+//! Analyzer for the nested queries used in filters.
+
+use syn::{Expr, Ident};
+use syn::spanned::Spanned;
+
+use ast::{
+    Expression,
+    FilterExpression,
+    Projection,
+    Query,
+    SelectResult,
+    Subquery,
+};
+use error::{Error, Result, res};
+use optimizer::optimize;
+use parser::Parser;
+use super::{analyze, analyze_methods, analyze_types, get_methods};
+
+/// Check if the expression is a query like `Table.filter(...)`, i.e. a chain of query methods
+/// called on a table name.
+pub fn is_subquery(expr: &Expression) -> bool {
+    let methods = get_methods();
+    let mut expr = expr;
+    let mut has_call = false;
+    loop {
+        match *expr {
+            Expr::MethodCall(ref call) if methods.contains(&call.method.to_string()) => {
+                has_call = true;
+                expr = &call.receiver;
+            },
+            Expr::Index(ref index) => expr = &index.expr,
+            Expr::Path(ref path) => {
+                // NOTE: the tables are structs, so their name starts with an uppercase letter.
+                return has_call && path.path.segments.len() == 1 &&
+                    path.path.segments.first().expect("first segment in path").value().ident.to_string()
+                        .starts_with(|character: char| character.is_uppercase());
+            },
+            _ => return false,
+        }
+    }
+}
+
+/// Convert the expression to a `Subquery` by running the whole analysis on it.
+pub fn expression_to_subquery(expr: &Expression) -> Result<Subquery> {
+    let calls = Parser::new().parse(expr)?;
+    let mut query = analyze(&calls)?;
+    analyze_methods(&query)?;
+    optimize(&mut query);
+    analyze_types(&query)?;
+    let mut errors = vec![];
+    match query {
+        Query::Select { ref annotations, .. } if !annotations.is_empty() =>
+            errors.push(Error::new("annotated queries cannot be used as subqueries", expr.span())),
+        Query::Select { result: SelectResult::Rows, .. } => (),
+        _ => errors.push(Error::new("only select queries can be used as subqueries", expr.span())),
+    }
+    let table = calls.name.clone().expect("table name in method_calls");
+    res(Subquery {
+        calls,
+        query: Box::new(query),
+        table,
+    }, errors)
+}
+
+/// Get the field selected by the `subquery`, which is compared to a field in `is_in()`.
+pub fn get_selected_field(subquery: &Subquery, errors: &mut Vec<Error>) -> Option<Ident> {
+    if let Query::Select { projection: Projection::Fields(ref fields), .. } = *subquery.query {
+        if fields.len() == 1 {
+            return Some(fields[0].clone());
+        }
+    }
+    let mut error = Error::new("the subquery must select exactly one field", subquery.calls.position);
+    error.add_help("use only() to select the field to compare to");
+    errors.push(error);
+    None
+}
+
+/// Get the fields compared to the field selected by a subquery with the table and the field
+/// selected by this subquery.
+/// The fields selected by the queries combined with the main query are also compared to the fields
+/// it selects.
+pub fn get_subquery_fields(query: &Query) -> Vec<(Ident, Ident, Ident)> {
+    match *query {
+        Query::Select { ref combinations, ref filter, ref projection, ref table, .. } => {
+            let mut fields = get_subquery_fields_from_filter(filter);
+            if let Projection::Fields(ref selected_fields) = *projection {
+                // NOTE: the fields of the same table have the same types.
+                for combination in combinations.iter().filter(|combination| combination.query.table != table) {
+                    for field in selected_fields {
+                        fields.push((field.clone(), combination.query.table.clone(), field.clone()));
+                    }
+                }
+            }
+            fields
+        },
+        Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } | Query::Update { ref filter, .. } =>
+            get_subquery_fields_from_filter(filter),
+        Query::CreateTable { .. } | Query::Drop { .. } | Query::Insert { .. } =>
+            vec![],
+    }
+}
+
+fn get_subquery_fields_from_filter(filter: &FilterExpression) -> Vec<(Ident, Ident, Ident)> {
+    let mut fields = vec![];
+    match *filter {
+        FilterExpression::InSubquery(ref filter) => {
+            if let Query::Select { projection: Projection::Fields(ref selected_fields), .. } = *filter.subquery.query {
+                if let (Some(field), Some(selected_field)) = (filter.operand1.identifier(), selected_fields.first()) {
+                    fields.push((field.clone(), filter.subquery.table.clone(), selected_field.clone()));
+                }
+            }
+        },
+        FilterExpression::Filters(ref filters) => {
+            fields.extend(get_subquery_fields_from_filter(&filters.operand1));
+            fields.extend(get_subquery_fields_from_filter(&filters.operand2));
+        },
+        FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) =>
+            fields.extend(get_subquery_fields_from_filter(filter)),
+        FilterExpression::Exists(_) | FilterExpression::Filter(_) | FilterExpression::FilterValue(_) |
+            FilterExpression::ManyToMany(_) | FilterExpression::NoFilters => (),
+    }
+    fields
+}
@@ -27,6 +27,7 @@ use syn::{
     parse,
 };
 
+use analyzer::get_cursor_fields;
 use ast::{
     Aggregate,
     AggregateFilterExpression,
@@ -35,29 +36,48 @@ use ast::{
     FilterExpression,
     FilterValue,
     Limit,
+    ManyToManyFilter,
     MethodCall,
+    Operand,
+    Pagination,
     Query,
+    RelationalOperator,
+    Subquery,
 };
 
 /// A Rust expression to be send as a parameter to the SQL query function.
 #[derive(Clone, Debug)]
 pub struct Arg {
+    /// The fields of the `Cursor` values, when the expression is the cursor passed to `after()` or
+    /// `before()`.
+    pub cursor_fields: Vec<Ident>,
     pub expression: Expression,
     pub field_name: Option<Ident>,
-    pub field_name_prefix: Option<String>,
+    /// The `ForeignKey` field of the table containing the field, when it is from a joined table.
+    pub foreign_key: Option<Ident>,
+    /// Whether the expression is a slice or a `Vec` whose elements are compared to the field.
+    pub list: bool,
+    /// The `ManyToMany` field of the table containing the field, when it is from `any()`.
+    pub many_to_many: Option<Ident>,
+    /// The table containing the field, when it is from a subquery.
+    pub table: Option<Ident>,
 }
 
 /// A collection of `Arg`s.
 pub type Args = Vec<Arg>;
 
 /// Create an argument from the parameters and add it to `arguments`.
-fn add(arguments: &mut Args, literals: &mut Args, field_name: Option<Ident>, field_name_prefix: Option<String>,
+fn add(arguments: &mut Args, literals: &mut Args, field_name: Option<Ident>, foreign_key: Option<Ident>,
        expr: Expression)
 {
     add_expr(arguments, literals, Arg {
+        cursor_fields: vec![],
         expression: expr,
-        field_name_prefix,
         field_name,
+        foreign_key,
+        list: false,
+        many_to_many: None,
+        table: None,
     });
 }
 
@@ -66,7 +86,7 @@ fn add_assignments(assignments: Vec<Assignment>, arguments: &mut Args, literals:
     for assign in assignments {
         let field_name = assign.identifier.expect("Assignment identifier");
         // NOTE: At this stage (code generation), the field exists, hence unwrap().
-        add(arguments, literals, Some(field_name), None, assign.value);
+        add_operand_arguments(arguments, literals, Some(field_name), None, assign.value);
     }
 }
 
@@ -84,7 +104,13 @@ fn add_expr(arguments: &mut Args, literals: &mut Args, arg: Arg) {
 fn add_filter_arguments(filter: FilterExpression, args: &mut Args, literals: &mut Args) {
     match filter {
         FilterExpression::Filter(filter) => {
-            add_filter_value_arguments(&filter.operand1, args, literals, Some(filter.operand2));
+            match filter.operator {
+                RelationalOperator::In | RelationalOperator::NotIn =>
+                    if let Operand::Expression(expr) = filter.operand2 {
+                        add_list_arguments(&filter.operand1, args, expr);
+                    },
+                _ => add_filter_value_arguments(&filter.operand1, args, literals, Some(filter.operand2)),
+            }
         },
         FilterExpression::Filters(filters) => {
             add_filter_arguments(*filters.operand1, args, literals);
@@ -97,6 +123,15 @@ fn add_filter_arguments(filter: FilterExpression, args: &mut Args, literals: &mu
         FilterExpression::ParenFilter(filter) => {
             add_filter_arguments(*filter, args, literals);
         },
+        FilterExpression::Exists(subquery) => {
+            add_subquery_arguments(subquery, args, literals);
+        },
+        FilterExpression::InSubquery(filter) => {
+            add_subquery_arguments(filter.subquery, args, literals);
+        },
+        FilterExpression::ManyToMany(filter) => {
+            add_many_to_many_arguments(filter, args, literals);
+        },
         FilterExpression::FilterValue(filter_value) => {
             add_filter_value_arguments(&filter_value.node, args, literals, None);
         },
@@ -138,9 +173,13 @@ fn add_limit_arguments(limit: Limit, arguments: &mut Args, literals: &mut Args)
             let expression = parse((quote! { #expression2 - #offset }).into())
                 .expect("Subtraction quoted expression");
             add_expr(arguments, literals, Arg {
+                cursor_fields: vec![],
                 expression,
                 field_name: None,
-                field_name_prefix: None,
+                foreign_key: None,
+                list: false,
+                many_to_many: None,
+                table: None,
             });
             add(arguments, literals, None, None, expression1);
         },
@@ -148,13 +187,83 @@ fn add_limit_arguments(limit: Limit, arguments: &mut Args, literals: &mut Args)
     }
 }
 
+/// Add the arguments of the `subquery`, whose fields are in the table of this subquery.
+fn add_subquery_arguments(subquery: Subquery, args: &mut Args, literals: &mut Args) {
+    let table = subquery.table;
+    let (subquery_args, subquery_literals) = arguments(*subquery.query);
+    let with_table = |mut arg: Arg| {
+        // The arguments of a nested subquery already have their table.
+        arg.table = arg.table.or_else(|| Some(table.clone()));
+        arg
+    };
+    args.extend(subquery_args.into_iter().map(&with_table));
+    literals.extend(subquery_literals.into_iter().map(&with_table));
+}
+
+/// Add the arguments of the filter of `field.any(filter)`, whose fields are in the table related by
+/// this `ManyToMany` field.
+fn add_many_to_many_arguments(filter: ManyToManyFilter, args: &mut Args, literals: &mut Args) {
+    let field = filter.field;
+    let mut filter_args = vec![];
+    let mut filter_literals = vec![];
+    add_filter_arguments(*filter.filter, &mut filter_args, &mut filter_literals);
+    let with_field = |mut arg: Arg| {
+        // NOTE: the arguments of a subquery are in the table of this subquery.
+        if arg.table.is_none() {
+            arg.many_to_many = Some(field.clone());
+        }
+        arg
+    };
+    args.extend(filter_args.into_iter().map(&with_field));
+    literals.extend(filter_literals.into_iter().map(&with_field));
+}
+
+/// Create a list argument compared to the field `filter_value` and add it to `args`.
+fn add_list_arguments(filter_value: &FilterValue, args: &mut Args, expr: Expression) {
+    if let FilterValue::Identifier(_, ref identifier) = *filter_value {
+        // NOTE: a list is never a literal, hence it is not added with add_expr().
+        args.push(Arg {
+            cursor_fields: vec![],
+            expression: expr,
+            field_name: Some(identifier.clone()),
+            foreign_key: None,
+            list: true,
+            many_to_many: None,
+            table: None,
+        });
+    }
+}
+
+/// Create arguments from the expressions of the `operand` and add them to `arguments`.
+/// The field references of the operand are not arguments since they are in the query.
+fn add_operand_arguments(arguments: &mut Args, literals: &mut Args, field_name: Option<Ident>,
+                         foreign_key: Option<Ident>, operand: Operand)
+{
+    for leaf in operand.leaves() {
+        match *leaf {
+            Operand::Expression(ref expr) =>
+                add(arguments, literals, field_name.clone(), foreign_key.clone(), expr.clone()),
+            // The arguments of a function do not have the type of the field.
+            Operand::Function(ref function) =>
+                for argument in &function.arguments {
+                    add_operand_arguments(arguments, literals, None, None, argument.clone());
+                },
+            Operand::Arithmetic(_, _, _) | Operand::Field(_, _) | Operand::Paren(_) => (),
+        }
+    }
+}
+
 /// Construct an argument from the method and add it to `args`.
 fn add_with_method(args: &mut Args, literals: &mut Args, expr: Expression)
 {
     add_expr(args, literals, Arg {
+        cursor_fields: vec![],
         expression: expr,
         field_name: None,
-        field_name_prefix: None,
+        foreign_key: None,
+        list: false,
+        many_to_many: None,
+        table: None,
     });
 }
 
@@ -167,25 +276,36 @@ fn add_aggregate_filter_value_arguments(_aggregate: &Aggregate, args: &mut Args,
 }
 
 fn add_filter_value_arguments(filter_value: &FilterValue, args: &mut Args, literals: &mut Args,
-                              expression: Option<Expression>)
+                              operand: Option<Operand>)
 {
     match *filter_value {
-        FilterValue::Identifier(ref table, ref identifier) => {
+        FilterValue::Arithmetic(ref operand1) => {
+            add_operand_arguments(args, literals, None, None, operand1.clone());
+            if let Some(operand) = operand {
+                add_operand_arguments(args, literals, None, None, operand);
+            }
+        },
+        FilterValue::Identifier(_, ref identifier) => {
             // It is possible to have an identifier without expression, when the identifier is a
             // boolean field name, hence this condition.
-            if let Some(expr) = expression {
-                add(args, literals, Some(identifier.clone()), Some(table.clone()), expr);
+            if let Some(operand) = operand {
+                add_operand_arguments(args, literals, Some(identifier.clone()), None, operand);
             }
         },
-        FilterValue::MethodCall(MethodCall { ref arguments, .. }) => {
+        FilterValue::MethodCall(_, MethodCall { ref arguments, .. }) => {
             for arg in arguments {
                 add_with_method(args, literals, arg.clone());
             }
         },
         FilterValue::None => unreachable!("FilterValue::None in add_filter_value_arguments()"),
-        FilterValue::PrimaryKey(ref table) => {
-            if let Some(expr) = expression {
-                add(args, literals, None, Some(table.clone()), expr);
+        FilterValue::PrimaryKey(_) => {
+            if let Some(operand) = operand {
+                add_operand_arguments(args, literals, None, None, operand);
+            }
+        },
+        FilterValue::RelatedIdentifier(_, ref foreign_key, ref identifier) => {
+            if let Some(operand) = operand {
+                add_operand_arguments(args, literals, Some(identifier.clone()), Some(foreign_key.clone()), operand);
             }
         },
     }
@@ -195,6 +315,7 @@ fn add_filter_value_arguments(filter_value: &FilterValue, args: &mut Args, liter
 pub fn arguments(query: Query) -> (Args, Args) {
     let mut arguments = vec![];
     let mut literals = vec![];
+    let cursor_fields = get_cursor_fields(&query);
 
     match query {
         Query::Aggregate { aggregate_filter, filter, .. } => {
@@ -209,8 +330,26 @@ pub fn arguments(query: Query) -> (Args, Args) {
         Query::Insert { assignments, .. } => {
             add_assignments(assignments, &mut arguments, &mut literals);
         },
-        Query::Select { filter, limit, ..} => {
+        Query::Select { combinations, filter, limit, pagination, ..} => {
             add_filter_arguments(filter, &mut arguments, &mut literals);
+            for combination in combinations {
+                add_subquery_arguments(combination.query, &mut arguments, &mut literals);
+            }
+            match pagination {
+                Pagination::After(cursor) | Pagination::Before(cursor) => {
+                    // NOTE: a cursor is never a literal, hence it is not added with add_expr().
+                    arguments.push(Arg {
+                        cursor_fields,
+                        expression: cursor,
+                        field_name: None,
+                        foreign_key: None,
+                        list: false,
+                        many_to_many: None,
+                        table: None,
+                    });
+                },
+                Pagination::First | Pagination::Unpaginated => (),
+            }
             add_limit_arguments(limit, &mut arguments, &mut literals);
         },
         Query::Update { assignments, filter, .. } => {
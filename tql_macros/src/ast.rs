@@ -27,13 +27,17 @@ use proc_macro2::{Span, TokenStream};
 use quote::ToTokens;
 use syn::{Expr, Ident};
 
+use parser::MethodCalls;
+use state::functions_singleton;
+
 pub type Expression = Expr;
-pub type Groups = Vec<Ident>;
+pub type Groups = Vec<FieldPath>;
 
 /// `Aggregate` for use in SQL Aggregate `Query`.
 #[derive(Clone, Debug, Default)]
 pub struct Aggregate {
-    pub field: Option<Ident>,
+    /// The field or arithmetic expression of fields the aggregate function is applied to.
+    pub argument: Option<Operand>,
     pub function: String,
     pub has_name_in_query: bool,
     pub result_name: Option<Ident>,
@@ -79,12 +83,48 @@ pub struct AggregateFilters {
     pub operand2: Box<AggregateFilterExpression>,
 }
 
+/// A window function call added to the rows by the annotate() method.
+#[derive(Clone, Debug)]
+pub struct Annotation {
+    /// The function call, represented like an aggregate call, with the name of the result in
+    /// `result_name`.
+    pub function: Aggregate,
+    /// The offset of lag() and lead().
+    pub offset: Option<Expression>,
+    pub order: Vec<Order>,
+    pub partition: Vec<FieldPath>,
+}
+
+/// `ArithmeticOperator` to combine `Operand`s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArithmeticOperator {
+    Add,
+    Divide,
+    Modulo,
+    Mul,
+    Sub,
+}
+
+impl ToTokens for ArithmeticOperator {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let op =
+            match *self {
+                ArithmeticOperator::Add => quote! { + },
+                ArithmeticOperator::Divide => quote! { / },
+                ArithmeticOperator::Modulo => quote! { % },
+                ArithmeticOperator::Mul => quote! { * },
+                ArithmeticOperator::Sub => quote! { - },
+            };
+        tokens.extend(op);
+    }
+}
+
 /// `Assignment` for use in SQL Insert and Update `Query`.
 #[derive(Debug)]
 pub struct Assignment {
     pub identifier: Option<Ident>,
     pub operator: WithSpan<AssignmentOperator>,
-    pub value: Expression,
+    pub value: Operand,
 }
 
 /// `AssignmentOperator` for use in SQL Insert and Update `Query`.
@@ -114,6 +154,13 @@ impl Display for AssignmentOperator {
     }
 }
 
+/// A select `Query` whose rows are combined with the rows of the main select `Query`.
+#[derive(Debug)]
+pub struct Combination {
+    pub operator: SetOperator,
+    pub query: Subquery,
+}
+
 /// `Filter` for SQL `Query` (WHERE clause).
 #[derive(Debug)]
 pub struct Filter {
@@ -121,15 +168,22 @@ pub struct Filter {
     pub operand1: FilterValue,
     /// The `operator` used to compare `operand1` to `operand2`.
     pub operator: RelationalOperator,
-    /// The expression to be compared to `operand1`.
-    pub operand2: Expression,
+    /// The operand to be compared to `operand1`.
+    pub operand2: Operand,
 }
 
-/// Either a single `Filter`, `Filters`, `NegFilter`, `NoFilters`, `ParenFilter` or a `FilterValue`.
+/// Either a single `Filter`, `Filters`, `NegFilter`, `NoFilters`, `ParenFilter`, a `FilterValue` or a
+/// filter using a `Subquery`.
 #[derive(Debug)]
 pub enum FilterExpression {
+    /// Comes from `Exists(Table.filter(...))`.
+    Exists(Subquery),
     Filter(Filter),
     Filters(Filters),
+    /// Comes from `field.is_in(Table.filter(...).only(field))` or `field.not_in(...)`.
+    InSubquery(SubqueryFilter),
+    /// Comes from `field.any(filter)` where `field` is a `ManyToMany`.
+    ManyToMany(ManyToManyFilter),
     NegFilter(Box<FilterExpression>),
     NoFilters,
     ParenFilter(Box<FilterExpression>),
@@ -153,13 +207,45 @@ pub struct Filters {
     pub operand2: Box<FilterExpression>,
 }
 
-/// Either an identifier or a method call.
+/// A field of the table or, as in `author.name`, of the table joined via a `ForeignKey` field.
+#[derive(Clone, Debug)]
+pub struct FieldPath {
+    pub field: Ident,
+    pub foreign_key: Option<Ident>,
+}
+
+impl FieldPath {
+    pub fn new(field: Ident) -> Self {
+        FieldPath {
+            field,
+            foreign_key: None,
+        }
+    }
+}
+
+/// Either an identifier, a method call or an arithmetic expression.
 #[derive(Debug)]
 pub enum FilterValue {
+    /// Comes from `field1 * field2`.
+    Arithmetic(Operand),
     None,
     Identifier(String, Ident),
-    MethodCall(MethodCall),
+    /// Comes from `field.method()`, the `String` being the name of the table.
+    MethodCall(String, MethodCall),
     PrimaryKey(String),
+    /// Comes from `foreign_key.field`, the `String` being the name of the base table.
+    RelatedIdentifier(String, Ident, Ident),
+}
+
+impl FilterValue {
+    /// Get the field identifier if the filter value is an identifier.
+    pub fn identifier(&self) -> Option<&Ident> {
+        match *self {
+            FilterValue::Identifier(_, ref identifier) => Some(identifier),
+            FilterValue::Arithmetic(_) | FilterValue::MethodCall(_, _) | FilterValue::None | FilterValue::PrimaryKey(_) |
+                FilterValue::RelatedIdentifier(_, _, _) => None,
+        }
+    }
 }
 
 /// A `Join` with another table via a specific `joined_field`.
@@ -167,6 +253,31 @@ pub enum FilterValue {
 pub struct Join {
     pub base_field: Ident,
     pub base_table: String,
+    /// Whether this join was added to reach the table of another join like `join(author.company)`.
+    pub implicit: bool,
+    pub join_type: JoinType,
+    /// The `ForeignKey` fields to follow from `base_table` to reach the table containing
+    /// `base_field`, like `author` in `join(author.company)`.
+    pub parent_fields: Vec<Ident>,
+}
+
+impl Join {
+    /// Get the `ForeignKey` fields to follow from `base_table` to reach the joined table.
+    pub fn path(&self) -> Vec<&Ident> {
+        self.parent_fields.iter()
+            .chain(Some(&self.base_field))
+            .collect()
+    }
+}
+
+/// The type of SQL join.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinType {
+    /// Comes from `join(field)`.
+    Inner,
+    /// Comes from `left_join(field)`.
+    /// The related rows without a match are `None`.
+    Left,
 }
 
 /// An SQL LIMIT clause.
@@ -200,6 +311,18 @@ pub enum LogicalOperator {
     Or,
 }
 
+/// A `ManyToManyFilter` checks whether one of the rows related by a `ManyToMany` field matches
+/// a filter.
+#[derive(Debug)]
+pub struct ManyToManyFilter {
+    /// The `ManyToMany` field.
+    pub field: Ident,
+    /// The filter on the fields of the related table.
+    pub filter: Box<FilterExpression>,
+    /// The name of the table containing the `field`.
+    pub table: String,
+}
+
 /// A method call is an abstraction of SQL function call.
 #[derive(Clone, Debug)]
 pub struct MethodCall {
@@ -209,23 +332,155 @@ pub struct MethodCall {
     pub position: Span,
 }
 
+/// The right-hand side of a `Filter` or an `Assignment`.
+#[derive(Clone, Debug)]
+pub enum Operand {
+    /// Comes from `operand1 + operand2`.
+    Arithmetic(Box<Operand>, ArithmeticOperator, Box<Operand>),
+    /// A Rust expression, sent as a query parameter.
+    Expression(Expression),
+    /// Comes from `$field`.
+    Field(String, Ident),
+    /// Comes from `now()`, `days(7)`, `trunc_day($date)`, ….
+    Function(SqlFunction),
+    /// Comes from `(operand)`.
+    Paren(Box<Operand>),
+}
+
+impl Operand {
+    /// Get the leaves of the operand, from left to right.
+    pub fn leaves(&self) -> Vec<&Operand> {
+        match *self {
+            Operand::Arithmetic(ref operand1, _, ref operand2) => {
+                let mut leaves = operand1.leaves();
+                leaves.extend(operand2.leaves());
+                leaves
+            },
+            Operand::Expression(_) | Operand::Field(_, _) | Operand::Function(_) => vec![self],
+            Operand::Paren(ref operand) => operand.leaves(),
+        }
+    }
+
+    /// Check if the operand is a call to a function creating an interval, like `days(7)`.
+    pub fn is_interval(&self) -> bool {
+        if let Operand::Function(ref function) = *self {
+            functions_singleton()[&function.name.to_string()].interval
+        }
+        else {
+            false
+        }
+    }
+
+    /// Check if the operand references a field, including in the arguments of the functions.
+    pub fn has_field(&self) -> bool {
+        self.leaves().into_iter().any(|leaf|
+            match *leaf {
+                Operand::Field(_, _) => true,
+                Operand::Function(ref function) => function.arguments.iter().any(Operand::has_field),
+                _ => false,
+            }
+        )
+    }
+}
+
+/// The keyset pagination of a `Select` `Query`.
+#[derive(Debug, Default)]
+pub enum Pagination {
+    /// Comes from `after(cursor)`.
+    After(Expression),
+    /// Comes from `before(cursor)`.
+    Before(Expression),
+    /// Comes from `paginate()`.
+    First,
+    #[default]
+    Unpaginated,
+}
+
+/// The placement of the NULL values in an ORDER BY clause.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Nulls {
+    /// Comes from `sort(field.nulls_first())`.
+    First,
+    /// Comes from `sort(field.nulls_last())`.
+    Last,
+    /// The default placement of the database.
+    Unspecified,
+}
+
 /// An SQL ORDER BY clause.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Order {
     /// Comes from `sort(field)`.
-    Ascending(Ident),
+    Ascending(OrderValue, Nulls),
     /// Comes from `sort(-field)`.
-    Descending(Ident),
+    Descending(OrderValue, Nulls),
     NoOrder,
 }
 
+impl Order {
+    /// Get the sorted field if the rows are sorted by a field.
+    pub fn field(&self) -> Option<&FieldPath> {
+        match *self {
+            Order::Ascending(OrderValue::Field(ref field), _) | Order::Descending(OrderValue::Field(ref field), _) =>
+                Some(field),
+            Order::Ascending(OrderValue::MethodCall(_), _) | Order::Descending(OrderValue::MethodCall(_), _) |
+                Order::NoOrder => None,
+        }
+    }
+
+    /// Get the order in the opposite direction.
+    pub fn reverse(&self) -> Order {
+        match *self {
+            Order::Ascending(ref value, nulls) => Order::Descending(value.clone(), nulls),
+            Order::Descending(ref value, nulls) => Order::Ascending(value.clone(), nulls),
+            Order::NoOrder => Order::NoOrder,
+        }
+    }
+}
+
+/// The value the rows are sorted by.
+#[derive(Clone, Debug)]
+pub enum OrderValue {
+    /// Comes from `sort(field)`.
+    Field(FieldPath),
+    /// Comes from `sort(field.method())`.
+    MethodCall(MethodCall),
+}
+
+/// The de-duplication of the rows of a `Select` `Query`.
+#[derive(Clone, Debug, Default)]
+pub enum Distinct {
+    /// Comes from `distinct()`.
+    All,
+    #[default]
+    Duplicates,
+    /// Comes from `distinct_on(field1, field2)`.
+    On(Vec<Ident>),
+}
+
+/// The fields selected by a `Select` `Query`.
+#[derive(Clone, Debug, Default)]
+pub enum Projection {
+    /// All the fields of the table.
+    #[default]
+    All,
+    /// Comes from `only(field1, field2)`.
+    Fields(Vec<Ident>),
+    /// Comes from `only::<Struct>()`.
+    Struct(Ident),
+}
+
 /// `RelationalOperator` to be used in a `Filter`.
 #[derive(Clone, Copy, Debug)]
 pub enum RelationalOperator {
     Equal,
+    /// Comes from `field.is_in(values)`.
+    In,
     LesserThan,
     LesserThanEqual,
     NotEqual,
+    /// Comes from `field.not_in(values)`.
+    NotIn,
     GreaterThan,
     GreaterThanEqual,
 }
@@ -236,6 +491,8 @@ pub enum Query {
     Aggregate {
         aggregates: Vec<Aggregate>,
         aggregate_filter: AggregateFilterExpression,
+        /// The window functions computed over the grouped rows.
+        annotations: Vec<Annotation>,
         filter: FilterExpression,
         groups: Groups,
         joins: Vec<Join>,
@@ -257,11 +514,21 @@ pub enum Query {
         table: String,
     },
     Select {
+        /// The window functions added to the rows by the annotate() method.
+        annotations: Vec<Annotation>,
+        /// The queries combined with the union(), intersect() or except() methods.
+        combinations: Vec<Combination>,
+        distinct: Distinct,
         filter: FilterExpression,
         get: bool,
         joins: Vec<Join>,
         limit: Limit,
         order: Vec<Order>,
+        pagination: Pagination,
+        projection: Projection,
+        /// The fields filled with the related rows of another table by the with() method.
+        related: Vec<Ident>,
+        result: SelectResult,
         table: String,
         use_pk: bool,
     },
@@ -277,11 +544,64 @@ pub enum Query {
 pub enum QueryType {
     AggregateMulti,
     AggregateOne,
+    Count,
     Create,
+    Drop,
     Exec,
+    Exists,
     InsertOne,
     SelectMulti,
     SelectOne,
+    SelectPage,
+}
+
+/// The value returned by a `Select` `Query`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SelectResult {
+    /// Comes from `count()`.
+    Count,
+    /// Comes from `exists()`.
+    Exists,
+    #[default]
+    Rows,
+}
+
+/// `SetOperator` to combine the rows of two select `Query`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetOperator {
+    /// Comes from `except(query)`.
+    Except,
+    /// Comes from `intersect(query)`.
+    Intersect,
+    /// Comes from `union(query)`.
+    Union,
+}
+
+/// A call to a function evaluated by the database, like `now()` or `days(7)`.
+#[derive(Clone, Debug)]
+pub struct SqlFunction {
+    pub arguments: Vec<Operand>,
+    pub name: Ident,
+}
+
+/// A nested select `Query` used as the operand of a filter or combined with another query.
+#[derive(Debug)]
+pub struct Subquery {
+    /// The method calls of the nested query, needed to generate the stable macro patterns.
+    pub calls: MethodCalls,
+    pub query: Box<Query>,
+    /// The identifier of the table of the nested query.
+    pub table: Ident,
+}
+
+/// A `SubqueryFilter` checks whether a field is in the rows returned by a `Subquery`.
+#[derive(Debug)]
+pub struct SubqueryFilter {
+    /// The field to search in the rows of the `subquery`.
+    pub operand1: FilterValue,
+    /// Either `In` or `NotIn`.
+    pub operator: RelationalOperator,
+    pub subquery: Subquery,
 }
 
 /// An SQL field with its type.
@@ -303,7 +623,9 @@ pub fn query_type(query: &Query) -> QueryType {
             }
         },
         Query::Insert { .. } => QueryType::InsertOne,
-        Query::Select { get, ref limit, .. } => {
+        Query::Select { result: SelectResult::Count, .. } => QueryType::Count,
+        Query::Select { result: SelectResult::Exists, .. } => QueryType::Exists,
+        Query::Select { pagination: Pagination::Unpaginated, get, ref limit, .. } => {
             let mut typ = QueryType::SelectMulti;
             if get {
                 typ = QueryType::SelectOne;
@@ -313,8 +635,10 @@ pub fn query_type(query: &Query) -> QueryType {
             }
             typ
         },
+        Query::Select { .. } => QueryType::SelectPage,
         Query::CreateTable { .. } => QueryType::Create,
-        Query::Delete { .. } | Query::Drop { .. } | Query::Update { .. } => QueryType::Exec,
+        Query::Drop { .. } => QueryType::Drop,
+        Query::Delete { .. } | Query::Update { .. } => QueryType::Exec,
     }
 }
 
@@ -24,18 +24,27 @@
 use std::collections::BTreeMap;
 use std::fmt::Write;
 
+use proc_macro2::Span;
 use syn::{
     self,
     AngleBracketedGenericArguments,
+    Attribute,
     Field,
+    Ident,
+    Lit,
+    Meta,
+    MetaNameValue,
+    NestedMeta,
     PathArguments,
     TypePath,
+    parse_str,
 };
 use syn::spanned::Spanned;
 
 use ast::WithSpan;
+use error::{Error, Result};
 use state::{BothTypes, SqlFields};
-use types::Type;
+use types::{Type, get_type_parameter};
 
 /// Convert a type from the Rust AST to the SQL `Type`.
 //#[allow(cmp_owned)]
@@ -67,10 +76,161 @@ pub fn field_ty_to_type(ty: &syn::Type) -> WithSpan<Type> {
     }
 }
 
+/// A field filled with the rows of another table.
+pub enum RelatedField {
+    /// Comes from #[sql(related = "Table.foreign_key")]: the rows of `table` having a
+    /// `foreign_key` to this table.
+    ForeignKey {
+        foreign_key: Ident,
+        table: Ident,
+    },
+    /// Comes from a `ManyToMany<Table>` field: the rows associated via a junction table.
+    ManyToMany(Junction),
+}
+
+/// The junction table of a `ManyToMany` field.
+pub struct Junction {
+    /// Whether the junction table is created and dropped with the table, i.e. it is not an
+    /// intermediate model declared with #[sql(through = "Table")].
+    pub auto_created: bool,
+    /// The column referencing the primary key of the table containing the field.
+    pub source: Ident,
+    pub table: Ident,
+    /// The column referencing the primary key of the `target_table`.
+    pub target: Ident,
+    pub target_table: Ident,
+}
+
+/// Get the table and the foreign key declared with #[sql(related = "Table.foreign_key")] on the
+/// `field` or the junction table of a `ManyToMany` field of the table `table_ident`.
+pub fn get_related_field(table_ident: &Ident, field: &Field) -> Result<Option<RelatedField>> {
+    let mut values = vec![];
+    let mut attr_span = None;
+    for attr in field.attrs.iter().filter(|attr| is_sql_attribute(attr) && !is_fulltext_attribute(attr)) {
+        attr_span = Some(attr.span());
+        match attr.parse_meta() {
+            Ok(Meta::List(ref list)) => {
+                for nested in &list.nested {
+                    if let NestedMeta::Meta(Meta::NameValue(MetaNameValue { ref ident, lit: Lit::Str(ref value), .. })) =
+                        *nested
+                    {
+                        values.push((ident.to_string(), value.value()));
+                    }
+                    else {
+                        return Err(vec![malformed_attribute(attr.span(), field)]);
+                    }
+                }
+            },
+            _ => return Err(vec![malformed_attribute(attr.span(), field)]),
+        }
+    }
+    let value = |name: &str| values.iter()
+        .find(|(ident, _)| ident == name)
+        .and_then(|(_, value)| parse_str::<Ident>(value).ok());
+
+    if let Some(target_table) = get_many_to_many_table(&field.ty) {
+        let field_ident = field.ident.as_ref().expect("field has name");
+        let span = field_ident.span();
+        let junction =
+            match attr_span {
+                None => Junction {
+                    auto_created: true,
+                    source: Ident::new("source", span),
+                    table: Ident::new(&format!("{}_{}", table_ident, field_ident), span),
+                    target: Ident::new("target", span),
+                    target_table,
+                },
+                Some(span) => {
+                    match (value("through"), value("source"), value("target")) {
+                        (Some(table), Some(source), Some(target)) if values.len() == 3 => Junction {
+                            auto_created: false,
+                            source: Ident::new(&source.to_string(), span),
+                            table: Ident::new(&table.to_string(), span),
+                            target: Ident::new(&target.to_string(), span),
+                            target_table,
+                        },
+                        _ => return Err(vec![malformed_attribute(span, field)]),
+                    }
+                },
+            };
+        return Ok(Some(RelatedField::ManyToMany(junction)));
+    }
+
+    if let Some(span) = attr_span {
+        let parts: Vec<_> = values.iter()
+            .filter(|(ident, _)| ident == "related")
+            .flat_map(|(_, value)| value.split('.')
+                .map(|part| parse_str::<Ident>(part).ok())
+                .collect::<Vec<_>>())
+            .collect();
+        if let (1, &[Some(ref table), Some(ref foreign_key)]) = (values.len(), &parts[..]) {
+            return Ok(Some(RelatedField::ForeignKey {
+                foreign_key: Ident::new(&foreign_key.to_string(), span),
+                table: Ident::new(&table.to_string(), span),
+            }));
+        }
+        return Err(vec![malformed_attribute(span, field)]);
+    }
+    Ok(None)
+}
+
+/// Get the table in the type `ManyToMany<Table>`.
+fn get_many_to_many_table(ty: &syn::Type) -> Option<Ident> {
+    if let syn::Type::Path(TypePath { ref path, .. }) = *ty {
+        let segment = path.segments.first().expect("first segment in path").into_value();
+        if segment.ident == "ManyToMany" {
+            return get_type_parameter(&segment.arguments)
+                .map(|table| Ident::new(&table, segment.ident.span()));
+        }
+    }
+    None
+}
+
+fn malformed_attribute(span: Span, field: &Field) -> Error {
+    let mut error = Error::new("malformed `sql` attribute", span);
+    if get_many_to_many_table(&field.ty).is_some() {
+        error.add_help("use #[sql(through = \"Table\", source = \"field\", target = \"field\")]");
+    }
+    else {
+        error.add_help("use #[sql(related = \"Table.foreign_key\")]");
+    }
+    error
+}
+
+/// Check if the attribute is #[sql(fulltext)].
+fn is_fulltext_attribute(attr: &Attribute) -> bool {
+    if let Ok(Meta::List(ref list)) = attr.parse_meta() {
+        if let (1, Some(&NestedMeta::Meta(Meta::Word(ref ident)))) =
+            (list.nested.len(), list.nested.first().map(|nested| nested.into_value()))
+        {
+            return ident == "fulltext";
+        }
+    }
+    false
+}
+
+/// Check if the `field` is indexed for the full-text search, i.e. it has a #[sql(fulltext)]
+/// attribute.
+pub fn is_fulltext_field(field: &Field) -> bool {
+    field.attrs.iter().any(|attr| is_sql_attribute(attr) && is_fulltext_attribute(attr))
+}
+
+/// Check if the `field` is filled with the rows of another table, i.e. it has a #[sql] attribute
+/// other than #[sql(fulltext)] or it is a `ManyToMany`.
+pub fn is_related_field(field: &Field) -> bool {
+    field.attrs.iter().any(|attr| is_sql_attribute(attr) && !is_fulltext_attribute(attr)) ||
+        get_many_to_many_table(&field.ty).is_some()
+}
+
+fn is_sql_attribute(attr: &Attribute) -> bool {
+    attr.path.segments.len() == 1 && attr.path.segments[0].ident == "sql"
+}
+
 /// Convert a vector of Rust struct fields to a collection of fields.
+/// The related fields are skipped since they are not columns of the table.
 pub fn fields_vec_to_hashmap(fields: &[Field]) -> SqlFields {
     let mut sql_fields = BTreeMap::new();
-    for field in fields {
+    for field in fields.iter().filter(|field| !is_related_field(field)) {
         if let Some(ref ident) = &field.ident {
             if !sql_fields.contains_key(ident) {
                 let ty = field_ty_to_type(&field.ty);
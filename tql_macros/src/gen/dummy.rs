@@ -51,6 +51,18 @@ impl BackendGen for DummyBackend {
         unreachable!("Enable one of the following features: sqlite, pg");
     }
 
+    fn junction_method(&self, _name: &Ident, _primary_key: &Ident, _target_table: &Ident, _query: &str) -> TokenStream {
+        unreachable!("Enable one of the following features: sqlite, pg");
+    }
+
+    fn params_expr(&self, _params: &[(TokenStream, bool)]) -> TokenStream {
+        unreachable!("Enable one of the following features: sqlite, pg");
+    }
+
+    fn related_rows_query(&self, _primary_key: &Ident, _field: &Ident, _table: &Ident, _query: TokenStream) -> TokenStream {
+        unreachable!("Enable one of the following features: sqlite, pg");
+    }
+
     fn row_type_ident(&self, _table_ident: &Ident) -> TokenStream {
         unreachable!("Enable one of the following features: sqlite, pg");
     }
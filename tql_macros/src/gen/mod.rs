@@ -50,10 +50,22 @@ use syn::token::Comma;
 
 use ast::{
     Aggregate,
+    Annotation,
     Join,
+    Projection,
     TypedField,
 };
-use attribute::{field_ty_to_type, fields_vec_to_hashmap};
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+use ast::QueryType;
+use attribute::{
+    Junction,
+    RelatedField,
+    field_ty_to_type,
+    fields_vec_to_hashmap,
+    get_related_field,
+    is_fulltext_field,
+    is_related_field,
+};
 use error::{Error, Result, res};
 use plugin::{new_ident, string_literal};
 #[cfg(feature = "postgres")]
@@ -62,21 +74,23 @@ use self::postgres::create_backend;
 use self::sqlite::create_backend;
 #[cfg(not(any(feature = "rusqlite", feature = "postgres")))]
 use self::dummy::create_backend;
-use sql::fields_to_sql;
+use sql::{fields_to_sql, fulltext_queries};
 use state::SqlFields;
 use string::token_to_string;
 use types::{
     Type,
-    get_type_parameter,
     get_type_parameter_as_path,
     type_to_sql,
 };
+#[cfg(feature = "unstable")]
+use bind_list_arguments;
 use {
     Arguments,
     SqlQueryWithArgs,
     add_error,
     concat_token_stream,
     empty_token_stream,
+    operand_to_tokens,
     typecheck_arguments,
 };
 
@@ -84,20 +98,15 @@ use {
 pub fn table_methods(item_struct: &ItemStruct) -> Tokens {
     let table_ident = &item_struct.ident;
     if let Fields::Named(FieldsNamed { ref named , .. }) = item_struct.fields {
-        let field_idents = named.iter()
-            .map(|field| field.ty.clone());
         let index = &mut 0;
-        let columns = field_idents.map(|typ| to_row_get(typ, false, index));
+        let columns = named.iter().map(|field| to_table_row_get(field, false, index));
 
-        let field_idents = named.iter()
-            .map(|field| field.ty.clone());
         let index = &mut 0;
-        let related_columns = field_idents.map(|typ| to_row_get(typ, true, index));
+        let related_columns = named.iter().map(|field| to_table_row_get(field, true, index));
 
         let field_count = named.iter()
             .filter(|field| {
-                let typ = token_to_string(&field.ty);
-                !typ.starts_with("ForeignKey")
+                related_table(&field.ty).is_none() && !is_related_field(field)
             })
             .count();
         let backend = create_backend();
@@ -111,6 +120,9 @@ pub fn table_methods(item_struct: &ItemStruct) -> Tokens {
         let trait_ident = quote_spanned! { table_ident.span() =>
             ::tql::SqlTable
         };
+        let tql_ident = quote_spanned! { table_ident.span() =>
+            ::tql
+        };
         let row_type_ident = backend.row_type_ident(&table_ident);
         let delta_type = backend.delta_type();
         let row_ident = Ident::new("__tql_item_row", Span::call_site());
@@ -131,10 +143,14 @@ pub fn table_methods(item_struct: &ItemStruct) -> Tokens {
                 }
 
                 #[allow(unused)]
-                fn from_related_row(#row_ident: &#row_type_ident, delta: #delta_type) -> Self {
-                    Self {
-                        #(#field_idents2: #related_columns,)*
+                fn from_related_row(#row_ident: &#row_type_ident, delta: #delta_type) -> Option<Self> {
+                    // NOTE: the related columns are all NULL when a left join has no match.
+                    if #tql_ident::is_null_row(#row_ident, delta, Self::FIELD_COUNT) {
+                        return None;
                     }
+                    Some(Self {
+                        #(#field_idents2: #related_columns,)*
+                    })
                 }
             }
         }
@@ -144,6 +160,83 @@ pub fn table_methods(item_struct: &ItemStruct) -> Tokens {
     }
 }
 
+/// Create the from_row() method for a struct filled by the only() method.
+pub fn row_methods(item_struct: &ItemStruct) -> Tokens {
+    let struct_ident = &item_struct.ident;
+    if let Fields::Named(FieldsNamed { ref named , .. }) = item_struct.fields {
+        let index = &mut 0;
+        let columns = named.iter()
+            .map(|field| to_row_get(field.ty.clone(), false, index));
+        let field_idents = named.iter()
+            .map(|field| field.ident.clone().expect("field has name"));
+
+        let trait_ident = quote_spanned! { struct_ident.span() =>
+            ::tql::SqlRow
+        };
+        let backend = create_backend();
+        let row_type_ident = backend.row_type_ident(struct_ident);
+        let row_ident = Ident::new("__tql_item_row", Span::call_site());
+
+        quote! {
+            unsafe impl #trait_ident for #struct_ident {
+                #[allow(unused)]
+                fn from_row(#row_ident: &#row_type_ident) -> Self {
+                    Self {
+                        #(#field_idents: #columns,)*
+                    }
+                }
+            }
+        }
+    }
+    else {
+        unreachable!("Check is done in sql_row()")
+    }
+}
+
+/// Create the macros to get the column list of a struct filled by the only() method and to check
+/// that its fields exist in the table struct.
+pub fn row_macro(item_struct: &ItemStruct) -> Tokens {
+    let struct_ident = &item_struct.ident;
+    if let Fields::Named(FieldsNamed { ref named , .. }) = item_struct.fields {
+        let field_idents: Vec<_> = named.iter()
+            .map(|field| field.ident.clone().expect("field has name"))
+            .collect();
+        let columns = field_idents.iter()
+            .enumerate()
+            .map(|(index, ident)| {
+                let column = format!(".{}", ident);
+                if index == 0 {
+                    quote! { $table, #column }
+                }
+                else {
+                    quote! { ", ", $table, #column }
+                }
+            });
+        let field_idents1 = &field_idents;
+        let field_idents2 = &field_idents;
+        let column_list_macro_name = Ident::new(&format!("tql_{}_column_list", struct_ident), Span::call_site());
+        let check_fields_macro_name = Ident::new(&format!("tql_{}_check_fields", struct_ident), Span::call_site());
+        quote_spanned! { struct_ident.span() =>
+            #[macro_export]
+            macro_rules! #column_list_macro_name {
+                ($table:expr) => { concat!(#(#columns),*) };
+            }
+
+            #[macro_export]
+            macro_rules! #check_fields_macro_name {
+                ($table:ident) => {
+                    let _ = |__tql_row: #struct_ident| {
+                        #($table.#field_idents1 = __tql_row.#field_idents2;)*
+                    };
+                };
+            }
+        }
+    }
+    else {
+        unreachable!("Check is done in sql_row()")
+    }
+}
+
 /// Add the postgres::types::ToSql implementation on the struct.
 /// Its SQL representation is the same as the primary key SQL representation.
 pub fn tosql_impl(item_struct: &ItemStruct, primary_key_field: Option<String>) -> Tokens {
@@ -167,8 +260,10 @@ pub fn tosql_impl(item_struct: &ItemStruct, primary_key_field: Option<String>) -
         #code
 
         impl #table_ident {
+            // NOTE: the type is generic to check the value of both a ForeignKey and an
+            // Option<ForeignKey>.
             #[allow(dead_code)]
-            pub fn #to_owned_ident(&self) -> Option<Self> {
+            pub fn #to_owned_ident<T: ::std::convert::From<::tql::ForeignKey<Self>>>(&self) -> T {
                 unimplemented!();
             }
         }
@@ -226,53 +321,229 @@ pub fn generate_errors(errors: Vec<Error>) -> TokenStream {
 
 /// Generate the Rust code from the SQL query.
 pub(crate) fn gen_query(args: &SqlQueryWithArgs, connection_expr: Tokens) -> (TokenStream, Vec<Tokens>) {
-    let struct_expr = create_struct(&args.table_name, &args.joins);
-    let (aggregate_struct, aggregate_expr) = gen_aggregate_struct(&args.aggregates);
+    let struct_expr = create_struct(&args.table_name, &args.joins, &args.projection, &args.annotations);
+    let (aggregate_struct, aggregate_expr) = gen_aggregate_struct(&args.aggregates, &args.annotations,
+        &args.table_name);
     let (args_expr, metavars) = typecheck_arguments(args);
     let backend = create_backend();
     let tokens = backend.gen_query_expr(connection_expr, args, args_expr, struct_expr, aggregate_struct,
                                         aggregate_expr);
+    #[cfg(feature = "unstable")]
+    let tokens = bind_list_arguments(args, tokens);
     (tokens.into(), metavars)
 }
 
+/// Create the value returned by a query selecting rows, after loading their related rows: the rows
+/// themselves or, for a paginated query, a page holding the rows and the cursor built from the last
+/// fetched one.
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+fn items_expr(args: &SqlQueryWithArgs, connection_expr: &Tokens) -> Tokens {
+    let items =
+        match args.query_type {
+            QueryType::SelectOne => quote! { ::std::slice::from_mut(&mut __tql_item) },
+            _ => quote! { &mut __tql_items[..] },
+        };
+    let prefetch_expr = prefetch_calls(args, connection_expr, items);
+    let items_expr =
+        match args.query_type {
+            QueryType::SelectOne => quote! { __tql_item },
+            QueryType::SelectPage => page_expr(args),
+            _ => quote! { __tql_items },
+        };
+    if args.related.is_empty() {
+        items_expr
+    }
+    else {
+        quote! {{
+            #prefetch_expr
+            #items_expr
+        }}
+    }
+}
+
+/// Create the page holding the rows and the cursor built from the last fetched one.
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+fn page_expr(args: &SqlQueryWithArgs) -> Tokens {
+    let fields = &args.cursor_fields;
+    let reverse =
+        if args.reverse_items {
+            quote! {
+                __tql_items.reverse();
+            }
+        }
+        else {
+            quote! {}
+        };
+    quote! {{
+        let __tql_cursor = __tql_items.last()
+            .map(|__tql_item| ::tql::Cursor::new((#(__tql_item.#fields.clone(),)*)));
+        #reverse
+        ::tql::Page {
+            cursor: __tql_cursor,
+            items: __tql_items,
+        }
+    }}
+}
+
+/// Create the calls to the macros loading the rows related to the `items` (a mutable slice of the
+/// table structs) for the fields passed to the with() method.
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+fn prefetch_calls(args: &SqlQueryWithArgs, connection_expr: &Tokens, items: Tokens) -> Tokens {
+    if args.related.is_empty() {
+        return quote! {};
+    }
+    let macro_name = Ident::new(&format!("tql_{}_prefetch", args.table_name), Span::call_site());
+    let calls = args.related.iter()
+        .map(|field| quote! {
+            #macro_name!(#field, #connection_expr, __tql_items);
+        });
+    quote! {{
+        let __tql_items: &mut [_] = #items;
+        #(#calls)*
+    }}
+}
+
+/// Create the expression of the parameters sent to the query.
+/// The boolean indicates whether the parameter is a list.
+pub fn params_expr(params: &[(Tokens, bool)]) -> Tokens {
+    let backend = create_backend();
+    backend.params_expr(params)
+}
+
 /// Create the struct expression needed by the generated code.
-fn create_struct(table_ident: &Ident, joins: &[Join]) -> Tokens {
+fn create_struct(table_ident: &Ident, joins: &[Join], projection: &Projection, annotations: &[Annotation]) -> Tokens {
     let row_ident = quote! { __tql_item_row };
+    let annotated_struct = create_annotated_struct(table_ident, annotations);
+    match *projection {
+        Projection::All => (),
+        Projection::Fields(ref fields) => {
+            let tuple = create_tuple(table_ident, fields);
+            if annotations.is_empty() {
+                return tuple;
+            }
+            let delta = create_backend().convert_index(fields.len());
+            return quote! {{
+                let item = #tuple;
+                let _tql_delta = #delta;
+                #annotated_struct
+            }};
+        },
+        Projection::Struct(ref struct_ident) => return quote_spanned! { struct_ident.span() =>
+            <#struct_ident as ::tql::SqlRow>::from_row(&#row_ident)
+        },
+    }
     let assign_related_fields =
         joins.iter()
             .map(|join| {
                 let ident = &join.base_field;
-                quote_spanned! { ident.span() => {
-                    let ref mut _related_field: Option<_> = item.#ident;
-                    _tql_delta += ::tql::from_related_row(_related_field, &#row_ident, _tql_delta);
-                }}
+                match join.parent_fields.split_first() {
+                    Some((first_parent, parents)) => {
+                        // NOTE: the parent is None when a left join has no match, in which case the
+                        // related columns are skipped.
+                        quote_spanned! { ident.span() => {
+                            let _parent = ::tql::ForeignKeyField::related_row(&mut item.#first_parent)
+                                #(.and_then(|_parent| ::tql::ForeignKeyField::related_row(&mut _parent.#parents)))*;
+                            _tql_delta += ::tql::from_nested_related_row(_parent, |_parent| &mut _parent.#ident,
+                                &#row_ident, _tql_delta);
+                        }}
+                    },
+                    None => quote_spanned! { ident.span() => {
+                        let ref mut _related_field: Option<_> = item.#ident;
+                        _tql_delta += ::tql::from_related_row(_related_field, &#row_ident, _tql_delta);
+                    }},
+                }
             });
     quote_spanned! { table_ident.span() => {
         #[allow(unused_mut)]
         let mut item = <#table_ident as ::tql::SqlTable>::from_row(&#row_ident);
         let mut _tql_delta = <#table_ident as ::tql::SqlTable>::FIELD_COUNT;
         #(#assign_related_fields)*
-        item
+        #annotated_struct
+    }}
+}
+
+/// Create the struct containing the `item` and the values of the window functions, which are
+/// read from the row starting at the column `_tql_delta`.
+fn create_annotated_struct(table_ident: &Ident, annotations: &[Annotation]) -> Tokens {
+    if annotations.is_empty() {
+        return quote! { item };
+    }
+    let backend = create_backend();
+    let mut field_idents = vec![];
+    let mut field_values = vec![];
+    let mut type_params = vec![];
+    for (index, annotation) in annotations.iter().enumerate() {
+        type_params.push(new_ident(&format!("T{}", index)));
+        let index = backend.convert_index(index);
+        let value = quote! { __tql_item_row.get(_tql_delta + #index) };
+        field_idents.push(annotation.function.result_name.clone());
+        field_values.push(typed_value("window_functions", &annotation.function, table_ident, value));
+    }
+    let struct_ident = new_ident("Annotated");
+    let field_idents = &field_idents;
+    let field_types = type_params.clone();
+    quote! {{
+        struct #struct_ident<I, #(#type_params),*> {
+            item: I,
+            #(#field_idents: #field_types),*
+        }
+        #struct_ident {
+            item,
+            #(#field_idents: #field_values),*
+        }
+    }}
+}
+
+/// Create the tuple expression containing the `fields` selected by the only() method.
+/// When there is only one field, its value is not wrapped in a tuple.
+fn create_tuple(table_ident: &Ident, fields: &[Ident]) -> Tokens {
+    let backend = create_backend();
+    let indexes = (0..fields.len()).map(|index| backend.convert_index(index));
+    let values: Vec<_> = (0..fields.len())
+        .map(|index| Ident::new(&format!("__tql_field{}", index), Span::call_site()))
+        .collect();
+    let values = &values;
+    quote_spanned! { table_ident.span() => {
+        #(let #values = __tql_item_row.get(#indexes);)*
+        // NOTE: hack to get the type of the values from the fields of the table struct.
+        let _ = |__tql_table: #table_ident| {
+            fn __tql_same_type<T>(_field: &T, _value: &T) {
+            }
+            #(__tql_same_type(&__tql_table.#fields, &#values);)*
+        };
+        (#(#values),*)
     }}
 }
 
 /// Generate the aggregate struct and struct expression.
-fn gen_aggregate_struct(aggregates: &[Aggregate]) -> (Tokens, Tokens) {
+/// The values of the window functions in the `annotations` are added after the aggregates.
+///
+/// The type of each field is a type parameter inferred from the result type of the aggregate
+/// function in `tql::aggregates` or of the window function in `tql::window_functions`.
+fn gen_aggregate_struct(aggregates: &[Aggregate], annotations: &[Annotation], table: &Ident) -> (Tokens, Tokens) {
     let mut aggregate_field_idents = vec![];
     let mut aggregate_field_values = vec![];
     let mut def_field_idents = vec![];
+    let mut type_params = vec![];
     let backend = create_backend();
-    for (index, aggregate) in aggregates.iter().enumerate() {
+    let calls = aggregates.iter()
+        .map(|aggregate| ("aggregates", aggregate))
+        .chain(annotations.iter().map(|annotation| ("window_functions", &annotation.function)));
+    for (index, (module, call)) in calls.enumerate() {
+        let type_param = new_ident(&format!("T{}", index));
         let index = backend.convert_index(index);
-        let field_name = aggregate.result_name.clone();
+        let field_name = call.result_name.clone();
         aggregate_field_idents.push(field_name.clone());
-        aggregate_field_values.push(quote! { __tql_item_row.get(#index) });
+        let value = quote! { __tql_item_row.get(#index) };
+        aggregate_field_values.push(typed_value(module, call, table, value));
         def_field_idents.push(field_name);
+        type_params.push(type_param);
     }
     let struct_ident = new_ident("Aggregate");
+    let field_types = type_params.clone();
     (quote! {
-        struct #struct_ident {
-            #(#def_field_idents: f64),* // TODO: choose the type from the aggregate function?
+        struct #struct_ident<#(#type_params),*> {
+            #(#def_field_idents: #field_types),*
         }
     },
     quote! {{
@@ -282,6 +553,63 @@ fn gen_aggregate_struct(aggregates: &[Aggregate]) -> (Tokens, Tokens) {
     }})
 }
 
+/// Give to the `value` read from the row the type returned by the function of the `call` in the
+/// runtime `module`.
+fn typed_value(module: &str, call: &Aggregate, table: &Ident, value: Tokens) -> Tokens {
+    let module = new_ident(module);
+    let function = new_ident(&call.function);
+    let function_call =
+        if let Some(ref argument) = call.argument {
+            let table_ident = new_ident("__tql_table");
+            let argument = operand_to_tokens(argument, &table_ident);
+            quote! {{
+                fn __tql_field_type<T>(_field: &T) -> T {
+                    unimplemented!()
+                }
+                let #table_ident = <#table as ::tql::SqlTable>::_tql_default();
+                ::tql::#module::#function(&(#argument))
+            }}
+        }
+        else {
+            quote! {
+                ::tql::#module::#function()
+            }
+        };
+    quote! {
+        ::tql::aggregate_value(|| #function_call, #value)
+    }
+}
+
+
+/// Check that the type `typ` used in a ForeignKey has a #[derive(SqlTable)].
+fn foreign_key_check(typ: &str, field_type: &syn::Type) -> TokenStream {
+    let type_ident = new_ident(typ);
+    let struct_ident = new_ident(&format!("CheckForeignKey{}", rand_string()));
+    // TODO: replace with a trait bound on ForeignKey when it is stable.
+    let span = {
+        if let syn::Type::Path(TypePath { path: Path { ref segments, .. }, ..}) = *field_type {
+            let segment = segments.first().expect("first segment").into_value();
+            if let AngleBracketed(AngleBracketedGenericArguments { ref args, .. }) =
+                segment.arguments
+            {
+                args.first().expect("first argument").span()
+            }
+            else {
+                field_type.span()
+            }
+        }
+        else {
+            field_type.span()
+        }
+    };
+
+    quote_spanned!{ span =>
+        #[allow(dead_code)]
+        struct #struct_ident where #type_ident: ::tql::SqlTable {
+            field: #type_ident,
+        }
+    }.into()
+}
 
 /// Get the fields from the struct (also returns the ToSql implementations to check that the types
 /// used for ForeignKey have a #[derive(SqlTable)]).
@@ -304,14 +632,36 @@ pub fn get_struct_fields(item_struct: &ItemStruct) -> (Result<SqlFields>, Option
         };
     let mut primary_key_count = 0;
     for field in &fields {
+        match get_related_field(&item_struct.ident, field) {
+            // NOTE: the type of a related field is checked when the related rows are added to it.
+            Ok(Some(RelatedField::ForeignKey { .. })) => continue,
+            Ok(Some(RelatedField::ManyToMany(Junction { ref target_table, .. }))) => {
+                let code = foreign_key_check(&target_table.to_string(), &field.ty);
+                impls = concat_token_stream(impls, code);
+                continue;
+            },
+            Ok(None) => (),
+            Err(errs) => {
+                errors.extend(errs);
+                continue;
+            },
+        }
+        if is_fulltext_field(field) && field_ty_to_type(&field.ty).node != Type::String {
+            errors.push(Error::new("#[sql(fulltext)] can only be used on a `String` field", field.span()));
+        }
         if let Some(ref field_ident) = field.ident {
             let field_type = &field.ty;
             let field_name = field_ident.to_string();
             let field = field_ty_to_type(&field.ty);
             match field.node {
                 Type::Nullable(ref inner_type) => {
-                    if let Type::UnsupportedType(ref typ) = **inner_type {
-                        errors.push(error(field.span, typ));
+                    match **inner_type {
+                        Type::UnsupportedType(ref typ) => errors.push(error(field.span, typ)),
+                        Type::Custom(ref typ) => {
+                            let code = foreign_key_check(typ, field_type);
+                            impls = concat_token_stream(impls, code);
+                        },
+                        _ => (),
                     }
                 },
                 Type::UnsupportedType(ref typ) =>
@@ -322,33 +672,7 @@ pub fn get_struct_fields(item_struct: &ItemStruct) -> (Result<SqlFields>, Option
                     primary_key_count += 1;
                 },
                 Type::Custom(ref typ) => {
-                    let type_ident = new_ident(typ);
-                    let struct_ident = new_ident(&format!("CheckForeignKey{}", rand_string()));
-                    // TODO: replace with a trait bound on ForeignKey when it is stable.
-                    let span = {
-                        if let syn::Type::Path(TypePath { path: Path { ref segments, .. }, ..}) = *field_type {
-                            let segment = segments.first().expect("first segment").into_value();
-                            if let AngleBracketed(AngleBracketedGenericArguments { ref args, .. }) =
-                                segment.arguments
-                            {
-                                args.first().expect("first argument").span()
-                            }
-                            else {
-                                field_type.span()
-                            }
-                        }
-                        else {
-                            field_type.span()
-                        }
-                    };
-
-                    let code: TokenStream = quote_spanned!{ span =>
-                        #[allow(dead_code)]
-                        struct #struct_ident where #type_ident: ::tql::SqlTable {
-                            field: #type_ident,
-                        }
-                    }.into();
-
+                    let code = foreign_key_check(typ, field_type);
                     impls = concat_token_stream(impls, code);
                 },
                 _ => (),
@@ -367,32 +691,47 @@ pub fn get_struct_fields(item_struct: &ItemStruct) -> (Result<SqlFields>, Option
 }
 
 fn field_list_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
-    let field_list = named.iter()
+    let field_idents: Vec<_> = named.iter()
         .filter(|field| {
-            let typ = token_to_string(&field.ty);
-            !typ.starts_with("ForeignKey")
+            related_table(&field.ty).is_none() && !is_related_field(field)
         })
-        .map(|field| {
+        .map(|field| field.ident.clone().expect("field has name"))
+        .collect();
+    let field_list = field_idents.iter()
+        .map(|ident| {
             format!("{table}.{column}",
-                    column = field.ident.clone().expect("field has name"),
+                    column = ident,
                     table = table_ident
                    )
         })
         .collect::<Vec<_>>()
         .join(", ");
     let field_list = string_literal(&field_list);
+    // The fields of a joined table are qualified with the alias of this table.
+    let aliased_columns = field_idents.iter()
+        .enumerate()
+        .map(|(index, ident)| {
+            let column = format!(".{}", ident);
+            if index == 0 {
+                quote! { $table, #column }
+            }
+            else {
+                quote! { ", ", $table, #column }
+            }
+        });
     let macro_name = Ident::new(&format!("tql_{}_field_list", table_ident), Span::call_site());
     quote! {
         #[macro_export]
         macro_rules! #macro_name {
             () => { #field_list };
+            ($table:expr) => { concat!(#(#aliased_columns),*) };
         }
     }
 }
 
 fn create_query_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
     let mut fields_to_create = vec![];
-    for field in named {
+    for field in named.iter().filter(|field| !is_related_field(field)) {
         fields_to_create.push(TypedField {
             identifier: field.ident.clone().expect("field ident").to_string(),
             typ: type_to_sql(&field_ty_to_type(&field.ty).node),
@@ -415,30 +754,60 @@ fn create_query_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) ->
 fn related_pks_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
     let mut related_table_names = vec![];
     let mut related_pk_macro_names = vec![];
+    let mut nested_macro_names = vec![];
     for field in named {
-        let typ = token_to_string(&field.ty);
         if let Some(ref ident) = field.ident {
-            if typ.starts_with("ForeignKey") {
-                if let syn::Type::Path(ref path) = field.ty {
-                    let element = path.path.segments.first().expect("first segment of path");
-                    let first_segment = element.value();
-                    if let Some(typ) = get_type_parameter(&first_segment.arguments) {
-                        related_table_names.push(ident);
-                        related_pk_macro_names.push(Ident::new(&format!("tql_{}_primary_key_field", typ),
-                            Span::call_site()));
-                    }
-                }
+            if let Some(typ) = related_table(&field.ty) {
+                related_table_names.push(ident);
+                related_pk_macro_names.push(Ident::new(&format!("tql_{}_primary_key_field", typ),
+                    Span::call_site()));
+                nested_macro_names.push(Ident::new(&format!("tql_{}_related_pks", typ),
+                    Span::call_site()));
             }
         }
     }
+    let related_table_names2 = &related_table_names;
+    let related_table_names = &related_table_names;
     let macro_name = Ident::new(&format!("tql_{}_related_pks", table_ident), Span::call_site());
     quote! {
         #[macro_export]
         macro_rules! #macro_name {
             #((#related_table_names) => { #related_pk_macro_names!() };)*
+            // Paths like author.company are forwarded to the macro of the related table.
+            #((#related_table_names2 . $($rest:tt)+) => { #nested_macro_names!($($rest)+) };)*
             // NOTE: the check for the field name is done elsewhere, hence it is okay to return
             // "" here.
-            ($tt:tt) => { "" };
+            ($($tt:tt)*) => { "" };
+        }
+    }
+}
+
+/// Create the macro giving the SQL type of the sum of a field.
+fn sum_type_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let macro_name = Ident::new(&format!("tql_{}_sum_type", table_ident), Span::call_site());
+    let mut fields = vec![];
+    let mut types = vec![];
+    for field in named {
+        if let Some(ref ident) = field.ident {
+            let mut typ = field_ty_to_type(&field.ty).node;
+            if let Type::Nullable(nullable_type) = typ {
+                typ = *nullable_type;
+            }
+            let sum_type =
+                match typ {
+                    Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::Serial => "BIGINT",
+                    Type::F32 | Type::F64 => "DOUBLE PRECISION",
+                    _ => continue,
+                };
+            fields.push(ident);
+            types.push(sum_type);
+        }
+    }
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #((#fields) => { #types };)*
+            ($($tt:tt)*) => { "" };
         }
     }
 }
@@ -479,7 +848,9 @@ fn check_missing_fields_macro(named: &Punctuated<Field, Comma>, table_ident: &Id
     for field in named {
         let typ = token_to_string(&field.ty);
         if let Some(ref ident) = field.ident {
-            if !typ.starts_with("Option") && typ != "PrimaryKey" {
+            if !typ.starts_with("Option") && typ != "PrimaryKey" &&
+                !is_related_field(field)
+            {
                 mandatory_fields.push(ident);
             }
         }
@@ -509,25 +880,26 @@ fn related_table_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) ->
     let mut related_tables = vec![];
     let mut check_related_pk = vec![];
     let mut compiler_errors = vec![];
+    let mut nested_macro_names = vec![];
+    let mut nested_check_macro_names = vec![];
+    let mut nested_check_pk_macro_names = vec![];
     for field in named {
         let typ = token_to_string(&field.ty);
         if let Some(ref ident) = field.ident {
-            if typ.starts_with("ForeignKey") {
-                if let syn::Type::Path(ref path) = field.ty {
-                    let element = path.path.segments.first().expect("first segment of path");
-                    let first_segment = element.value();
-                    if let Some(typ) = get_type_parameter(&first_segment.arguments) {
-                        related_table_names.push(ident);
-                        let span = get_type_parameter_as_path(&first_segment.arguments)
-                            .expect("ForeignKey inner type")
-                            .span();
-                        let macro_name = Ident::new(&format!("tql_{}_check_primary_key", typ), span);
-                        check_related_pk.push(quote_spanned! { span =>
-                            #macro_name!();
-                        });
-                        related_tables.push(typ);
-                    }
-                }
+            if let Some(typ) = related_table(&field.ty) {
+                related_table_names.push(ident);
+                let span = typ.span();
+                let macro_name = Ident::new(&format!("tql_{}_check_primary_key", typ), span);
+                check_related_pk.push(quote_spanned! { span =>
+                    #macro_name!();
+                });
+                nested_macro_names.push(Ident::new(&format!("tql_{}_related_tables", typ),
+                    Span::call_site()));
+                nested_check_macro_names.push(Ident::new(&format!("tql_{}_check_related_tables", typ),
+                    Span::call_site()));
+                nested_check_pk_macro_names.push(Ident::new(&format!("tql_{}_check_related_pks", typ),
+                    Span::call_site()));
+                related_tables.push(typ);
             }
             else {
                 non_related_table_names.push(ident);
@@ -541,6 +913,9 @@ expected type `ForeignKey<_>`
         }
     }
     let related_table_names2 = &related_table_names;
+    let related_table_names3 = &related_table_names;
+    let related_table_names4 = &related_table_names;
+    let related_table_names5 = &related_table_names;
     let related_table_names = &related_table_names;
     let macro_name = Ident::new(&format!("tql_{}_related_tables", table_ident), Span::call_site());
     let check_macro_name = Ident::new(&format!("tql_{}_check_related_tables", table_ident), Span::call_site());
@@ -549,21 +924,25 @@ expected type `ForeignKey<_>`
         #[macro_export]
         macro_rules! #macro_name {
             #((#related_table_names) => { #related_tables };)*
+            // Paths like author.company are forwarded to the macro of the related table.
+            #((#related_table_names3 . $($rest:tt)+) => { #nested_macro_names!($($rest)+) };)*
             // NOTE: the check for the field name is done elsewhere, hence it is okay to return
             // "" here.
-            ($tt:tt) => { "" };
+            ($($tt:tt)*) => { "" };
         }
 
         #[macro_export]
         macro_rules! #check_macro_name {
-            #((#non_related_table_names) => { #compiler_errors };)*
-            ($tt:tt) => {};
+            #((#non_related_table_names $($rest:tt)*) => { #compiler_errors };)*
+            #((#related_table_names4 . $($rest:tt)+) => { #nested_check_macro_names!($($rest)+) };)*
+            ($($tt:tt)*) => {};
         }
 
         #[macro_export]
         macro_rules! #check_related_pk_macro_name {
             #((#related_table_names2) => { #check_related_pk };)*
-            ($tt:tt) => {};
+            #((#related_table_names5 . $($rest:tt)+) => { #nested_check_pk_macro_names!($($rest)+) };)*
+            ($($tt:tt)*) => {};
         }
     }
 }
@@ -598,6 +977,356 @@ fn check_pk_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Toke
     }
 }
 
+/// Create the macro checking that a field is declared with #[sql(fulltext)], which is needed to
+/// call the full-text search methods on it.
+fn check_fulltext_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let fulltext_fields = named.iter()
+        .filter(|field| is_fulltext_field(field))
+        .map(|field| field.ident.as_ref().expect("field has name"));
+    let macro_name = Ident::new(&format!("tql_{}_check_fulltext", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #((#fulltext_fields) => {};)*
+            ($field:ident) => {
+                compile_error!(concat!("the field `", stringify!($field),
+                    "` must be declared with #[sql(fulltext)] to be searched"))
+            };
+        }
+    }
+}
+
+/// Create the macro choosing the SQL of a method depending on whether the field is an array, for
+/// the methods which also exist on other types, like `len()`.
+fn if_array_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let array_fields = named.iter()
+        .filter(|field| !is_related_field(field))
+        .filter(|field| is_array_field(field))
+        .map(|field| field.ident.as_ref().expect("field has name"));
+    let macro_name = Ident::new(&format!("tql_{}_if_array", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #((#array_fields, $array:expr, $other:expr) => { $array };)*
+            ($field:ident, $array:expr, $other:expr) => { $other };
+        }
+    }
+}
+
+/// Check if the `field` is an array, which can only be stored by PostgreSQL.
+#[cfg(feature = "postgres")]
+fn is_array_field(field: &Field) -> bool {
+    match field_ty_to_type(&field.ty).node {
+        Type::Nullable(typ) => matches!(*typ, Type::Array(_)),
+        typ => matches!(typ, Type::Array(_)),
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+fn is_array_field(_field: &Field) -> bool {
+    false
+}
+
+/// Create the macro checking that a field sorting the rows of a paginated query is not nullable,
+/// since NULL values cannot be compared to the values of the cursor.
+fn check_not_nullable_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let nullable_fields = named.iter()
+        .filter(|field| !is_related_field(field))
+        .filter(|field| matches!(field_ty_to_type(&field.ty).node, Type::Nullable(_)))
+        .map(|field| {
+            let ident = field.ident.as_ref().expect("field has name");
+            let msg = string_literal(&format!("cannot paginate the rows sorted by the nullable field `{}`", ident));
+            quote_spanned! { field.span() =>
+                (#ident) => {
+                    compile_error!(#msg)
+                };
+            }
+        });
+    let macro_name = Ident::new(&format!("tql_{}_check_not_nullable", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #(#nullable_fields)*
+            ($field:ident) => {};
+        }
+    }
+}
+
+/// Get the primary key field of the table struct.
+fn primary_key_field(named: &Punctuated<Field, Comma>) -> Option<&Ident> {
+    named.iter()
+        .find(|field| token_to_string(&field.ty) == "PrimaryKey")
+        .and_then(|field| field.ident.as_ref())
+}
+
+/// Check that the source and target fields exist in the intermediate model of a `ManyToMany`.
+fn junction_check(junction: &Junction) -> Tokens {
+    if junction.auto_created {
+        return quote! {};
+    }
+    let Junction { ref source, ref table, ref target, .. } = *junction;
+    quote_spanned! { table.span() =>
+        let _ = |__tql_junction: #table| {
+            let _ = __tql_junction.#source;
+            let _ = __tql_junction.#target;
+        };
+    }
+}
+
+/// Create the macro loading the rows related to the table structs by a field declared with
+/// #[sql(related = "Table.foreign_key")] or by a `ManyToMany` field.
+fn prefetch_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let primary_key = primary_key_field(named);
+    let backend = create_backend();
+    let mut related_field_names = vec![];
+    let mut related_codes = vec![];
+    let mut non_related_field_names = vec![];
+    let mut compiler_errors = vec![];
+    for field in named {
+        if let Some(ref ident) = field.ident {
+            let (table, query, check) =
+                match get_related_field(table_ident, field) {
+                    Ok(Some(RelatedField::ForeignKey { foreign_key, table })) => {
+                        let field_list_macro_name = Ident::new(&format!("tql_{}_field_list", table), Span::call_site());
+                        let from_clause = format!(", {table}.{foreign_key} FROM {table} WHERE {table}.{foreign_key}",
+                                                  foreign_key = foreign_key, table = table);
+                        let query = quote! { "SELECT ", #field_list_macro_name!(), #from_clause };
+                        (table, query, quote! {})
+                    },
+                    Ok(Some(RelatedField::ManyToMany(junction))) => {
+                        let Junction { ref source, table: ref junction_table, ref target, ref target_table, .. } =
+                            junction;
+                        let field_list_macro_name = Ident::new(&format!("tql_{}_field_list", target_table),
+                            Span::call_site());
+                        let pk_macro_name = Ident::new(&format!("tql_{}_primary_key_field", target_table),
+                            Span::call_site());
+                        let from_clause = format!(", {junction}.{source} FROM {table} INNER JOIN {junction} ON \
+                                                  {junction}.{target} = {table}.",
+                                                  junction = junction_table, source = source, table = target_table,
+                                                  target = target);
+                        let where_clause = format!(" WHERE {junction}.{source}", junction = junction_table,
+                                                   source = source);
+                        let query = quote! {
+                            "SELECT ", #field_list_macro_name!(), #from_clause, #pk_macro_name!(), #where_clause
+                        };
+                        (target_table.clone(), query, junction_check(&junction))
+                    },
+                    Ok(None) => {
+                        non_related_field_names.push(ident);
+                        let msg = string_literal(&format!("mismatched types
+expected type `Vec<_>` with #[sql(related = \"Table.foreign_key\")] or `ManyToMany<_>`
+   found type `{}`", token_to_string(&field.ty)));
+                        compiler_errors.push(quote_spanned! { field.span() =>
+                            compile_error!(#msg)
+                        });
+                        continue;
+                    },
+                    // NOTE: the error is reported by get_struct_fields().
+                    Err(_) => continue,
+                };
+            related_field_names.push(ident);
+            let code =
+                if let Some(primary_key) = primary_key {
+                    let code = backend.related_rows_query(primary_key, ident, &table, query);
+                    quote! {
+                        #check
+                        #code
+                    }
+                }
+                else {
+                    let error = format!("No primary key found for table {} which is needed for a related field",
+                                        table_ident);
+                    quote_spanned! { table_ident.span() =>
+                        compile_error!(#error)
+                    }
+                };
+            related_codes.push(code);
+        }
+    }
+    let macro_name = Ident::new(&format!("tql_{}_prefetch", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #((#related_field_names, $connection:expr, $items:expr) => {{ #related_codes }};)*
+            #((#non_related_field_names $($rest:tt)*) => { #compiler_errors };)*
+            // NOTE: show an error for the unknown fields.
+            ($field:ident $($rest:tt)*) => {
+                let _ = |__tql_table: #table_ident| __tql_table.$field;
+            };
+        }
+    }
+}
+
+/// Get the junction tables of the `ManyToMany` fields of the table struct.
+fn junctions(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Vec<(Ident, Junction)> {
+    named.iter()
+        .filter_map(|field| match get_related_field(table_ident, field) {
+            Ok(Some(RelatedField::ManyToMany(junction))) =>
+                Some((field.ident.clone().expect("field has name"), junction)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Create the macro returning the queries creating or dropping the junction tables of the
+/// `ManyToMany` fields which are not intermediate models and what the full-text search on the
+/// #[sql(fulltext)] fields needs.
+fn auxiliary_queries_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let mut create_queries = vec![];
+    let mut drop_queries = vec![];
+    for field in named.iter().filter(|field| is_fulltext_field(field)) {
+        let field = field.ident.as_ref().expect("field has name");
+        let (create, drop) = fulltext_queries(&table_ident.to_string(), &field.to_string());
+        create_queries.extend(create.into_iter().map(|query| quote! { #query }));
+        drop_queries.extend(drop);
+    }
+    for (_, junction) in junctions(named, table_ident) {
+        if !junction.auto_created {
+            continue;
+        }
+        let fields = fields_to_sql(&[
+            TypedField {
+                identifier: junction.source.to_string(),
+                typ: type_to_sql(&Type::Custom(table_ident.to_string())),
+            },
+            TypedField {
+                identifier: junction.target.to_string(),
+                typ: type_to_sql(&Type::Custom(junction.target_table.to_string())),
+            },
+        ]);
+        let table = junction.table.to_string();
+        let primary_key = format!(", PRIMARY KEY ({}, {}))", junction.source, junction.target);
+        create_queries.push(quote! {
+            concat!("CREATE TABLE ", #table, " (", #fields, #primary_key)
+        });
+        drop_queries.push(format!("DROP TABLE IF EXISTS {}", table));
+    }
+    let macro_name = Ident::new(&format!("tql_{}_auxiliary_queries", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            (create) => {{
+                let __tql_queries: &[&str] = &[#(#create_queries),*];
+                __tql_queries
+            }};
+            (drop) => {{
+                let __tql_queries: &[&str] = &[#(#drop_queries),*];
+                __tql_queries
+            }};
+        }
+    }
+}
+
+/// Create the macros used by the `field.any(filter)` filter: one to get the subquery selecting the
+/// primary keys of the table structs related to the rows of the `ManyToMany` field and one to check
+/// the field.
+fn many_to_many_macro(named: &Punctuated<Field, Comma>, table_ident: &Ident) -> Tokens {
+    let has_primary_key = primary_key_field(named).is_some();
+    let mut field_names = vec![];
+    let mut subqueries = vec![];
+    let mut checks = vec![];
+    for (field, junction) in junctions(named, table_ident) {
+        let alias = format!("{}_{}", table_ident, field);
+        let subquery = format!("SELECT {alias}_junction.{source} FROM {junction} AS {alias}_junction INNER JOIN \
+                               {table} AS {alias} ON {alias}_junction.{target} = {alias}.",
+                               alias = alias, junction = junction.table, source = junction.source,
+                               table = junction.target_table, target = junction.target);
+        let pk_macro_name = Ident::new(&format!("tql_{}_primary_key_field", junction.target_table), Span::call_site());
+        subqueries.push(quote! {
+            concat!(#subquery, #pk_macro_name!())
+        });
+        let check = junction_check(&junction);
+        checks.push(
+            if has_primary_key {
+                check
+            }
+            else {
+                let error = format!("No primary key found for table {} which is needed for a ManyToMany field",
+                                    table_ident);
+                quote_spanned! { table_ident.span() =>
+                    compile_error!(#error);
+                }
+            });
+        field_names.push(field);
+    }
+    let mut non_many_to_many_field_names = vec![];
+    let mut compiler_errors = vec![];
+    for field in named {
+        if let Some(ref ident) = field.ident {
+            if !field_names.contains(ident) {
+                non_many_to_many_field_names.push(ident);
+                let msg = string_literal(&format!("mismatched types
+expected type `ManyToMany<_>`
+   found type `{}`", token_to_string(&field.ty)));
+                compiler_errors.push(quote_spanned! { field.span() =>
+                    compile_error!(#msg)
+                });
+            }
+        }
+    }
+    let field_names = &field_names;
+    let macro_name = Ident::new(&format!("tql_{}_many_to_many_subquery", table_ident), Span::call_site());
+    let check_macro_name = Ident::new(&format!("tql_{}_check_many_to_many", table_ident), Span::call_site());
+    quote! {
+        #[macro_export]
+        macro_rules! #macro_name {
+            #((#field_names) => { #subqueries };)*
+            // NOTE: the check for the field is done elsewhere, hence it is okay to return "" here.
+            ($($tt:tt)*) => { "" };
+        }
+
+        #[macro_export]
+        macro_rules! #check_macro_name {
+            #((#field_names) => { #checks };)*
+            #((#non_many_to_many_field_names) => { #compiler_errors; };)*
+            ($field:ident) => {
+                let _ = |__tql_table: #table_ident| __tql_table.$field;
+            };
+        }
+    }
+}
+
+/// Create the methods adding and removing the rows related by the `ManyToMany` fields.
+/// The rows of an intermediate model are managed with queries on this model instead.
+pub fn many_to_many_methods(item_struct: &ItemStruct) -> Tokens {
+    let table_ident = &item_struct.ident;
+    if let Fields::Named(FieldsNamed { ref named , .. }) = item_struct.fields {
+        let primary_key =
+            match primary_key_field(named) {
+                Some(primary_key) => primary_key,
+                // NOTE: the error is reported when the field is used.
+                None => return quote! {},
+            };
+        let backend = create_backend();
+        let mut methods = vec![];
+        for (field, junction) in junctions(named, table_ident) {
+            if !junction.auto_created {
+                continue;
+            }
+            let Junction { ref source, ref table, ref target, ref target_table, .. } = junction;
+            let add_ident = Ident::new(&format!("add_{}", field), field.span());
+            let add_query = format!("INSERT INTO {table} ({source}, {target}) VALUES ($1, $2)",
+                                    source = source, table = table, target = target);
+            methods.push(backend.junction_method(&add_ident, primary_key, target_table, &add_query));
+            let remove_ident = Ident::new(&format!("remove_{}", field), field.span());
+            let remove_query = format!("DELETE FROM {table} WHERE {source} = $1 AND {target} = $2",
+                                       source = source, table = table, target = target);
+            methods.push(backend.junction_method(&remove_ident, primary_key, target_table, &remove_query));
+        }
+        if methods.is_empty() {
+            return quote! {};
+        }
+        quote! {
+            impl #table_ident {
+                #(#methods)*
+            }
+        }
+    }
+    else {
+        unreachable!("Check is done in get_struct_fields()")
+    }
+}
+
 /// Create the insert macro for the table struct to check that all the mandatory fields are
 /// provided.
 pub fn table_macro(item_struct: &ItemStruct) -> Tokens {
@@ -608,20 +1337,19 @@ pub fn table_macro(item_struct: &ItemStruct) -> Tokens {
         for field in named {
             let typ = token_to_string(&field.ty);
             if let Some(ref ident) = field.ident {
-                if !typ.starts_with("Option") && typ != "PrimaryKey" {
+                if !typ.starts_with("Option") && typ != "PrimaryKey" &&
+                    !is_related_field(field)
+                {
                     mandatory_fields.push(ident);
                 }
-                if typ.starts_with("ForeignKey") {
-                    if let syn::Type::Path(ref path) = field.ty {
-                        let element = path.path.segments.first().expect("first segment of path");
-                        let first_segment = element.value();
-                        if let Some(typ) = get_type_parameter(&first_segment.arguments) {
-                            let macro_name = Ident::new(&format!("tql_{}_field_list", typ), Span::call_site());
-                            fk_patterns.push(quote_spanned! { table_ident.span() =>
-                                (#ident) => { #macro_name!() };
-                            });
-                        }
-                    }
+                if let Some(typ) = related_table(&field.ty) {
+                    let macro_name = Ident::new(&format!("tql_{}_field_list", typ), Span::call_site());
+                    let nested_macro_name = Ident::new(&format!("tql_{}_related_field_list", typ),
+                        Span::call_site());
+                    fk_patterns.push(quote_spanned! { table_ident.span() =>
+                        ($alias:expr; #ident) => { #macro_name!($alias) };
+                        ($alias:expr; #ident . $($rest:tt)+) => { #nested_macro_name!($alias; $($rest)+) };
+                    });
                 }
             }
         }
@@ -634,11 +1362,18 @@ pub fn table_macro(item_struct: &ItemStruct) -> Tokens {
         let related_pks_macro = related_pks_macro(named, table_ident);
         let related_table_macro = related_table_macro(named, table_ident);
         let check_pk_macro = check_pk_macro(named, table_ident);
+        let prefetch_macro = prefetch_macro(named, table_ident);
+        let auxiliary_queries_macro = auxiliary_queries_macro(named, table_ident);
+        let check_fulltext_macro = check_fulltext_macro(named, table_ident);
+        let if_array_macro = if_array_macro(named, table_ident);
+        let check_not_nullable_macro = check_not_nullable_macro(named, table_ident);
+        let many_to_many_macro = many_to_many_macro(named, table_ident);
+        let sum_type_macro = sum_type_macro(named, table_ident);
         quote! {
             #[macro_export]
             macro_rules! #related_field_list_macro_name {
                 #(#fk_patterns)*
-                ($tt:tt) => { "" };
+                ($($tt:tt)*) => { "" };
             }
 
             #check_pk_macro
@@ -648,6 +1383,13 @@ pub fn table_macro(item_struct: &ItemStruct) -> Tokens {
             #create_query_macro
             #related_pks_macro
             #pk_macro
+            #prefetch_macro
+            #auxiliary_queries_macro
+            #check_fulltext_macro
+            #if_array_macro
+            #check_not_nullable_macro
+            #many_to_many_macro
+            #sum_type_macro
         }
     }
     else {
@@ -655,16 +1397,44 @@ pub fn table_macro(item_struct: &ItemStruct) -> Tokens {
     }
 }
 
-fn to_row_get(typ: syn::Type, with_delta: bool, index: &mut usize) -> Tokens {
-    if let syn::Type::Path(path) = typ {
-        let segment = path.path.segments.first().expect("first segment").into_value();
-        if segment.ident == "ForeignKey" {
-            // NOTE: this use the Span call_site() to work-around a privacy issue:
-            // https://github.com/rust-lang/rust/issues/46635
-            return quote_spanned! { Span::call_site() =>
-                None
-            };
+/// Get the table referenced by the type `ty` when it is a `ForeignKey<Table>` or an
+/// `Option<ForeignKey<Table>>`.
+fn related_table(ty: &syn::Type) -> Option<Ident> {
+    let nullable =
+        match field_ty_to_type(ty).node {
+            Type::Custom(_) => false,
+            Type::Nullable(ref typ) if matches!(**typ, Type::Custom(_)) => true,
+            _ => return None,
+        };
+    if let syn::Type::Path(TypePath { ref path, .. }) = *ty {
+        let mut path = path;
+        if nullable {
+            path = get_type_parameter_as_path(&path.segments.first()?.value().arguments)?;
         }
+        let table = get_type_parameter_as_path(&path.segments.first()?.value().arguments)?;
+        return Some(table.segments.first()?.value().ident.clone());
+    }
+    None
+}
+
+/// Get the value of a field of a table struct from the row.
+/// The related fields are empty since their rows are loaded by another query.
+fn to_table_row_get(field: &Field, with_delta: bool, index: &mut usize) -> Tokens {
+    if is_related_field(field) {
+        return quote_spanned! { Span::call_site() =>
+            Vec::new()
+        };
+    }
+    to_row_get(field.ty.clone(), with_delta, index)
+}
+
+fn to_row_get(typ: syn::Type, with_delta: bool, index: &mut usize) -> Tokens {
+    if related_table(&typ).is_some() {
+        // NOTE: this use the Span call_site() to work-around a privacy issue:
+        // https://github.com/rust-lang/rust/issues/46635
+        return quote_spanned! { Span::call_site() =>
+            None
+        };
     }
     let backend = create_backend();
     let index_lit = backend.int_literal(*index);
@@ -754,6 +1524,9 @@ trait BackendGen {
     fn gen_query_expr(&self, connection_expr: Tokens, args: &SqlQueryWithArgs, args_expr: Tokens, struct_expr: Tokens,
                       aggregate_struct: Tokens, aggregate_expr: Tokens) -> Tokens;
     fn int_literal(&self, num: usize) -> Expr;
+    fn junction_method(&self, name: &Ident, primary_key: &Ident, target_table: &Ident, query: &str) -> Tokens;
+    fn params_expr(&self, params: &[(Tokens, bool)]) -> Tokens;
+    fn related_rows_query(&self, primary_key: &Ident, field: &Ident, table: &Ident, query: Tokens) -> Tokens;
     fn row_type_ident(&self, table_ident: &Ident) -> Tokens;
     fn to_sql(&self, primary_key_ident: &Ident) -> Tokens;
     fn to_sql_impl(&self, table_ident: &Ident, to_sql_code: Tokens) -> Tokens;
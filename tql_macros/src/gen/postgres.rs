@@ -31,7 +31,7 @@ use syn::{
 use syn::spanned::Spanned;
 
 use ast::QueryType;
-use super::BackendGen;
+use super::{BackendGen, items_expr};
 use SqlQueryWithArgs;
 
 pub struct PostgresBackend {}
@@ -56,6 +56,7 @@ impl BackendGen for PostgresBackend {
     fn gen_query_expr(&self, connection_expr: TokenStream, args: &SqlQueryWithArgs, args_expr: TokenStream, struct_expr: TokenStream,
                       aggregate_struct: TokenStream, aggregate_expr: TokenStream) -> TokenStream
     {
+        let items_expr = items_expr(args, &connection_expr);
         let result_ident = Ident::new("__tql_result", proc_macro2::Span::call_site());
         let sql_query = &args.sql;
         let std_ident = quote_spanned! { connection_expr.span() =>
@@ -90,10 +91,55 @@ impl BackendGen for PostgresBackend {
                         })
                 }}
             },
+            QueryType::Count => {
+                quote! {
+                    #connection_expr.prepare(#sql_query)
+                        .and_then(|#result_ident| {
+                            let #result_ident = #result_ident.query(&#args_expr)?;
+                            let __tql_item_row = #result_ident.iter().next()
+                                .ok_or_else(|| #std_ident::io::Error::from(#std_ident::io::ErrorKind::NotFound))?;
+                            let __tql_count: i64 = __tql_item_row.get(0);
+                            Ok(__tql_count)
+                        })
+                }
+            },
             QueryType::Create => {
+                let auxiliary_queries_macro_name = Ident::new(&format!("tql_{}_auxiliary_queries", args.table_name),
+                    Span::call_site());
                 quote! {
                     #connection_expr.prepare(#sql_query)
                         .and_then(|result| result.execute(&[]))
+                        .and_then(|__tql_count| {
+                            // NOTE: the junction tables and the full-text search tables reference the table, so
+                            // they are created after.
+                            for __tql_query in #auxiliary_queries_macro_name!(create) {
+                                #connection_expr.execute(__tql_query, &[])?;
+                            }
+                            Ok(__tql_count)
+                        })
+                }
+            },
+            QueryType::Drop => {
+                let auxiliary_queries_macro_name = Ident::new(&format!("tql_{}_auxiliary_queries", args.table_name),
+                    Span::call_site());
+                quote! {
+                    #auxiliary_queries_macro_name!(drop).iter()
+                        .fold(Ok(0), |__tql_result, __tql_query|
+                            __tql_result.and_then(|_| #connection_expr.execute(__tql_query, &[])))
+                        .and_then(|_| #connection_expr.prepare(#sql_query))
+                        .and_then(|result| result.execute(&[]))
+                }
+            },
+            QueryType::Exists => {
+                quote! {
+                    #connection_expr.prepare(#sql_query)
+                        .and_then(|#result_ident| {
+                            let #result_ident = #result_ident.query(&#args_expr)?;
+                            let __tql_item_row = #result_ident.iter().next()
+                                .ok_or_else(|| #std_ident::io::Error::from(#std_ident::io::ErrorKind::NotFound))?;
+                            let __tql_exists: bool = __tql_item_row.get(0);
+                            Ok(__tql_exists)
+                        })
                 }
             },
             QueryType::InsertOne => {
@@ -108,15 +154,17 @@ impl BackendGen for PostgresBackend {
                         })
                 }
             },
-            QueryType::SelectMulti => {
+            QueryType::SelectMulti | QueryType::SelectPage => {
                 quote! {
                     #connection_expr.prepare(#sql_query)
                         .and_then(|#result_ident| {
                             let #result_ident = #result_ident.query(&#args_expr)?;
                             let #result_ident = #result_ident.iter();
-                            Ok(#result_ident.map(|__tql_item_row| {
+                            #[allow(unused_mut)]
+                            let mut __tql_items = #result_ident.map(|__tql_item_row| {
                                 #struct_expr
-                            }).collect::<Vec<_>>())
+                            }).collect::<Vec<_>>();
+                            Ok(#items_expr)
                             // TODO: return an iterator instead of a vector.
                         })
                 }
@@ -128,7 +176,9 @@ impl BackendGen for PostgresBackend {
                             let #result_ident = #result_ident.query(&#args_expr)?;
                             let __tql_item_row = #result_ident.iter().next()
                                 .ok_or_else(|| #std_ident::io::Error::from(#std_ident::io::ErrorKind::NotFound))?;
-                            Ok(#struct_expr)
+                            #[allow(unused_mut)]
+                            let mut __tql_item = #struct_expr;
+                            Ok(#items_expr)
                         })
                 }
             },
@@ -148,6 +198,56 @@ impl BackendGen for PostgresBackend {
         })
     }
 
+    /// Generate a method executing the `query` on the junction table of a `ManyToMany` field with
+    /// the primary key of the table struct and a row of the `target_table`.
+    fn junction_method(&self, name: &Ident, primary_key: &Ident, target_table: &Ident, query: &str) -> TokenStream {
+        quote! {
+            #[allow(dead_code)]
+            pub fn #name(&self, connection: &::postgres::Connection, row: &#target_table) -> ::postgres::Result<()> {
+                connection.execute(#query, &[&self.#primary_key, row])
+                    .map(|_| ())
+            }
+        }
+    }
+
+    fn params_expr(&self, params: &[(TokenStream, bool)]) -> TokenStream {
+        let params = params.iter()
+            .map(|&(ref param, list)|
+                if list {
+                    // NOTE: a slice is sent as an SQL array.
+                    quote! { &&#param[..] }
+                }
+                else {
+                    quote! { &#param }
+                }
+            );
+        quote! {
+            [#(#params),*]
+        }
+    }
+
+    /// Generate the code loading the rows of the related `table` associated to one of the table
+    /// structs in `$items`.
+    /// The `query` selects these rows with the primary key of their table struct and ends with the
+    /// column to compare to this primary key.
+    fn related_rows_query(&self, primary_key: &Ident, field: &Ident, table: &Ident, query: TokenStream) -> TokenStream {
+        quote! {
+            let __tql_parents: &mut [_] = $items;
+            let __tql_pks: Vec<::tql::PrimaryKey> = __tql_parents.iter()
+                .map(|__tql_parent| __tql_parent.#primary_key)
+                .collect();
+            let __tql_statement = $connection.prepare(concat!(#query, " = ANY($1)"))?;
+            let __tql_children = __tql_statement.query(&[&__tql_pks])?.iter()
+                .map(|__tql_item_row| {
+                    let __tql_pk: ::tql::PrimaryKey = __tql_item_row.get(<#table as ::tql::SqlTable>::FIELD_COUNT);
+                    (__tql_pk, <#table as ::tql::SqlTable>::from_row(&__tql_item_row))
+                })
+                .collect();
+            ::tql::add_related_rows(__tql_parents, __tql_children, |__tql_parent| __tql_parent.#primary_key,
+                |__tql_parent| &mut __tql_parent.#field);
+        }
+    }
+
     fn row_type_ident(&self, table_ident: &Ident) -> proc_macro2::TokenStream {
         quote_spanned! { table_ident.span() =>
             ::postgres::rows::Row
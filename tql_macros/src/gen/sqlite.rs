@@ -31,8 +31,8 @@ use syn::{
 use syn::spanned::Spanned;
 
 use ast::QueryType;
-use super::BackendGen;
-use SqlQueryWithArgs;
+use super::{BackendGen, items_expr};
+use {SqlQueryWithArgs, list_arguments};
 
 pub struct SqliteBackend {}
 
@@ -56,8 +56,22 @@ impl BackendGen for SqliteBackend {
     fn gen_query_expr(&self, connection_expr: TokenStream, args: &SqlQueryWithArgs, args_expr: TokenStream, struct_expr: TokenStream,
                       aggregate_struct: TokenStream, aggregate_expr: TokenStream) -> TokenStream
     {
+        let items_expr = items_expr(args, &connection_expr);
         let result_ident = Ident::new("__tql_result",Span::call_site());
         let sql_query = &args.sql;
+        let list_arguments = list_arguments(args);
+        let sql_query =
+            if list_arguments.is_empty() {
+                quote! { #sql_query }
+            }
+            else {
+                // The number of parameters of the lists is only known at runtime.
+                let params = list_arguments.iter()
+                    .map(|&(param, ref list)| quote! { (#param, #list.len()) });
+                quote! {
+                    &::tql::expand_list_params(#sql_query, &[#(#params),*])
+                }
+            };
         let rusqlite_ident = quote_spanned! { connection_expr.span() =>
             ::rusqlite
         };
@@ -91,10 +105,55 @@ impl BackendGen for SqliteBackend {
                         })
                 }}
             },
+            QueryType::Count => {
+                quote! {
+                    #connection_expr.prepare(#sql_query)
+                        .and_then(|mut #result_ident| {
+                            #result_ident.query_map(&#args_expr, |__tql_item_row| -> i64 {
+                                    __tql_item_row.get(0)
+                                })?
+                                .next()
+                                .ok_or_else(|| #rusqlite_ident::Error::QueryReturnedNoRows)?
+                        })
+                }
+            },
             QueryType::Create => {
+                let auxiliary_queries_macro_name = Ident::new(&format!("tql_{}_auxiliary_queries", args.table_name),
+                    Span::call_site());
                 quote! {
                     #connection_expr.prepare(#sql_query)
                         .and_then(|mut result| result.execute(&[]))
+                        .and_then(|__tql_count| {
+                            // NOTE: the junction tables and the full-text search tables reference the table, so
+                            // they are created after.
+                            for __tql_query in #auxiliary_queries_macro_name!(create) {
+                                #connection_expr.execute(__tql_query, &[])?;
+                            }
+                            Ok(__tql_count)
+                        })
+                }
+            },
+            QueryType::Drop => {
+                let auxiliary_queries_macro_name = Ident::new(&format!("tql_{}_auxiliary_queries", args.table_name),
+                    Span::call_site());
+                quote! {
+                    #auxiliary_queries_macro_name!(drop).iter()
+                        .fold(Ok(0), |__tql_result, __tql_query|
+                            __tql_result.and_then(|_| #connection_expr.execute(__tql_query, &[])))
+                        .and_then(|_| #connection_expr.prepare(#sql_query))
+                        .and_then(|mut result| result.execute(&[]))
+                }
+            },
+            QueryType::Exists => {
+                quote! {
+                    #connection_expr.prepare(#sql_query)
+                        .and_then(|mut #result_ident| {
+                            #result_ident.query_map(&#args_expr, |__tql_item_row| -> bool {
+                                    __tql_item_row.get(0)
+                                })?
+                                .next()
+                                .ok_or_else(|| #rusqlite_ident::Error::QueryReturnedNoRows)?
+                        })
                 }
             },
             QueryType::InsertOne => {
@@ -104,14 +163,16 @@ impl BackendGen for SqliteBackend {
                         .map(|_| #connection_expr.last_insert_rowid() as i32) // FIXME: don't cast?
                 }
             },
-            QueryType::SelectMulti => {
+            QueryType::SelectMulti | QueryType::SelectPage => {
                 quote! {
                     #connection_expr.prepare(#sql_query)
                         .and_then(|mut #result_ident| {
                             let #result_ident = #result_ident.query_map(&#args_expr, |__tql_item_row| {
                                     #struct_expr
                                 })?;
-                            #result_ident.collect::<Result<Vec<_>, _>>()
+                            #[allow(unused_mut)]
+                            let mut __tql_items = #result_ident.collect::<Result<Vec<_>, _>>()?;
+                            Ok(#items_expr)
                             // TODO: return an iterator instead of a vector.
                         })
                 }
@@ -120,11 +181,13 @@ impl BackendGen for SqliteBackend {
                 quote! {
                     #connection_expr.prepare(#sql_query)
                         .and_then(|mut #result_ident| {
-                            #result_ident.query_map(&#args_expr, |__tql_item_row| {
+                            #[allow(unused_mut)]
+                            let mut __tql_item = #result_ident.query_map(&#args_expr, |__tql_item_row| {
                                     #struct_expr
                                 })?
                                 .next()
-                                .ok_or_else(|| #rusqlite_ident::Error::QueryReturnedNoRows)?
+                                .ok_or_else(|| #rusqlite_ident::Error::QueryReturnedNoRows)??;
+                            Ok(#items_expr)
                         })
                 }
             },
@@ -144,6 +207,80 @@ impl BackendGen for SqliteBackend {
         })
     }
 
+    /// Generate a method executing the `query` on the junction table of a `ManyToMany` field with
+    /// the primary key of the table struct and a row of the `target_table`.
+    fn junction_method(&self, name: &Ident, primary_key: &Ident, target_table: &Ident, query: &str) -> TokenStream {
+        quote! {
+            #[allow(dead_code)]
+            pub fn #name(&self, connection: &::rusqlite::Connection, row: &#target_table) -> ::rusqlite::Result<()> {
+                connection.execute(#query, &[&self.#primary_key, row])
+                    .map(|_| ())
+            }
+        }
+    }
+
+    fn params_expr(&self, params: &[(TokenStream, bool)]) -> TokenStream {
+        if params.iter().any(|&(_, list)| list) {
+            // Each element of a list is sent as a separate parameter.
+            let to_sql_ident = quote! { ::rusqlite::types::ToSql };
+            let params = params.iter()
+                .map(|&(ref param, list)|
+                    if list {
+                        quote! { .chain(#param.iter().map(|__tql_param| __tql_param as &dyn #to_sql_ident)) }
+                    }
+                    else {
+                        quote! { .chain(::std::iter::once(&#param as &dyn #to_sql_ident)) }
+                    }
+                );
+            quote! {
+                ::std::iter::empty::<&dyn #to_sql_ident>()
+                    #(#params)*
+                    .collect::<Vec<_>>()
+            }
+        }
+        else {
+            let params = params.iter().map(|(param, _)| param);
+            quote! {
+                [#(&#params),*]
+            }
+        }
+    }
+
+    /// Generate the code loading the rows of the related `table` associated to one of the table
+    /// structs in `$items`.
+    /// The `query` selects these rows with the primary key of their table struct and ends with the
+    /// column to compare to this primary key.
+    fn related_rows_query(&self, primary_key: &Ident, field: &Ident, table: &Ident, query: TokenStream) -> TokenStream {
+        let to_sql_ident = quote! { ::rusqlite::types::ToSql };
+        let load_chunk = quote! {
+            let __tql_query = ::tql::expand_list_params(concat!(#query, " IN ($1)"), &[(1, __tql_chunk.len())]);
+            let mut __tql_statement = $connection.prepare(&__tql_query)?;
+            let __tql_params = __tql_chunk.iter()
+                .map(|__tql_pk| __tql_pk as &dyn #to_sql_ident)
+                .collect::<Vec<_>>();
+            let __tql_rows = __tql_statement.query_map(&__tql_params, |__tql_item_row| {
+                    let __tql_pk: ::tql::PrimaryKey = __tql_item_row.get(<#table as ::tql::SqlTable>::FIELD_COUNT);
+                    (__tql_pk, <#table as ::tql::SqlTable>::from_row(__tql_item_row))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            __tql_children.extend(__tql_rows);
+        };
+        quote! {
+            let __tql_parents: &mut [_] = $items;
+            let __tql_pks: Vec<::tql::PrimaryKey> = __tql_parents.iter()
+                .map(|__tql_parent| __tql_parent.#primary_key)
+                .collect();
+            let mut __tql_children = vec![];
+            // The number of primary keys is only known at runtime and SQLite limits the number of
+            // parameters of a query, hence the rows are loaded for a chunk of primary keys at a time.
+            for __tql_chunk in __tql_pks.chunks(::tql::SQLITE_MAX_VARIABLES) {
+                #load_chunk
+            }
+            ::tql::add_related_rows(__tql_parents, __tql_children, |__tql_parent| __tql_parent.#primary_key,
+                |__tql_parent| &mut __tql_parent.#field);
+        }
+    }
+
     fn row_type_ident(&self, table_ident: &Ident) -> TokenStream {
         quote_spanned! { table_ident.span() =>
             ::rusqlite::Row
@@ -20,7 +20,6 @@
  */
 
 /*
- * TODO: ManyToMany.
  * TODO: looks like the function annotate does not exist anymore.
  * TODO: return an iterator instead of a Vec.
  * TODO: add support for default value.
@@ -28,7 +27,6 @@
  *
  * TODO: support the missing types
  * (https://docs.rs/postgres/0.15.1/postgres/types/trait.ToSql.html).
- * TODO: support intermediate models in ManyToMany (allow adding extra fields).
  * TODO: error for unsupported types in backends.
  * TODO: remove useless empty string ("") in generated code (concat!("", "")).
  * TODO: avoid using quote_spanned and respan when possible and document all of their usage.
@@ -39,22 +37,15 @@
  * TODO: show a better error when using a type that is not a table (both in ForeignKey<_> and in
  * sql!(_.all())).
  * FIXME: escape name like `Table` to avoid error.
- * FIXME: error when having mutiple ForeignKey with the same table (then support having multiple
- * ForeignKey).
  * TODO: document the management of the connection.
  * TODO: use as_ref() for Ident instead of &ident.to_string().
- * TODO: support recursive foreign key.
  * TODO: write fail tests for stable using include!().
  * TODO: try to hide Option in the mismatched type error message for ForeignKey.
  * TODO: use fully-qualified name everywhere in the query (aggregate, …).
  *
  * TODO: support other types (uuid, string) for the primary key, possibly by making it generic.
- * TODO: allow using other fields in filter(), update(), … like F() expressions in Django
- ** Table.filter(field1 > Table.field2) may not work.
- ** Table.filter(field1 > $field2)
  * TODO: unique constraints.
  * TODO: support primary key with multiple columns.
- * TODO: allow selecting only some fields.
  * TODO: join on non foreign key.
  * TODO: allow user-defined functions (maybe with partial query?) and types.
  * TODO: add table_name attribute to allow changing the table name.
@@ -117,7 +108,9 @@ use proc_macro2::TokenStream as Tokens;
 
 use syn::{
     Expr,
+    Fields,
     Ident,
+    Index,
     Item,
     ItemEnum,
     parse,
@@ -131,10 +124,19 @@ use analyzer::{
     analyze_methods,
     analyze_types,
     get_aggregate_calls,
+    get_annotation_idents,
+    get_arithmetic_filters,
+    get_cursor_fields,
+    get_field_references,
+    get_function_operands,
     get_insert_idents,
     get_limit_args,
+    get_distinct_idents,
     get_method_calls,
+    get_order_method_calls,
+    get_projection_idents,
     get_sort_idents,
+    get_subquery_fields,
     get_values_idents,
 };
 #[cfg(feature = "unstable")]
@@ -142,9 +144,13 @@ use analyzer::get_insert_position;
 use arguments::{Arg, Args, arguments};
 use ast::{
     Aggregate,
-    Expression,
+    Annotation,
+    FieldPath,
     Join,
     MethodCall,
+    Operand,
+    Pagination,
+    Projection,
     Query,
     QueryType,
     query_type,
@@ -157,29 +163,45 @@ use gen::{
     generate_errors,
     gen_query,
     get_struct_fields,
+    many_to_many_methods,
+    params_expr,
+    row_macro,
+    row_methods,
     table_macro,
     table_methods,
     tosql_impl,
 };
 use optimizer::optimize;
-use parser::Parser;
+use parser::{Parser, replace_field_references};
 use stable::generate_macro_patterns;
+use state::methods_singleton;
 
+// NOTE: some fields are only read by the code generators of the backends.
+#[cfg_attr(not(any(feature = "postgres", feature = "rusqlite")), allow(dead_code))]
 struct SqlQueryWithArgs {
-    aggregate_calls: Vec<(String, Expr)>,
+    aggregate_calls: Vec<(String, Operand, Expr)>,
     aggregates: Vec<Aggregate>,
+    annotations: Vec<Annotation>,
     arguments: Args,
-    filter_method_calls: Vec<(MethodCall, Option<Expression>)>,
-    idents: Vec<Ident>,
+    arithmetic_filters: Vec<(Operand, Operand)>,
+    cursor_fields: Vec<Ident>,
+    field_references: Vec<(Ident, Ident)>,
+    filter_method_calls: Vec<(MethodCall, Option<Operand>)>,
+    idents: Vec<FieldPath>,
     #[cfg(feature = "unstable")]
     insert_call_span: Option<Span>,
     insert_idents: Option<Vec<Ident>>,
     joins: Vec<Join>,
     limit_exprs: Vec<Expr>,
     literal_arguments: Args,
+    order_method_calls: Vec<MethodCall>,
+    projection: Projection,
     query_type: QueryType,
+    related: Vec<Ident>,
+    reverse_items: bool,
     sql: Tokens,
     stable_macro_query: Tokens,
+    subquery_fields: Vec<(Ident, Ident, Ident)>,
     table_name: Ident,
 }
 
@@ -189,8 +211,9 @@ struct SqlQueryWithArgs {
 #[cfg(feature = "unstable")]
 #[proc_macro]
 pub fn sql(input: TokenStream) -> TokenStream {
+    let input = replace_field_references(input.into());
     let arguments: Arguments =
-        match parse(input) {
+        match parse2(input) {
             Ok(args) => args,
             Err(error) => return generate_errors(vec![Error::new(
                     &format!("cannot parse expression in sql!(): {}", error), Span::call_site())]),
@@ -238,7 +261,7 @@ fn to_sql_query(input: proc_macro2::TokenStream) -> Result<SqlQueryWithArgs> {
                                              input.span(), "E0061")]);
     }
     let expr: Expr =
-        match parse2(input) {
+        match parse2(replace_field_references(input)) {
             Ok(expr) => expr,
             Err(error) => return Err(vec![Error::new(&error.to_string(), Span::call_site())]),
         };
@@ -257,24 +280,54 @@ fn to_sql_query(input: proc_macro2::TokenStream) -> Result<SqlQueryWithArgs> {
             Query::Select { ref joins, .. } => joins.clone(),
             _ => vec![],
         };
+    let projection =
+        match query {
+            Query::Select { ref projection, .. } => projection.clone(),
+            _ => Projection::All,
+        };
+    let related =
+        match query {
+            Query::Select { ref related, .. } => related.clone(),
+            _ => vec![],
+        };
     let aggregates: Vec<Aggregate> =
         match query {
             Query::Aggregate { ref aggregates, .. } => aggregates.clone(),
             _ => vec![],
         };
+    let annotations: Vec<Annotation> =
+        match query {
+            Query::Aggregate { ref annotations, .. } | Query::Select { ref annotations, .. } => annotations.clone(),
+            _ => vec![],
+        };
     let query_type = query_type(&query);
     let mut idents = get_sort_idents(&query);
     idents.extend(get_values_idents(&query));
+    idents.extend(get_projection_idents(&query).into_iter().map(FieldPath::new));
+    idents.extend(get_distinct_idents(&query).into_iter().map(FieldPath::new));
+    idents.extend(get_annotation_idents(&query));
     let insert_idents = get_insert_idents(&query);
     let limit_exprs = get_limit_args(&query);
     let filter_method_calls = get_method_calls(&query);
+    let order_method_calls = get_order_method_calls(&query);
+    let field_references = get_field_references(&query);
+    let mut arithmetic_filters = get_arithmetic_filters(&query);
+    arithmetic_filters.extend(get_function_operands(&query));
     let aggregate_calls = get_aggregate_calls(&query);
+    let subquery_fields = get_subquery_fields(&query);
+    let cursor_fields = get_cursor_fields(&query);
+    // NOTE: the rows before the cursor are fetched in reverse order.
+    let reverse_items = matches!(query, Query::Select { pagination: Pagination::Before(_), .. });
     let stable_macro_query = generate_macro_patterns(&query, &method_calls);
     let (arguments, literal_arguments) = arguments(query);
     Ok(SqlQueryWithArgs {
         aggregates,
+        annotations,
         aggregate_calls,
         arguments,
+        arithmetic_filters,
+        cursor_fields,
+        field_references,
         filter_method_calls,
         idents,
         #[cfg(feature = "unstable")]
@@ -283,16 +336,53 @@ fn to_sql_query(input: proc_macro2::TokenStream) -> Result<SqlQueryWithArgs> {
         joins,
         limit_exprs,
         literal_arguments,
+        order_method_calls,
+        projection,
         query_type,
+        related,
+        reverse_items,
         sql,
         stable_macro_query,
+        subquery_fields,
         table_name,
     })
 }
 
+/// Expand the `#[derive(SqlRow)]` attribute.
+/// This attribute must be used on structs filled by the only() method.
+#[proc_macro_derive(SqlRow)]
+pub fn sql_row(input: TokenStream) -> TokenStream {
+    let item: Item =
+        match parse(input) {
+            Ok(item) => item,
+            Err(error) => return generate_errors(vec![Error::new(
+                    &format!("cannot parse expression in SqlRow: {}", error), Span::call_site())]),
+        };
+
+    let mut compiler_errors = quote! {};
+    if let Item::Struct(item_struct) = item {
+        if let Fields::Named(_) = item_struct.fields {
+            let methods = row_methods(&item_struct);
+            let row_macro = row_macro(&item_struct);
+            let code = quote! {
+                #methods
+                #row_macro
+            };
+            return code.into();
+        }
+        let error = Error::new("Expected normal struct, found", item_struct.span()); // TODO: improve this message.
+        add_error(error, &mut compiler_errors);
+    }
+    else {
+        let error = Error::new("Expected struct but found", item.span()); // TODO: improve this message.
+        add_error(error, &mut compiler_errors);
+    }
+    compiler_errors.into()
+}
+
 /// Expand the `#[SqlTable]` attribute.
 /// This attribute must be used on structs to tell tql that it represents an SQL table.
-#[proc_macro_derive(SqlTable)]
+#[proc_macro_derive(SqlTable, attributes(sql))]
 pub fn sql_table(input: TokenStream) -> TokenStream {
     let item: Item =
         match parse(input) {
@@ -319,11 +409,13 @@ pub fn sql_table(input: TokenStream) -> TokenStream {
             let code = tosql_impl(&item_struct, primary_key);
             let methods = table_methods(&item_struct);
             let table_macro = table_macro(&item_struct);
+            let many_to_many_methods = many_to_many_methods(&item_struct);
             let code = quote! {
                 #errors
                 #methods
                 #code
                 #table_macro
+                #many_to_many_methods
             };
             concat_token_stream(code.into(), impls)
         }
@@ -361,11 +453,40 @@ fn respan_with(tokens: TokenStream, span: proc_macro::Span) -> TokenStream {
     FromIterator::from_iter(result.into_iter())
 }
 
+/// Get the struct containing the fields: either the table struct `ident`, a new struct of the
+/// table of a `subquery`, an element of a `many_to_many` field or, when there's a `foreign_key`, the
+/// struct of the joined table.
+fn field_object(ident: &Ident, foreign_key: Option<&Ident>, subquery: Option<&Ident>, many_to_many: Option<&Ident>,
+                table_ident: &Ident) -> Tokens
+{
+    let trait_ident = quote_spanned! { table_ident.span() =>
+        ::tql::SqlTable
+    };
+    let object =
+        match (subquery, many_to_many) {
+            (Some(table), _) => quote_spanned! { table.span() =>
+                <#table as #trait_ident>::_tql_default()
+            },
+            (None, Some(field)) => quote_spanned! { field.span() =>
+                #ident.#field[0]
+            },
+            (None, None) => quote! { #ident },
+        };
+    match foreign_key {
+        Some(foreign_key) => {
+            quote_spanned! { foreign_key.span() =>
+                ::tql::ForeignKeyField::related_row_or_default(&mut #object.#foreign_key)
+            }
+        },
+        None => object,
+    }
+}
+
 /// Get the arguments to send to the `postgres::stmt::Statement::query` or
 /// `postgres::stmt::Statement::execute` method.
 fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
     let table_ident = &args.table_name;
-    let mut arg_refs = vec![];
+    let mut params = vec![];
     let mut fns = vec![];
     let mut assigns = vec![];
     let mut typechecks = vec![];
@@ -373,9 +494,7 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
     let mut metavars = vec![];
     #[cfg(feature = "unstable")]
     let metavars = vec![];
-    let mut next_name = (0..).map(|counter|
-        Ident::new(&format!("__tql_arg{}", counter), Span::call_site())
-    );
+    let mut next_name = (0..).map(argument_name);
 
     let ident = Ident::new("__tql_table", Span::call_site());
     {
@@ -385,6 +504,22 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
                     Expr::Lit(_) => None,
                     _ => Some(next_name.next().expect("Next name")),
                 };
+            #[cfg(not(feature = "unstable"))]
+            let expr = arg_name.clone().map(|arg_name| quote! { #arg_name }).unwrap_or_else(|| {
+                let expr = &arg.expression;
+                quote! { #expr }
+            });
+            // NOTE: a list is used several times, so it is bound to a variable by bind_list_arguments().
+            #[cfg(feature = "unstable")]
+            let expr =
+                if arg.list {
+                    let name = arg_name.clone().expect("list argument name");
+                    quote! { #name }
+                }
+                else {
+                    let expr = &arg.expression;
+                    quote! { #expr }
+                };
             if let Some(name) = arg.field_name.as_ref()
                 .map(|name| {
                     let pos = name.span();
@@ -395,23 +530,52 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
                     Ident::new(&name[index..], pos)
                 })
             {
+                let object = field_object(&ident, arg.foreign_key.as_ref(), arg.table.as_ref(),
+                                          arg.many_to_many.as_ref(), table_ident);
                 let convert_ident = Ident::new("__tql_convert", arg.expression.span());
                 let to_owned_ident = Ident::new("to_owned", Span::call_site());
-                #[cfg(not(feature = "unstable"))]
-                let expr = arg_name.clone().map(|arg_name| quote! { #arg_name }).unwrap_or_else(|| {
-                    let expr = &arg.expression;
-                    quote! { #expr }
-                });
-                #[cfg(feature = "unstable")]
-                let expr = &arg.expression;
+                if arg.list {
+                    assigns.push(quote_spanned! { arg.expression.span() =>
+                        #object.#name = #convert_ident(&(#expr)[..]);
+                    });
+                    fns.push(quote_spanned! { arg.expression.span() =>
+                        // NOTE: hack to get the type of the elements of the slice.
+                        fn #convert_ident<T: Clone>(_arg: &[T]) -> T {
+                            unimplemented!()
+                        }
+                    });
+                }
+                else {
+                    assigns.push(quote_spanned! { arg.expression.span() =>
+                        #object.#name = #convert_ident(&#expr.#to_owned_ident());
+                    });
+                    fns.push(quote_spanned! { arg.expression.span() =>
+                        // NOTE: hack to get the type required by the field struct.
+                        fn #convert_ident<T: ::std::ops::Deref>(_arg: T) -> T::Target
+                        where T::Target: Sized
+                        {
+                            unimplemented!()
+                        }
+                    });
+                }
+            }
+            else if !arg.cursor_fields.is_empty() {
+                let values_ident = Ident::new("__tql_cursor_values", arg.expression.span());
+                let values = arg.cursor_fields.iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        let index = Index::from(index);
+                        quote! {
+                            #ident.#field = __tql_values.#index;
+                        }
+                    });
                 assigns.push(quote_spanned! { arg.expression.span() =>
-                    #ident.#name = #convert_ident(&#expr.#to_owned_ident());
+                    let __tql_values = #values_ident(&#expr);
+                    #(#values)*
                 });
                 fns.push(quote_spanned! { arg.expression.span() =>
-                    // NOTE: hack to get the type required by the field struct.
-                    fn #convert_ident<T: ::std::ops::Deref>(_arg: T) -> T::Target
-                    where T::Target: Sized
-                    {
+                    // NOTE: hack to get the type of the values of the cursor.
+                    fn #values_ident<T>(_cursor: &::tql::Cursor<T>) -> T {
                         unimplemented!()
                     }
                 });
@@ -421,6 +585,25 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
 
         for arg in &args.arguments {
             let _name = add_arg(&arg);
+            if !arg.cursor_fields.is_empty() {
+                // Each value of the cursor is sent as a separate parameter.
+                #[cfg(not(feature = "unstable"))]
+                let cursor = {
+                    let name = _name.expect("Cursor argument name");
+                    metavars.push(quote! { #name });
+                    quote! { #name }
+                };
+                #[cfg(feature = "unstable")]
+                let cursor = {
+                    let expr = &arg.expression;
+                    quote! { (#expr) }
+                };
+                for index in 0..arg.cursor_fields.len() {
+                    let index = Index::from(index);
+                    params.push((quote! { ::tql::Cursor::values(&#cursor).#index }, false));
+                }
+                continue;
+            }
             match arg.expression {
                 // Do not add literal arguments as they are in the final string literal.
                 Expr::Lit(_) => (),
@@ -429,17 +612,23 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
                     {
                         if let Some(name) = _name {
                             metavars.push(quote! { #name });
-                            arg_refs.push(quote! { &#name })
+                            params.push((quote! { #name }, arg.list));
                         }
                         else {
                             let expr = &arg.expression;
-                            arg_refs.push(quote! { &(#expr) });
+                            params.push((quote! { (#expr) }, arg.list));
                         }
                     }
                     #[cfg(feature = "unstable")]
                     {
-                        let expr = &arg.expression;
-                        arg_refs.push(quote! { &(#expr) });
+                        if arg.list {
+                            let name = _name.expect("list argument name");
+                            params.push((quote! { #name }, true));
+                        }
+                        else {
+                            let expr = &arg.expression;
+                            params.push((quote! { (#expr) }, false));
+                        }
                     }
                 },
             }
@@ -450,9 +639,30 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
         }
     }
 
-    for name in &args.idents {
+    for field in &args.idents {
+        let object = field_object(&ident, field.foreign_key.as_ref(), None, None, table_ident);
+        let name = &field.field;
         typechecks.push(quote_spanned! { name.span() =>
-            #ident.#name = unsafe { ::std::mem::zeroed() };
+            #object.#name = unsafe { ::std::mem::zeroed() };
+        });
+    }
+
+    for (field, reference) in &args.field_references {
+        typechecks.push(quote_spanned! { reference.span() => {
+            #ident.#field = __tql_field_type(&#ident.#reference);
+        }});
+    }
+
+    for (field, table, selected_field) in &args.subquery_fields {
+        typechecks.push(quote_spanned! { selected_field.span() => {
+            #ident.#field = __tql_field_type(&<#table as ::tql::SqlTable>::_tql_default().#selected_field);
+        }});
+    }
+
+    if let Projection::Struct(ref struct_ident) = args.projection {
+        let macro_name = Ident::new(&format!("tql_{}_check_fields", struct_ident), struct_ident.span());
+        typechecks.push(quote! {
+            #macro_name!(#ident);
         });
     }
 
@@ -475,19 +685,20 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
         typechecks.push(code);
     }
 
+    let trait_ident = quote_spanned! { table_ident.span() =>
+        tql::ToTqlType;
+    };
+    let method_name = quote_spanned! { table_ident.span() =>
+        to_tql_type
+    };
     for data in &args.filter_method_calls {
         let call = &data.0;
         let field = &call.object_name;
         let method = &call.method_name;
         let arguments = &call.arguments;
-        let trait_ident = quote_spanned! { table_ident.span() =>
-            tql::ToTqlType;
-        };
-        let method_name = quote_spanned! { table_ident.span() =>
-            to_tql_type
-        };
         let comparison_expr =
-            if let Some(ref expr) = data.1 {
+            if let Some(ref operand) = data.1 {
+                let expr = operand_to_tokens(operand, &ident);
                 quote! {
                     let mut _data = #field.#method(#(#arguments),*);
                     _data = #expr;
@@ -505,27 +716,79 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
         }});
     }
 
+    for call in &args.order_method_calls {
+        let field = &call.object_name;
+        let method = &call.method_name;
+        let arguments = &call.arguments;
+        typechecks.push(quote_spanned! { call.position => {
+            use #trait_ident;
+            let #field = #ident.#field.#method_name();
+            let _ = #field.#method(#(#arguments),*);
+        }});
+    }
+
+    let methods = methods_singleton();
+    let method_calls = args.filter_method_calls.iter()
+        .map(|data| &data.0)
+        .chain(&args.order_method_calls);
+    for call in method_calls {
+        let is_fulltext = methods.get(&call.method_name.to_string())
+            .map(|method| method.fulltext)
+            .unwrap_or(false);
+        if is_fulltext {
+            let macro_name = Ident::new(&format!("tql_{}_check_fulltext", table_ident), call.position);
+            let field = &call.object_name;
+            typechecks.push(quote_spanned! { call.position =>
+                #macro_name!(#field);
+            });
+        }
+    }
+
+    let macro_name = Ident::new(&format!("tql_{}_check_not_nullable", table_ident), Span::call_site());
+    for field in &args.cursor_fields {
+        typechecks.push(quote_spanned! { field.span() =>
+            #macro_name!(#field);
+        });
+    }
+
+    for (operand1, operand2) in &args.arithmetic_filters {
+        let expr1 = operand_to_tokens(operand1, &ident);
+        let expr2 = operand_to_tokens(operand2, &ident);
+        typechecks.push(quote! {{
+            let mut _data = #expr1;
+            _data = #expr2;
+        }});
+    }
+
     let tql_ident = quote_spanned! { Span::call_site() =>
         ::tql
     };
-    for &(ref function, ref expr) in &args.aggregate_calls {
+    for (function, argument, expr) in &args.aggregate_calls {
         let function = Ident::new(function, Span::call_site());
-        typechecks.push(quote! {
-            let mut _data = #tql_ident::aggregates::#function();
+        let argument = operand_to_tokens(argument, &ident);
+        typechecks.push(quote! {{
+            let mut _data = #tql_ident::aggregates::#function(&(#argument));
             _data = #expr;
-        });
+        }});
     }
 
     let trait_ident = quote_spanned! { table_ident.span() =>
         ::tql::SqlTable
     };
 
+    let params_expr = params_expr(&params);
+
     let tokens = quote_spanned! { table_ident.span() => {
         // Type check the arguments by creating a dummy struct.
         // TODO: check that this let is not in the generated binary.
         {
             let _tql_closure = || {
                 let mut #ident = <#table_ident as #trait_ident>::_tql_default();
+                // NOTE: hack to check that an expression has the same type as a field.
+                #[allow(dead_code)]
+                fn __tql_field_type<T>(_field: &T) -> T {
+                    unimplemented!()
+                }
                 #({
                     #fns
                     #assigns
@@ -534,11 +797,89 @@ fn typecheck_arguments(args: &SqlQueryWithArgs) -> (Tokens, Vec<Tokens>) {
             };
         }
 
-        [#(#arg_refs),*]
+        #params_expr
     }};
     (tokens, metavars)
 }
 
+/// Convert the `operand` to a Rust expression having the same type.
+/// The fields referenced in the `operand` are taken from the dummy struct `table`.
+fn operand_to_tokens(operand: &Operand, table: &Ident) -> Tokens {
+    let tql_ident = quote_spanned! { Span::call_site() =>
+        ::tql
+    };
+    match *operand {
+        Operand::Arithmetic(ref operand1, operator, ref operand2) => {
+            let operand1 = operand_to_tokens(operand1, table);
+            // NOTE: adding an interval to a date gives a date of the same type.
+            if operand2.is_interval() {
+                let interval = operand_to_tokens(operand2, table);
+                return quote! { #tql_ident::functions::shift(#operand1, #interval) };
+            }
+            let operand2 = operand_to_tokens(operand2, table);
+            quote! { #operand1 #operator #operand2 }
+        },
+        Operand::Expression(ref expr) => quote! { #expr },
+        Operand::Field(_, ref field) => quote_spanned! { field.span() =>
+            __tql_field_type(&#table.#field)
+        },
+        Operand::Function(ref function) => {
+            let name = &function.name;
+            let arguments = function.arguments.iter().map(|argument| operand_to_tokens(argument, table));
+            quote! { #tql_ident::functions::#name(#(#arguments),*) }
+        },
+        Operand::Paren(ref operand) => {
+            let operand = operand_to_tokens(operand, table);
+            quote! { (#operand) }
+        },
+    }
+}
+
+/// Get the list arguments with the number of their parameter and the Rust expression of the list.
+/// These are needed by SQLite which expands a list parameter into multiple parameters.
+#[cfg(feature = "rusqlite")]
+fn list_arguments(args: &SqlQueryWithArgs) -> Vec<(usize, Tokens)> {
+    args.arguments.iter()
+        .enumerate()
+        .filter(|&(_, arg)| arg.list)
+        .map(|(index, _)| {
+            // NOTE: the literal arguments are not in args.arguments, so the index of the argument
+            // is the same as the index of the parameter and the index of its variable.
+            let name = argument_name(index);
+            (index + 1, quote! { #name })
+        })
+        .collect()
+}
+
+/// Get the name of the variable holding the argument at `index`.
+fn argument_name(index: usize) -> Ident {
+    Ident::new(&format!("__tql_arg{}", index), Span::call_site())
+}
+
+/// Bind the list arguments to variables before the generated `code`, since their expression is
+/// used several times: in the typecheck, for the number of parameters and to send the parameters.
+#[cfg(feature = "unstable")]
+fn bind_list_arguments(args: &SqlQueryWithArgs, code: Tokens) -> Tokens {
+    let bindings: Vec<_> = args.arguments.iter()
+        .enumerate()
+        .filter(|&(_, arg)| arg.list)
+        .map(|(index, arg)| {
+            let name = argument_name(index);
+            let expr = &arg.expression;
+            quote_spanned! { arg.expression.span() =>
+                let #name = #expr;
+            }
+        })
+        .collect();
+    if bindings.is_empty() {
+        return code;
+    }
+    quote! {{
+        #(#bindings)*
+        #code
+    }}
+}
+
 fn concat_token_stream(stream1: TokenStream, stream2: TokenStream) -> TokenStream {
     FromIterator::from_iter(stream1.into_iter().chain(stream2.into_iter()))
 }
@@ -21,6 +21,8 @@
 
 //! Methods definition for use in filters.
 
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+use state::{SqlFunctionTypes, functions_singleton};
 use state::{SqlMethodTypes, aggregates_singleton, methods_singleton};
 use types::Type;
 
@@ -32,13 +34,69 @@ pub fn add_method<'a, T: Into<Option<&'a str>>>(object_type: &Type, return_type:
 {
     let methods = methods_singleton();
     methods.insert(method.to_string(), SqlMethodTypes {
+        array_template: None,
         argument_types,
+        fulltext: false,
         object_type: object_type.clone(),
         return_type,
         template: template.into().map(ToString::to_string),
     });
 }
 
+/// Add a new `method` on the array fields.
+/// If a method with the same name exists for another type, like `len()`, it keeps its template and
+/// the `template` is only used on the array fields.
+#[cfg(feature = "postgres")]
+pub fn add_array_method<'a, T: Into<Option<&'a str>>>(return_type: Type, argument_types: Vec<Type>, method: &str,
+                                                      template: T)
+{
+    let methods = methods_singleton();
+    let template = template.into().map(ToString::to_string);
+    if let Some(existing_method) = methods.get_mut(method) {
+        existing_method.array_template = template;
+        return;
+    }
+    methods.insert(method.to_string(), SqlMethodTypes {
+        array_template: None,
+        argument_types,
+        fulltext: false,
+        object_type: Type::Array(Box::new(Type::Generic)),
+        return_type,
+        template,
+    });
+}
+
+/// Add a new full-text search `method`, taking the search terms, on the fields declared with
+/// #[sql(fulltext)].
+/// Besides the placeholders of `add_method()`, the template can use `$table` and `$field` as
+/// placeholders for the names of the table and of the field.
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+pub fn add_fulltext_method(return_type: Type, method: &str, template: &str) {
+    let methods = methods_singleton();
+    methods.insert(method.to_string(), SqlMethodTypes {
+        array_template: None,
+        argument_types: vec![Type::String],
+        fulltext: true,
+        object_type: Type::String,
+        return_type,
+        template: Some(template.to_string()),
+    });
+}
+
+/// Add a new `function` evaluated by the database, taking arguments of type `argument_types`.
+/// The template is the resulting SQL with `$1`, `$2`, … as placeholders for the arguments.
+/// The template of an `interval` function also has `$0` as a placeholder for the date it is added
+/// to or subtracted from and `$op` as a placeholder for the operator (`+` or `-`).
+#[cfg(any(feature = "postgres", feature = "rusqlite"))]
+pub fn add_function(function: &str, argument_types: Vec<Type>, interval: bool, template: &str) {
+    let functions = functions_singleton();
+    functions.insert(function.to_string(), SqlFunctionTypes {
+        argument_types,
+        interval,
+        template: template.to_string(),
+    });
+}
+
 /// Add a new aggregate `rust_function` mapping to `sql_function`.
 pub fn add_aggregate(rust_function: &str, sql_function: &str) {
     let aggregates = aggregates_singleton();
@@ -48,6 +106,10 @@ pub fn add_aggregate(rust_function: &str, sql_function: &str) {
 /// Add the default SQL aggregate functions.
 pub fn add_initial_aggregates() {
     add_aggregate("avg", "AVG");
+    add_aggregate("count", "COUNT");
+    add_aggregate("max", "MAX");
+    add_aggregate("min", "MIN");
+    add_aggregate("sum", "SUM");
 }
 
 /// Add the default SQL methods.
@@ -97,16 +159,145 @@ pub fn add_initial_methods() {
     add_method(&Type::String, Type::I32, vec![], "len", "LENGTH($0)");
 
     #[cfg(feature = "postgres")]
-    add_method(&Type::String, Type::Bool, vec![Type::String], "regex", "$0 LIKE $1");
+    add_method(&Type::String, Type::Bool, vec![Type::String], "regex", "$0 ~ $1");
+    // NOTE: the REGEXP function is added to the connection by tql::add_regexp_function().
+    #[cfg(feature = "rusqlite")]
+    add_method(&Type::String, Type::Bool, vec![Type::String], "regex", "$0 REGEXP $1");
+
+    #[cfg(feature = "postgres")]
+    add_method(&Type::String, Type::Bool, vec![Type::String], "iregex", "$0 ~* $1");
+    #[cfg(feature = "rusqlite")]
+    add_method(&Type::String, Type::Bool, vec![Type::String], "iregex", "$0 REGEXP '(?i)' || $1");
+
+    // Full-text search methods.
+    // NOTE: PostgreSQL searches in the generated column $field_tsv while SQLite searches in the
+    // FTS5 table $table_$field_fts, both created by create().
+    #[cfg(feature = "postgres")]
+    add_fulltext_method(Type::Bool, "search", "$0_tsv @@ PLAINTO_TSQUERY('english', $1)");
+    #[cfg(feature = "rusqlite")]
+    add_fulltext_method(Type::Bool, "search",
+        "$table.rowid IN (SELECT rowid FROM $table_$field_fts WHERE $table_$field_fts MATCH $1)");
+
+    // NOTE: the rank of SQLite is negated since its best matches have the lowest rank.
+    #[cfg(feature = "postgres")]
+    add_fulltext_method(Type::F32, "rank", "TS_RANK($0_tsv, PLAINTO_TSQUERY('english', $1))");
+    #[cfg(feature = "rusqlite")]
+    add_fulltext_method(Type::F32, "rank",
+        "(SELECT -rank FROM $table_$field_fts WHERE $table_$field_fts MATCH $1 AND rowid = $table.rowid)");
+
+    // Json methods.
+    // NOTE: the value is compared as text on both backends, like the ->> operator of PostgreSQL.
+    #[cfg(feature = "postgres")]
+    add_method(&Type::Json, Type::String, vec![Type::String], "get", "$0 ->> $1");
+    #[cfg(feature = "rusqlite")]
+    add_method(&Type::Json, Type::String, vec![Type::String], "get", "CAST(JSON_EXTRACT($0, '$.' || $1) AS TEXT)");
+
+    #[cfg(feature = "postgres")]
+    add_method(&Type::Json, Type::Bool, vec![Type::String], "has_key", "$0 ? $1");
     #[cfg(feature = "rusqlite")]
-    add_method(&Type::String, Type::Bool, vec![Type::String], "regex", None);
+    add_method(&Type::Json, Type::Bool, vec![Type::String], "has_key", "JSON_TYPE($0, '$.' || $1) IS NOT NULL");
+
+    // Array methods.
+    // NOTE: the arrays are only supported by PostgreSQL.
+    // NOTE: the string methods are added first since len() and contains() exist for both types.
+    #[cfg(feature = "postgres")]
+    add_array_method(Type::Bool, vec![Type::Generic], "contains", "$1 = ANY($0)");
+    #[cfg(feature = "postgres")]
+    add_array_method(Type::I32, vec![], "len", "CARDINALITY($0)");
 
     #[cfg(feature = "postgres")]
-    add_method(&Type::String, Type::Bool, vec![Type::String], "iregex", "$0 ILIKE $1");
+    let array = Type::Array(Box::new(Type::Generic));
+    #[cfg(feature = "postgres")]
+    add_array_method(Type::Bool, vec![array.clone()], "contains_all", "$0 @> $1");
+    #[cfg(feature = "postgres")]
+    add_array_method(Type::Bool, vec![array], "overlaps", "$0 && $1");
+
+    // NOTE: the methods without a template are declared to report that they are not available.
     #[cfg(feature = "rusqlite")]
-    add_method(&Type::String, Type::Bool, vec![Type::String], "iregex", "$0 LIKE $1");
+    for &method in &["contains_all", "overlaps"] {
+        add_method(&Type::Generic, Type::Bool, vec![Type::Generic], method, None);
+    }
 
     // Option methods.
     add_method(&Type::Nullable(Box::new(Type::Generic)), Type::Bool, vec![], "is_some", "$0 IS NOT NULL");
     add_method(&Type::Nullable(Box::new(Type::Generic)), Type::Bool, vec![], "is_none", "$0 IS NULL");
 }
+
+/// Add the default SQL functions.
+pub fn add_initial_functions() {
+    // NOTE: SQLite stores the dates as text, so the dates computed by SQLite use the same format as
+    // the ones sent by rusqlite, which is "YYYY-MM-DD" for a date and "YYYY-MM-DDTHH:MM:SS" for a
+    // date and time.
+    #[cfg(feature = "postgres")]
+    add_function("now", vec![], false, "CURRENT_TIMESTAMP");
+    #[cfg(feature = "rusqlite")]
+    add_function("now", vec![], false, "STRFTIME('%Y-%m-%dT%H:%M:%S', 'now')");
+
+    #[cfg(feature = "postgres")]
+    add_function("current_date", vec![], false, "CURRENT_DATE");
+    #[cfg(feature = "rusqlite")]
+    add_function("current_date", vec![], false, "DATE('now')");
+
+    // Interval functions.
+    // NOTE: the number is cast since the secs argument of MAKE_INTERVAL() is a float.
+    #[cfg(feature = "postgres")]
+    let units = [
+        ("years", "years"),
+        ("months", "months"),
+        ("days", "days"),
+        ("hours", "hours"),
+        ("minutes", "mins"),
+        ("seconds", "secs"),
+    ];
+    #[cfg(feature = "postgres")]
+    for &(function, unit) in &units {
+        add_function(function, vec![Type::I32], true,
+            &format!("$0 $op MAKE_INTERVAL({} => CAST($1 AS INT))", unit));
+    }
+
+    #[cfg(feature = "rusqlite")]
+    let units = [
+        ("years", "years"),
+        ("months", "months"),
+        ("days", "days"),
+        ("hours", "hours"),
+        ("minutes", "minutes"),
+        ("seconds", "seconds"),
+    ];
+    #[cfg(feature = "rusqlite")]
+    for &(function, unit) in &units {
+        add_function(function, vec![Type::I32], true,
+            &format!("CASE WHEN LENGTH($0) = 10 THEN DATE($0, $op$1 || ' {unit}') \
+                      ELSE STRFTIME('%Y-%m-%dT%H:%M:%S', $0, $op$1 || ' {unit}') END", unit = unit));
+    }
+
+    // Date truncation functions.
+    #[cfg(feature = "postgres")]
+    let truncations = [
+        ("trunc_year", "year"),
+        ("trunc_month", "month"),
+        ("trunc_day", "day"),
+        ("trunc_hour", "hour"),
+        ("trunc_minute", "minute"),
+    ];
+    #[cfg(feature = "postgres")]
+    for &(function, field) in &truncations {
+        add_function(function, vec![Type::Generic], false, &format!("DATE_TRUNC('{}', $1)", field));
+    }
+
+    // The first format is the one of a date and the second one is the one of a date and time.
+    #[cfg(feature = "rusqlite")]
+    let truncations = [
+        ("trunc_year", "%Y-01-01", "%Y-01-01T00:00:00"),
+        ("trunc_month", "%Y-%m-01", "%Y-%m-01T00:00:00"),
+        ("trunc_day", "%Y-%m-%d", "%Y-%m-%dT00:00:00"),
+        ("trunc_hour", "%Y-%m-%d", "%Y-%m-%dT%H:00:00"),
+        ("trunc_minute", "%Y-%m-%d", "%Y-%m-%dT%H:%M:00"),
+    ];
+    #[cfg(feature = "rusqlite")]
+    for &(function, date_format, date_time_format) in &truncations {
+        add_function(function, vec![Type::Generic], false,
+            &format!("CASE WHEN LENGTH($1) = 10 THEN STRFTIME('{}', $1) ELSE STRFTIME('{}', $1) END",
+                date_format, date_time_format));
+    }
+}
@@ -19,22 +19,33 @@
  * CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-use proc_macro2::Span;
+use std::iter::FromIterator;
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
 use syn::{
     Expr,
     Ident,
+    MethodTurbofish,
+    parse2,
 };
+use quote::ToTokens;
 use syn::spanned::Spanned;
 
 use ast::first_token_span;
 use error::{Error, Result, res};
 
+/// The name of the macro which replaces the field references (`$field`) before the query is parsed
+/// as a Rust expression.
+const FIELD_MACRO: &str = "__tql_field";
+
 /// A method call.
 #[derive(Debug)]
 pub struct MethodCall {
     pub args: Vec<Expr>,
     pub name: Ident,
     pub position: Span,
+    /// The type arguments, as in `only::<Struct>()`.
+    pub turbofish: Option<MethodTurbofish>,
 }
 
 /// A collection of method calls.
@@ -90,6 +101,7 @@ impl Parser {
                         name: call.method.clone(),
                         args,
                         position: expr.span(),
+                        turbofish: call.turbofish.clone(),
                     });
                 },
                 Expr::Path(ref path) => {
@@ -105,6 +117,7 @@ impl Parser {
                         name: Ident::new("limit", index.index.span()),
                         args: vec![*index.index.clone()],
                         position: expr.span(),
+                        turbofish: None,
                     });
                 }
                 _ => {
@@ -120,3 +133,66 @@ impl Parser {
         res(calls, errors)
     }
 }
+
+/// Replace the field references (`$field`) by `__tql_field!(field)` so that the query can be
+/// parsed as a Rust expression.
+pub fn replace_field_references(tokens: TokenStream) -> TokenStream {
+    let mut result = vec![];
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(ref group) => {
+                let mut new_group = Group::new(group.delimiter(), replace_field_references(group.stream()));
+                new_group.set_span(group.span());
+                result.push(TokenTree::Group(new_group));
+            },
+            TokenTree::Punct(ref punct) if punct.as_char() == '$' => {
+                let field =
+                    match tokens.peek() {
+                        Some(TokenTree::Ident(field)) => Some(field.clone()),
+                        _ => None,
+                    };
+                if let Some(field) = field {
+                    tokens.next();
+                    let macro_name = Ident::new(FIELD_MACRO, punct.span());
+                    result.extend(quote_spanned! { punct.span() =>
+                        #macro_name!(#field)
+                    });
+                }
+                else {
+                    result.push(token.clone());
+                }
+            },
+            _ => result.push(token.clone()),
+        }
+    }
+    TokenStream::from_iter(result)
+}
+
+/// Get the field identifier if the expression is a field reference (`$field`).
+pub fn field_reference(expr: &Expr) -> Option<Ident> {
+    if let Expr::Macro(ref macr) = *expr {
+        let path = &macr.mac.path;
+        if path.segments.len() == 1 &&
+            path.segments.first().expect("first segment in path").value().ident == FIELD_MACRO
+        {
+            return parse2(macr.mac.tts.clone()).ok();
+        }
+    }
+    None
+}
+
+/// Check if the expression contains a field reference (`$field`).
+pub fn has_field_reference(expr: &Expr) -> bool {
+    fn contains_field_macro(tokens: TokenStream) -> bool {
+        tokens.into_iter().any(|token|
+            match token {
+                TokenTree::Group(ref group) => contains_field_macro(group.stream()),
+                TokenTree::Ident(ref ident) => ident == FIELD_MACRO,
+                _ => false,
+            }
+        )
+    }
+
+    contains_field_macro(expr.into_token_stream())
+}
@@ -23,8 +23,8 @@
 
 use proc_macro2::TokenStream ;
 
-use ast::Aggregate;
-use sql::{SqlBackend, ToSql};
+use ast::{Aggregate, Nulls};
+use sql::SqlBackend;
 
 pub struct DummySqlBackend {}
 
@@ -32,13 +32,23 @@ pub fn create_sql_backend() -> DummySqlBackend {
     DummySqlBackend { }
 }
 
-impl ToSql for Aggregate {
-    fn to_sql(&self, _index: &mut usize) -> String {
+impl Aggregate {
+    pub fn call_to_tokens(&self, _call: TokenStream, _table: &str) -> TokenStream {
+        unreachable!("Enable one of the following features: sqlite, pg");
+    }
+}
+
+impl Nulls {
+    pub fn order_to_tokens(self, _value: TokenStream, _direction: &str) -> TokenStream {
         unreachable!("Enable one of the following features: sqlite, pg");
     }
 }
 
 impl SqlBackend for DummySqlBackend {
+    fn fulltext_queries(&self, _table: &str, _field: &str) -> (Vec<String>, Vec<String>) {
+        unreachable!("Enable one of the following features: sqlite, pg");
+    }
+
     fn insert_query(&self, _table: &str, _fields: &[String], _values: &[String]) -> TokenStream {
         unreachable!("Enable one of the following features: sqlite, pg");
     }
@@ -39,20 +39,36 @@ use ast::{
     AggregateFilter,
     AggregateFilters,
     AggregateFilterExpression,
+    Annotation,
+    ArithmeticOperator,
     Assignment,
     AssignmentOperator,
+    Combination,
+    Distinct,
     Expression,
+    FieldPath,
     Filter,
     FilterExpression,
     Filters,
     FilterValue,
     Join,
+    JoinType,
     Limit,
     LogicalOperator,
+    ManyToManyFilter,
     MethodCall,
+    Operand,
     Order,
+    OrderValue,
+    Pagination,
+    Projection,
     Query,
     RelationalOperator,
+    SelectResult,
+    SetOperator,
+    SqlFunction,
+    Subquery,
+    SubqueryFilter,
     TypedField,
 };
 use ast::Limit::{
@@ -64,7 +80,7 @@ use ast::Limit::{
     StartRange,
 };
 use plugin::string_literal;
-use state::methods_singleton;
+use state::{functions_singleton, methods_singleton};
 use types::Type;
 
 #[cfg(not(any(feature = "rusqlite", feature = "postgres")))]
@@ -75,51 +91,176 @@ use self::postgres::create_sql_backend;
 use self::sqlite::create_sql_backend;
 
 trait SqlBackend {
+    fn fulltext_queries(&self, table: &str, field: &str) -> (Vec<String>, Vec<String>);
     fn insert_query(&self, table: &str, fields: &[String], values: &[String]) -> TokenStream;
 }
 
+/// Get the queries creating and dropping what the full-text search on the `field` of the `table`
+/// needs: the first ones are executed after the table is created and the second ones before it is
+/// dropped.
+pub fn fulltext_queries(table: &str, field: &str) -> (Vec<String>, Vec<String>) {
+    create_sql_backend().fulltext_queries(table, field)
+}
+
 /// A generic trait for converting a value to SQL.
 pub trait ToSql {
     fn to_sql(&self, index: &mut usize) -> String;
 }
 
-impl ToSql for [Aggregate] {
-    fn to_sql(&self, index: &mut usize) -> String {
-        self.iter().map(|aggregate| aggregate.to_sql(index)).collect::<Vec<_>>().join(", ")
+impl Aggregate {
+    fn to_tokens(&self, table: &str) -> TokenStream {
+        let argument = self.argument.as_ref().expect("Aggregate argument");
+        let call = string_token(&format!("{}({})", self.sql_function, aggregate_operand_to_sql(argument)));
+        self.call_to_tokens(call, table)
     }
 }
 
-impl ToSql for AggregateFilter {
-    fn to_sql(&self, index: &mut usize) -> String {
-        self.operand1.to_sql(index) + " " +
-            &self.operator.to_sql(index) + " " +
-            &self.operand2.to_sql(index)
+/// Convert the argument of an aggregate function to SQL.
+/// The fields are not qualified because they are in the SELECT clause.
+fn aggregate_operand_to_sql(operand: &Operand) -> String {
+    match *operand {
+        Operand::Arithmetic(ref operand1, ref operator, ref operand2) => {
+            if let Operand::Function(ref function) = **operand2 {
+                if operand2.is_interval() {
+                    return function.to_sql(&aggregate_operand_to_sql(operand1), &operator.to_sql(&mut 1),
+                        aggregate_operand_to_sql);
+                }
+            }
+            aggregate_operand_to_sql(operand1) + " " +
+                &operator.to_sql(&mut 1) + " " +
+                &aggregate_operand_to_sql(operand2)
+        },
+        Operand::Expression(ref expression) => expression.to_sql(&mut 1),
+        Operand::Field(_, ref field) => field.to_string(),
+        Operand::Function(ref function) => function.to_sql("", "", aggregate_operand_to_sql),
+        Operand::Paren(ref operand) =>
+            "(".to_string() +
+            &aggregate_operand_to_sql(operand) +
+            ")",
     }
 }
 
-impl ToSql for AggregateFilterExpression {
-    fn to_sql(&self, index: &mut usize) -> String {
+impl AggregateFilter {
+    fn to_tokens(&self, table: &str, index: &mut usize) -> TokenStream {
+        let operand1 = self.operand1.to_tokens(table);
+        let operator = self.operator.to_sql(index);
+        let operand2 = self.operand2.to_sql(index);
+        quote! {
+            #operand1, " ", #operator, " ", #operand2
+        }
+    }
+}
+
+impl AggregateFilterExpression {
+    fn to_tokens(&self, table: &str, index: &mut usize) -> TokenStream {
         match *self {
-            AggregateFilterExpression::Filter(ref filter) => filter.to_sql(index),
-            AggregateFilterExpression::Filters(ref filters) => filters.to_sql(index),
-            AggregateFilterExpression::NegFilter(ref filter) =>
-                "NOT ".to_string() +
-                &filter.to_sql(index),
-            AggregateFilterExpression::NoFilters => "".to_string(),
-            AggregateFilterExpression::ParenFilter(ref filter) =>
-                "(".to_string() +
-                &filter.to_sql(index) +
-                ")",
-            AggregateFilterExpression::FilterValue(ref filter_value) => filter_value.node.to_sql(index),
+            AggregateFilterExpression::Filter(ref filter) => filter.to_tokens(table, index),
+            AggregateFilterExpression::Filters(ref filters) => filters.to_tokens(table, index),
+            AggregateFilterExpression::NegFilter(ref filter) => {
+                let filter = filter.to_tokens(table, index);
+                quote! {
+                    "NOT ", #filter
+                }
+            },
+            AggregateFilterExpression::NoFilters => quote! { "" },
+            AggregateFilterExpression::ParenFilter(ref filter) => {
+                let filter = filter.to_tokens(table, index);
+                quote! {
+                    "(", #filter, ")"
+                }
+            },
+            AggregateFilterExpression::FilterValue(ref filter_value) => filter_value.node.to_tokens(table),
         }
     }
 }
 
-impl ToSql for AggregateFilters {
-    fn to_sql(&self, index: &mut usize) -> String {
-        self.operand1.to_sql(index) + " " +
-            &self.operator.to_sql(index) + " " +
-            &self.operand2.to_sql(index)
+impl Annotation {
+    /// Convert the window function call to SQL.
+    /// In an aggregate query, the window can be ordered by the result of the `aggregates`.
+    fn to_tokens(&self, table: &str, qualify: bool, aggregates: &[Aggregate]) -> TokenStream {
+        let mut call = self.function.sql_function.clone() + "(";
+        if let Some(ref argument) = self.function.argument {
+            call += &aggregate_operand_to_sql(argument);
+        }
+        if let Some(ref offset) = self.offset {
+            call += ", ";
+            call += &offset.to_sql(&mut 1);
+        }
+        call += ") OVER (";
+        let partition_clause =
+            if !self.partition.is_empty() {
+                "PARTITION BY "
+            }
+            else {
+                ""
+            };
+        let order_clause =
+            match (self.partition.is_empty(), has_order_clauses(&self.order)) {
+                (_, false) => "",
+                (true, true) => "ORDER BY ",
+                (false, true) => " ORDER BY ",
+            };
+        let partition = sep_by(self.partition.iter().map(|field| field.to_tokens(table, qualify)), ", ");
+        let order = sep_by(self.order.iter().map(|order| window_order_to_tokens(order, table, qualify, aggregates)),
+            ", ");
+        let call = quote! {
+            #call, #partition_clause, #partition, #order_clause, #order, ")"
+        };
+        self.function.call_to_tokens(call, table)
+    }
+}
+
+fn annotations_to_tokens(annotations: &[Annotation], table: &str, qualify: bool, aggregates: &[Aggregate])
+    -> TokenStream
+{
+    let annotations = annotations.iter()
+        .map(|annotation| {
+            let annotation = annotation.to_tokens(table, qualify, aggregates);
+            quote! {
+                ", ", #annotation
+            }
+        });
+    quote! {
+        #(#annotations,)*
+    }
+}
+
+/// Convert the `order` of a window to SQL.
+/// The result of an aggregate cannot be referenced by its name in a window, so its call is
+/// repeated.
+fn window_order_to_tokens(order: &Order, table: &str, qualify: bool, aggregates: &[Aggregate]) -> TokenStream {
+    let aggregate = order.field()
+        .filter(|field| field.foreign_key.is_none())
+        .and_then(|field| aggregates.iter()
+            .find(|aggregate| aggregate.result_name.as_ref() == Some(&field.field)));
+    match (aggregate, order) {
+        (Some(aggregate), &Order::Ascending(_, nulls)) => nulls.order_to_tokens(aggregate.to_tokens(table), ""),
+        (Some(aggregate), &Order::Descending(_, nulls)) =>
+            nulls.order_to_tokens(aggregate.to_tokens(table), " DESC"),
+        _ => order.to_tokens(table, qualify),
+    }
+}
+
+impl AggregateFilters {
+    fn to_tokens(&self, table: &str, index: &mut usize) -> TokenStream {
+        let operand1 = self.operand1.to_tokens(table, index);
+        let operator = self.operator.to_sql(index);
+        let operand2 = self.operand2.to_tokens(table, index);
+        quote! {
+            #operand1, " ", #operator, " ", #operand2
+        }
+    }
+}
+
+impl ToSql for ArithmeticOperator {
+    fn to_sql(&self, _index: &mut usize) -> String {
+        match *self {
+            ArithmeticOperator::Add => "+",
+            ArithmeticOperator::Divide => "/",
+            ArithmeticOperator::Modulo => "%",
+            ArithmeticOperator::Mul => "*",
+            ArithmeticOperator::Sub => "-",
+        }.to_string()
     }
 }
 
@@ -130,9 +271,17 @@ impl ToSql for Assignment {
             identifier + &self.operator.node.to_sql(index) + &self.value.to_sql(index)
         }
         else {
+            let value =
+                // The value is the right operand of the operator, so it must be parenthesized.
+                if let Operand::Arithmetic(_, _, _) = self.value {
+                    format!("({})", self.value.to_sql(index))
+                }
+                else {
+                    self.value.to_sql(index)
+                };
             identifier.clone() +
                 &self.operator.node.to_sql(index).replace("{}", &identifier) +
-                &self.value.to_sql(index)
+                &value
         }
     }
 }
@@ -157,10 +306,37 @@ impl ToSql for AssignmentOperator {
 }
 
 impl FilterExpression {
+    /// Get the checks of the joins of the subqueries in the filter.
+    fn to_check(&self) -> TokenStream {
+        match *self {
+            FilterExpression::Exists(ref subquery) => subquery.to_check(),
+            FilterExpression::Filters(ref filters) => {
+                let check1 = filters.operand1.to_check();
+                let check2 = filters.operand2.to_check();
+                quote! {
+                    #check1
+                    #check2
+                }
+            },
+            FilterExpression::InSubquery(ref filter) => filter.subquery.to_check(),
+            FilterExpression::ManyToMany(ref filter) => filter.to_check(),
+            FilterExpression::NegFilter(ref filter) | FilterExpression::ParenFilter(ref filter) => filter.to_check(),
+            FilterExpression::Filter(_) | FilterExpression::FilterValue(_) | FilterExpression::NoFilters => quote! {},
+        }
+    }
+
     fn to_tokens(&self, index: &mut usize) -> TokenStream {
         match *self {
+            FilterExpression::Exists(ref subquery) => {
+                let subquery = subquery.to_tokens(index);
+                quote! {
+                    "EXISTS (", #subquery, ")"
+                }
+            },
             FilterExpression::Filter(ref filter) => filter.to_tokens(index),
             FilterExpression::Filters(ref filters) => filters.to_tokens(index),
+            FilterExpression::InSubquery(ref filter) => filter.to_tokens(index),
+            FilterExpression::ManyToMany(ref filter) => filter.to_tokens(index),
             FilterExpression::NegFilter(ref filter) => {
                 let filter = filter.to_tokens(index);
                 quote! {
@@ -174,7 +350,7 @@ impl FilterExpression {
                     "(", #filter, ")"
                 }
             }
-            FilterExpression::FilterValue(ref filter_value) => filter_value.node.to_tokens(),
+            FilterExpression::FilterValue(ref filter_value) => filter_value.node.to_tokens(index),
         }
     }
 }
@@ -203,36 +379,97 @@ impl ToSql for [Ident] {
     }
 }
 
+impl FieldPath {
+    /// Convert the field to SQL, qualifying the field of a joined table with the alias of this table.
+    /// The fields of `table` are only qualified when `qualify` is true, which is needed when there
+    /// are joins since the same table may be joined onto itself.
+    fn to_tokens(&self, table: &str, qualify: bool) -> TokenStream {
+        match self.foreign_key {
+            Some(ref foreign_key) => related_field_to_tokens(table, foreign_key, &self.field),
+            None if qualify => string_token(&format!("{}.{}", table, self.field)),
+            None => string_token(&self.field.to_string()),
+        }
+    }
+}
+
+/// Get the alias of the table joined via the `foreign_key` field.
+/// An alias is needed to join the same table more than once, via different foreign keys.
+fn join_alias(table: &str, foreign_key: &str) -> String {
+    format!("{}_{}", table, foreign_key)
+}
+
+/// Convert the `field` of the table joined via the `foreign_key` field to SQL.
+fn related_field_to_tokens(table: &str, foreign_key: &Ident, field: &Ident) -> TokenStream {
+    let field = format!("{}.{}", join_alias(table, &foreign_key.to_string()), field);
+    quote! {
+        #field
+    }
+}
+
 impl Join {
+    /// Get the alias of the joined table, like `Book_author_company` for `join(author.company)`.
+    fn alias(&self) -> String {
+        path_alias(&self.base_table, &self.path())
+    }
+
+    /// Get the alias of the table containing the `base_field`.
+    fn parent_alias(&self) -> String {
+        if self.parent_fields.is_empty() {
+            self.base_table.clone()
+        }
+        else {
+            path_alias(&self.base_table, &self.parent_fields.iter().collect::<Vec<_>>())
+        }
+    }
+
+    /// Get the path like `author.company` to give to the macros of `base_table`.
+    fn path_tokens(&self) -> TokenStream {
+        let path = self.path();
+        quote! {
+            #(#path).*
+        }
+    }
+
     fn to_check(&self) -> TokenStream {
         let related_table_macro_name =
             Ident::new(&format!("tql_{}_check_related_tables", self.base_table), self.base_field.span());
         let related_pk_macro_name = Ident::new(&format!("tql_{}_check_related_pks", self.base_table),
             self.base_field.span());
-        let base_field_ident = &self.base_field;
+        let path = self.path_tokens();
         quote_spanned! { self.base_field.span() =>
-            #related_table_macro_name!(#base_field_ident);
-            #related_pk_macro_name!(#base_field_ident);
+            #related_table_macro_name!(#path);
+            #related_pk_macro_name!(#path);
         }
     }
 
     fn to_tokens(&self) -> TokenStream {
-        let related_table_macro_name =
-            Ident::new(&format!("tql_{}_related_tables", self.base_table), Span::call_site());
         let related_pks_macro_name = Ident::new(&format!("tql_{}_related_pks", self.base_table), self.base_field.span());
-        let base_table = &self.base_table;
+        let related_table_macro_name = Ident::new(&format!("tql_{}_related_tables", self.base_table), Span::call_site());
         let base_field = self.base_field.to_sql(&mut 1);
-        let base_field_ident = &self.base_field;
-        let related_table_name = quote_spanned! { Span::call_site() =>
-            #related_table_macro_name!(#base_field_ident)
-        };
+        let path = self.path_tokens();
+        let join_type =
+            match self.join_type {
+                JoinType::Inner => " INNER JOIN ",
+                JoinType::Left => " LEFT OUTER JOIN ",
+            };
+        let alias = self.alias();
+        let parent_alias = self.parent_alias();
         quote! {
-            " INNER JOIN ", #related_table_name, " ON ", #base_table, ".", #base_field, " = ",
-                    #related_table_name, ".", #related_pks_macro_name!(#base_field_ident)
+            #join_type, #related_table_macro_name!(#path), " AS ", #alias, " ON ", #parent_alias, ".", #base_field, " = ",
+                    #alias, ".", #related_pks_macro_name!(#path)
         }
     }
 }
 
+/// Get the alias of the table joined by following the `ForeignKey` fields in `path`.
+fn path_alias(table: &str, path: &[&Ident]) -> String {
+    let path = path.iter()
+        .map(|field| field.to_string())
+        .collect::<Vec<_>>()
+        .join("_");
+    join_alias(table, &path)
+}
+
 fn sep_by<I: Iterator<Item=TokenStream>>(elements: I, sep: &str) -> TokenStream {
     let mut elements: Vec<_> = elements.collect();
     if let Some(last_element) = elements.pop() {
@@ -261,17 +498,63 @@ fn joins_to_check(joins: &[Join]) -> TokenStream {
 }
 
 fn joins_to_tokens(joins: &[Join]) -> TokenStream {
-    sep_by(joins.iter().map(|join| join.to_tokens()), " ")
+    // Every join starts with a space, so no separator is needed.
+    sep_by(joins.iter().map(|join| join.to_tokens()), "")
 }
 
 fn joined_fields(joins: &[Join], table: &str) -> TokenStream {
     let macro_name = Ident::new(&format!("tql_{}_related_field_list", table), Span::call_site());
-    let fields = joins.iter()
-        .map(|join| join.base_field.clone());
+    let aliases = joins.iter()
+        .map(|join| join.alias());
+    let paths = joins.iter()
+        .map(|join| join.path_tokens());
     let macro_name = iter::repeat(macro_name)
         .take(joins.len());
     quote! {
-        #(, ", ", #macro_name!(#fields))*
+        #(, ", ", #macro_name!(#aliases; #paths))*
+    }
+}
+
+/// Get the DISTINCT clause which goes before the fields to select.
+fn distinct_clause(distinct: &Distinct, table: &str) -> String {
+    match *distinct {
+        Distinct::All => "DISTINCT ".to_string(),
+        Distinct::Duplicates => String::new(),
+        Distinct::On(ref fields) => {
+            let fields = fields.iter()
+                .map(|field| format!("{}.{}", table, field))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("DISTINCT ON ({}) ", fields)
+        },
+    }
+}
+
+/// Get the fields to select.
+fn projection_fields(projection: &Projection, joins: &[Join], table: &str) -> TokenStream {
+    match *projection {
+        Projection::All => {
+            let macro_name = Ident::new(format!("tql_{}_field_list", table).as_str(), Span::call_site());
+            let joined_fields = joined_fields(joins, table);
+            quote! {
+                #macro_name!() #joined_fields
+            }
+        },
+        Projection::Fields(ref fields) => {
+            let fields = fields.iter()
+                .map(|field| format!("{}.{}", table, field))
+                .collect::<Vec<_>>()
+                .join(", ");
+            quote! {
+                #fields
+            }
+        },
+        Projection::Struct(ref struct_ident) => {
+            let macro_name = Ident::new(&format!("tql_{}_column_list", struct_ident), Span::call_site());
+            quote! {
+                #macro_name!(#table)
+            }
+        },
     }
 }
 
@@ -285,7 +568,9 @@ impl ToSql for String {
 impl Query {
     pub fn to_tokens(&self) -> TokenStream {
         match *self {
-            Query::Aggregate { ref aggregates, ref aggregate_filter, ref filter, ref groups, ref joins, ref table } => {
+            Query::Aggregate { ref aggregates, ref aggregate_filter, ref annotations, ref filter, ref groups, ref joins,
+                ref table } =>
+            {
                 let where_clause = filter_to_where_clause(filter);
                 let group_clause =
                     if !groups.is_empty() {
@@ -301,16 +586,20 @@ impl Query {
                     else {
                         " HAVING "
                     };
-                let aggregates = aggregates.to_sql(&mut 1);
+                let qualify = !joins.is_empty();
+                let annotations = annotations_to_tokens(annotations, table, qualify, aggregates);
+                let aggregates = sep_by(aggregates.iter().map(|aggregate| aggregate.to_tokens(table)), ", ");
                 let check_joins = joins_to_check(&joins);
+                let check_subqueries = filter.to_check();
                 let joins = joins_to_tokens(&joins);
                 let index = &mut 1;
                 let filter = filter.to_tokens(index);
-                let groups = groups.to_sql(&mut 1);
-                let aggregate_filter = aggregate_filter.to_sql(index);
+                let groups = sep_by(groups.iter().map(|group| group.to_tokens(table, qualify)), ", ");
+                let aggregate_filter = aggregate_filter.to_tokens(table, index);
                 quote! {{
                     #check_joins
-                    concat!("SELECT ", #aggregates, " FROM ", #table, #joins, #where_clause, #filter, #group_clause,
+                    #check_subqueries
+                    concat!("SELECT ", #aggregates, #annotations " FROM ", #table, #joins, #where_clause, #filter, #group_clause,
                             #groups, #having_clause, #aggregate_filter)
                 }}
             },
@@ -322,10 +611,12 @@ impl Query {
             },
             Query::Delete { ref filter, ref table, use_pk: _use_pk } => {
                 let where_clause = filter_to_where_clause(filter);
+                let check_subqueries = filter.to_check();
                 let filter = filter.to_tokens(&mut 1);
-                quote! {
+                quote! {{
+                    #check_subqueries
                     concat!("DELETE FROM ", #table, #where_clause, #filter)
-                }
+                }}
             },
             Query::Drop { ref table } => {
                 string_token(format!("DROP TABLE {table}", table = table).as_str())
@@ -342,49 +633,299 @@ impl Query {
                 let backend = create_sql_backend();
                 backend.insert_query(table, &fields, &values)
             },
-            Query::Select { ref filter, get: _get, ref joins, ref limit, ref order, ref table, use_pk: _use_pk } => {
-                let where_clause = filter_to_where_clause(filter);
-                let order_clause =
-                    if has_order_clauses(order) {
-                        " ORDER BY "
-                    }
-                    else {
-                        ""
-                    };
-                let macro_name = Ident::new(format!("tql_{}_field_list", table).as_str(), Span::call_site());
-                let joined_fields = joined_fields(&joins, table);
+            Query::Select { ref combinations, ref distinct, ref filter, ref joins, ref limit, result, ref table, .. } => {
                 let check_joins = joins_to_check(&joins);
-                let joins = joins_to_tokens(&joins);
-                let index = &mut 1;
-                let filter = filter.to_tokens(index);
-                let order = order.to_sql(&mut 1);
-                let limit = limit.to_sql(&mut 1);
+                let check_subqueries = filter.to_check();
+                let check_combinations = combinations.iter().map(|combination| combination.query.to_check());
+                let select = self.select_to_tokens(&mut 1);
+                let select =
+                    match result {
+                        SelectResult::Count if count_in_subquery(combinations, distinct, limit) => {
+                            let alias = format!("{}_rows", table);
+                            quote! {
+                                "SELECT COUNT(*) FROM (", #select, ") AS ", #alias
+                            }
+                        },
+                        SelectResult::Exists => quote! {
+                            "SELECT EXISTS(", #select, ")"
+                        },
+                        SelectResult::Count | SelectResult::Rows => select,
+                    };
                 quote_spanned! { Span::call_site() => {
                     #check_joins
-                    concat!("SELECT ", #macro_name!() #joined_fields, " FROM ", #table, #joins, #where_clause, #filter,
-                        #order_clause, #order, #limit)
+                    #check_subqueries
+                    #(#check_combinations)*
+                    concat!(#select)
                 }}
             },
             Query::Update { ref assignments, ref filter, ref table, use_pk: _use_pk } => {
                 let where_clause = filter_to_where_clause(filter);
+                let check_subqueries = filter.to_check();
                 let index = &mut 1;
                 let assignments = assignments.to_sql(index);
                 let filter = filter.to_tokens(index);
-                quote! {
+                quote! {{
+                    #check_subqueries
                     concat!("UPDATE ", #table, " SET ", #assignments, #where_clause, #filter)
+                }}
+            },
+        }
+    }
+
+    /// Convert a select `Query` to the arguments of `concat!()`, numbering the parameters from
+    /// `index`, so that it can be nested in another query.
+    fn select_to_tokens(&self, index: &mut usize) -> TokenStream {
+        if let Query::Select { ref annotations, ref combinations, ref distinct, ref filter, get: _get, ref joins, ref limit,
+            ref order, ref pagination, ref projection, related: _, result, ref table, use_pk: _use_pk } = *self
+        {
+            let mut where_clause = filter_to_where_clause(filter);
+            let order_clause =
+                if has_order_clauses(order) {
+                    " ORDER BY "
+                }
+                else {
+                    ""
+                };
+            let fields =
+                if result == SelectResult::Count && !count_in_subquery(combinations, distinct, limit) {
+                    quote! { "COUNT(*)" }
+                }
+                else {
+                    projection_fields(projection, joins, table)
+                };
+            let distinct = distinct_clause(distinct, table);
+            // NOTE: the ORDER BY clause of combined queries can only refer to the selected columns.
+            let qualify = !joins.is_empty() && combinations.is_empty();
+            let annotations = annotations_to_tokens(annotations, table, qualify, &[]);
+            let joins = joins_to_tokens(joins);
+            let mut filter = filter.to_tokens(index);
+            if let Some(keyset) = keyset_to_tokens(pagination, order, table, qualify, index) {
+                // NOTE: the filter is put in parentheses since it can contain OR.
+                filter =
+                    if where_clause.is_empty() {
+                        keyset
+                    }
+                    else {
+                        quote! {
+                            "(", #filter, ") AND ", #keyset
+                        }
+                    };
+                where_clause = " WHERE ";
+            }
+            let count = combinations.len();
+            let (open_parentheses, close_parenthesis) = combination_parentheses(count);
+            let combinations = combinations.iter().enumerate()
+                .map(|(position, combination)| {
+                    let combination = combination.to_tokens(index);
+                    let close_parenthesis =
+                        if position + 1 < count {
+                            close_parenthesis
+                        }
+                        else {
+                            ""
+                        };
+                    quote! {
+                        #combination, #close_parenthesis,
+                    }
+                })
+                .collect::<Vec<_>>();
+            let order =
+                if let Pagination::Before(_) = *pagination {
+                    // NOTE: the rows before the cursor are fetched in reverse order so that the
+                    // limit keeps the closest ones.
+                    let order: Vec<_> = order.iter().map(Order::reverse).collect();
+                    orders_to_tokens(&order, table, qualify)
+                }
+                else {
+                    orders_to_tokens(order, table, qualify)
+                };
+            let limit = limit.to_sql(index);
+            quote! {
+                #open_parentheses, "SELECT ", #distinct, #fields, #annotations " FROM ", #table, #joins, #where_clause, #filter,
+                    #(#combinations)* #order_clause, #order, #limit
+            }
+        }
+        else {
+            unreachable!("select_to_tokens() called on a non-select query")
+        }
+    }
+}
+
+/// Convert the comparison of the sorted fields with the cursor of the keyset `pagination` to SQL,
+/// numbering the parameters from `index`.
+/// The rows after the cursor have greater values in ascending order and lower values in descending
+/// order.
+fn keyset_to_tokens(pagination: &Pagination, order: &[Order], table: &str, qualify: bool, index: &mut usize)
+    -> Option<TokenStream>
+{
+    let after =
+        match *pagination {
+            Pagination::After(_) => true,
+            Pagination::Before(_) => false,
+            Pagination::First | Pagination::Unpaginated => return None,
+        };
+    let mut keys = vec![];
+    for order in order {
+        let descending =
+            match *order {
+                Order::Ascending(..) => false,
+                Order::Descending(..) => true,
+                Order::NoOrder => continue,
+            };
+        if let Some(field) = order.field() {
+            let operator =
+                if after != descending {
+                    ">"
+                }
+                else {
+                    "<"
+                };
+            let param = format!("${}", index);
+            *index += 1;
+            keys.push((field.to_tokens(table, qualify), operator, param));
+        }
+    }
+    let same_direction = keys.windows(2).all(|keys| keys[0].1 == keys[1].1);
+    if keys.len() > 1 && same_direction {
+        // Use a row value comparison, like (field1, field2) > ($1, $2).
+        let operator = format!(") {} (", keys[0].1);
+        let fields = sep_by(keys.iter().map(|(field, _, _)| field.clone()), ", ");
+        let params = keys.iter().map(|(_, _, param)| param.clone()).collect::<Vec<_>>().join(", ");
+        return Some(quote! {
+            "(", #fields, #operator, #params, ")"
+        });
+    }
+    // With different directions, the comparison is expanded to
+    // (field1 > $1 OR field1 = $1 AND (field2 < $2 OR ...)).
+    let mut comparison = None;
+    for (field, operator, param) in keys.into_iter().rev() {
+        let operator = format!(" {} {}", operator, param);
+        comparison = Some(match comparison {
+            Some(comparison) => {
+                let equal = format!(" = {} AND ", param);
+                quote! {
+                    "(", #field, #operator, " OR ", #field, #equal, #comparison, ")"
+                }
+            },
+            None => quote! {
+                #field, #operator
+            },
+        });
+    }
+    comparison
+}
+
+/// Check whether the rows must be selected in a subquery to be counted, since the number of rows
+/// depends on the selected fields or on the limit.
+fn count_in_subquery(combinations: &[Combination], distinct: &Distinct, limit: &Limit) -> bool {
+    let distinct = matches!(*distinct, Distinct::All | Distinct::On(_));
+    let limit = !matches!(*limit, NoLimit);
+    !combinations.is_empty() || distinct || limit
+}
+
+impl Combination {
+    fn to_tokens(&self, index: &mut usize) -> TokenStream {
+        let operator =
+            match self.operator {
+                SetOperator::Except => " EXCEPT ",
+                SetOperator::Intersect => " INTERSECT ",
+                SetOperator::Union => " UNION ",
+            };
+        let query = self.query.to_tokens(index);
+        quote! {
+            #operator, #query
+        }
+    }
+}
+
+/// Get the parentheses to open before the first query and the one to close after each combination
+/// but the last, so that the queries are combined from left to right.
+/// This is only needed with PostgreSQL, where INTERSECT has a higher precedence than UNION and
+/// EXCEPT, while SQLite does not support parentheses around the combined queries.
+fn combination_parentheses(count: usize) -> (String, &'static str) {
+    if cfg!(feature = "postgres") && count > 1 {
+        ("(".repeat(count - 1), ")")
+    }
+    else {
+        (String::new(), "")
+    }
+}
+
+impl Subquery {
+    fn to_check(&self) -> TokenStream {
+        match *self.query {
+            Query::Select { ref filter, ref joins, .. } => {
+                let check_joins = joins_to_check(joins);
+                let check_subqueries = filter.to_check();
+                quote! {
+                    #check_joins
+                    #check_subqueries
                 }
             },
+            _ => quote! {},
+        }
+    }
+
+    fn to_tokens(&self, index: &mut usize) -> TokenStream {
+        self.query.select_to_tokens(index)
+    }
+}
+
+impl SubqueryFilter {
+    fn to_tokens(&self, index: &mut usize) -> TokenStream {
+        let operand1 = self.operand1.to_tokens(index);
+        // NOTE: the operators of the lists cannot be used since their parameter is an array with
+        // PostgreSQL.
+        let operator =
+            match self.operator {
+                RelationalOperator::NotIn => " NOT IN (",
+                _ => " IN (",
+            };
+        let subquery = self.subquery.to_tokens(index);
+        quote! {
+            #operand1, #operator, #subquery, ")"
+        }
+    }
+}
+
+impl ManyToManyFilter {
+    fn to_check(&self) -> TokenStream {
+        let macro_name = Ident::new(&format!("tql_{}_check_many_to_many", self.table), self.field.span());
+        let field = &self.field;
+        let check_subqueries = self.filter.to_check();
+        quote_spanned! { self.field.span() =>
+            #macro_name!(#field);
+            #check_subqueries
+        }
+    }
+
+    /// Convert to a filter checking whether the primary key is in the subquery selecting the rows
+    /// related to a row matching the filter.
+    fn to_tokens(&self, index: &mut usize) -> TokenStream {
+        let pk_macro_name = Ident::new(&format!("tql_{}_primary_key_field", self.table), Span::call_site());
+        let subquery_macro_name = Ident::new(&format!("tql_{}_many_to_many_subquery", self.table), Span::call_site());
+        let table = &self.table;
+        let field = &self.field;
+        let filter = self.filter.to_tokens(index);
+        quote! {
+            #table, ".", #pk_macro_name!(), " IN (", #subquery_macro_name!(#field), " WHERE ", #filter, ")"
         }
     }
 }
 
 impl Filter {
     fn to_tokens(&self, index: &mut usize) -> TokenStream {
-        let operand1 = self.operand1.to_tokens();
+        let operand1 = self.operand1.to_tokens(index);
         let operator = self.operator.to_sql(index);
         let operand2 = self.operand2.to_sql(index);
-        quote! {
-            #operand1, " ", #operator, " ", #operand2
+        match self.operator {
+            // The operator contains the opening parenthesis of the list.
+            RelationalOperator::In | RelationalOperator::NotIn => quote! {
+                #operand1, " ", #operator, #operand2, ")"
+            },
+            _ => quote! {
+                #operand1, " ", #operator, " ", #operand2
+            },
         }
     }
 }
@@ -400,32 +941,52 @@ impl Filters {
     }
 }
 
+impl MethodCall {
+    /// Convert the method call to SQL, `object` being the SQL of the object the method is called on
+    /// and `table` the name of its table.
+    fn to_tokens(&self, table: &str, object: &str, index: &mut usize) -> TokenStream {
+        let methods = methods_singleton();
+        if let Some(method) = methods.get(&self.method_name.to_string()) {
+            // In the template, $0 represents the object identifier, $table and $field the names of
+            // its table and field and $1, $2, ... the arguments.
+            // NOTE: it's safe to use unwrap_or_default() because we check if the method exists for the
+            // backend in the method analyzer.
+            let template = method.template.as_ref().map(|string| string.as_str()).unwrap_or_default();
+            let arguments: Vec<_> = self.arguments.iter().map(|argument| argument.to_sql(index)).collect();
+            let field = self.object_name.to_string();
+            let placeholders = [("0", object), ("table", table), ("field", &field)];
+            let sql = string_literal(&fill_template(template, &placeholders, &arguments));
+            if let Some(ref array_template) = method.array_template {
+                // NOTE: only the table struct knows whether the field is an array, so its macro
+                // chooses the SQL.
+                let array_sql = string_literal(&fill_template(array_template, &placeholders, &arguments));
+                let macro_name = Ident::new(&format!("tql_{}_if_array", table), Span::call_site());
+                let field = &self.object_name;
+                return quote! {
+                    #macro_name!(#field, #array_sql, #sql)
+                };
+            }
+            quote! {
+                #sql
+            }
+        }
+        else {
+            // NOTE: type checking will disallow this code to be executed.
+            quote! {
+                ""
+            }
+        }
+    }
+}
+
 impl FilterValue {
-    fn to_tokens(&self) -> TokenStream {
+    fn to_tokens(&self, index: &mut usize) -> TokenStream {
         let sql =
             match *self {
+                FilterValue::Arithmetic(ref operand) => operand.to_sql(index),
                 FilterValue::Identifier(ref table, ref identifier) => format!("{}.{}", table, identifier.to_sql(&mut 1)),
-                FilterValue::MethodCall(MethodCall { ref arguments, ref object_name, ref method_name, ..  }) => {
-                    let methods = methods_singleton();
-                    if let Some(method) = methods.get(&method_name.to_string()) {
-                        // In the template, $0 represents the object identifier and $1, $2, ... the
-                        // arguments.
-                        let mut sql = method.template.as_ref().map(|string| string.as_str()).unwrap_or_default()
-                            // NOTE: it's safe to use unwrap_or_default() because we check if the method exists for the
-                            // backend in the method analyzer.
-                            .replace("$0", &object_name.to_string());
-                        let mut index = 1;
-                        for argument in arguments {
-                            sql = sql.replace(&format!("${}", index), &argument.to_sql(&mut 1));
-                            index += 1;
-                        }
-                        sql
-                    }
-                    else {
-                        // NOTE: type checking will disallow this code to be executed.
-                        String::new()
-                    }
-                },
+                FilterValue::MethodCall(ref table, ref call) =>
+                    return call.to_tokens(table, &call.object_name.to_string(), index),
                 FilterValue::None => unreachable!("FilterValue::None in FilterValue::to_sql()"),
                 FilterValue::PrimaryKey(ref table) => {
                     let macro_name = Ident::new(&format!("tql_{}_primary_key_field", table), Span::call_site());
@@ -433,6 +994,8 @@ impl FilterValue {
                         #table, ".", #macro_name!()
                     };
                 },
+                FilterValue::RelatedIdentifier(ref table, ref foreign_key, ref field) =>
+                    return related_field_to_tokens(table, foreign_key, field),
             };
         let expr = string_literal(&sql);
         quote! {
@@ -490,14 +1053,35 @@ fn escape(string: String) -> String {
 pub fn type_to_sql(typ: &Type, nullable: bool) -> TokenStream {
     let sql_type =
         match *typ {
+            #[cfg(feature = "postgres")]
+            Type::Array(ref typ) => {
+                let sql = type_to_sql(typ, true);
+                let not_null =
+                    if nullable {
+                        ""
+                    }
+                    else {
+                        " NOT NULL"
+                    };
+                return quote! {
+                    #sql, "[]", #not_null
+                };
+            },
             Type::Bool => "BOOLEAN",
             Type::ByteString => "BYTEA",
             Type::I8 | Type::Char => "CHARACTER(1)",
             Type::Custom(ref related_table_name) => {
                 let pk_macro_name = Ident::new(&format!("tql_{}_primary_key_field", related_table_name),
                     Span::call_site());
+                let not_null =
+                    if nullable {
+                        ""
+                    }
+                    else {
+                        " NOT NULL"
+                    };
                 return quote! {
-                    "INTEGER REFERENCES ", #related_table_name, "(", #pk_macro_name!(), ") NOT NULL"
+                    "INTEGER REFERENCES ", #related_table_name, "(", #pk_macro_name!(), ")", #not_null
                 };
                 // NOTE: if the field type is not an SQL table, an error is thrown.
             },
@@ -507,6 +1091,10 @@ pub fn type_to_sql(typ: &Type, nullable: bool) -> TokenStream {
             Type::I16 => "SMALLINT",
             Type::I32 => "INTEGER",
             Type::I64 => "BIGINT",
+            #[cfg(feature = "rusqlite")]
+            Type::Json => "TEXT",
+            #[cfg(not(feature = "rusqlite"))]
+            Type::Json => "JSONB",
             Type::LocalDateTime => "TIMESTAMP WITH TIME ZONE",
             Type::NaiveDate => "DATE",
             Type::NaiveDateTime => "TIMESTAMP",
@@ -541,12 +1129,6 @@ pub fn type_to_sql(typ: &Type, nullable: bool) -> TokenStream {
     }
 }
 
-impl ToSql for [Order] {
-    fn to_sql(&self, index: &mut usize) -> String {
-        self.iter().map(|order| order.to_sql(index)).collect::<Vec<_>>().join(", ")
-    }
-}
-
 impl ToSql for LogicalOperator {
     fn to_sql(&self, _index: &mut usize) -> String {
         match *self {
@@ -557,16 +1139,110 @@ impl ToSql for LogicalOperator {
     }
 }
 
-impl ToSql for Order {
+impl ToSql for Operand {
     fn to_sql(&self, index: &mut usize) -> String {
         match *self {
-            Order::Ascending(ref field) => field.to_sql(index),
-            Order::Descending(ref field) => field.to_sql(index) + " DESC",
-            Order::NoOrder => String::new(),
+            Operand::Arithmetic(ref operand1, ref operator, ref operand2) => {
+                if let Operand::Function(ref function) = **operand2 {
+                    if operand2.is_interval() {
+                        // NOTE: the date is converted first since it comes first in the parameters.
+                        let date = operand1.to_sql(index);
+                        let operator = operator.to_sql(index);
+                        return function.to_sql(&date, &operator, |argument| argument.to_sql(index));
+                    }
+                }
+                operand1.to_sql(index) + " " +
+                    &operator.to_sql(index) + " " +
+                    &operand2.to_sql(index)
+            },
+            Operand::Expression(ref expression) => expression.to_sql(index),
+            Operand::Field(ref table, ref field) => format!("{}.{}", table, field.to_sql(index)),
+            Operand::Function(ref function) => function.to_sql("", "", |argument| argument.to_sql(index)),
+            Operand::Paren(ref operand) =>
+                "(".to_string() +
+                &operand.to_sql(index) +
+                ")",
+        }
+    }
+}
+
+impl SqlFunction {
+    /// Convert the function call to SQL, `convert` being used to convert the arguments.
+    /// For an interval, `date` is the SQL of the date the interval is added to or subtracted from
+    /// with the `operator`.
+    fn to_sql<F: FnMut(&Operand) -> String>(&self, date: &str, operator: &str, mut convert: F) -> String {
+        let functions = functions_singleton();
+        let template = &functions[&self.name.to_string()].template;
+        let arguments: Vec<_> = self.arguments.iter().map(&mut convert).collect();
+        // In the template, $0 represents the date, $op the operator and $1, $2, ... the arguments.
+        fill_template(template, &[("0", date), ("op", operator)], &arguments)
+    }
+}
+
+/// Fill the `template` of a method or a function, replacing the named `placeholders` (like $0) by
+/// their value and $1, $2, ... by the `arguments`.
+/// The template is filled in a single pass since the arguments can contain parameters like $1.
+fn fill_template(template: &str, placeholders: &[(&str, &str)], arguments: &[String]) -> String {
+    let mut parts = template.split('$');
+    let mut sql = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let digit_count = part.chars().take_while(|character| character.is_ascii_digit()).count();
+        let placeholder = placeholders.iter()
+            .find_map(|&(name, value)| part.strip_prefix(name).map(|rest| (value, rest)));
+        if let Some((value, rest)) = placeholder {
+            sql.push_str(value);
+            sql.push_str(rest);
+        }
+        else if digit_count > 0 {
+            let index: usize = part[..digit_count].parse().expect("template placeholder index");
+            let argument = index.checked_sub(1)
+                .and_then(|index| arguments.get(index))
+                .expect("template placeholder should refer to an argument");
+            sql.push_str(argument);
+            sql.push_str(&part[digit_count..]);
+        }
+        else {
+            sql.push('$');
+            sql.push_str(part);
+        }
+    }
+    sql
+}
+
+impl Order {
+    fn to_tokens(&self, table: &str, qualify: bool) -> TokenStream {
+        match *self {
+            Order::Ascending(ref value, nulls) => nulls.order_to_tokens(value.to_tokens(table, qualify), ""),
+            Order::Descending(ref value, nulls) => nulls.order_to_tokens(value.to_tokens(table, qualify), " DESC"),
+            Order::NoOrder => quote! { "" },
         }
     }
 }
 
+impl OrderValue {
+    fn to_tokens(&self, table: &str, qualify: bool) -> TokenStream {
+        match *self {
+            OrderValue::Field(ref field) => field.to_tokens(table, qualify),
+            OrderValue::MethodCall(ref call) => {
+                let object =
+                    if qualify {
+                        format!("{}.{}", table, call.object_name)
+                    }
+                    else {
+                        call.object_name.to_string()
+                    };
+                call.to_tokens(table, &object, &mut 1)
+            },
+        }
+    }
+}
+
+fn orders_to_tokens(orders: &[Order], table: &str, qualify: bool) -> TokenStream {
+    let orders = orders.iter()
+        .map(|order| order.to_tokens(table, qualify));
+    sep_by(orders, ", ")
+}
+
 impl ToSql for Limit {
     fn to_sql(&self, index: &mut usize) -> String {
         match *self {
@@ -595,9 +1271,23 @@ impl ToSql for RelationalOperator {
     fn to_sql(&self, _index: &mut usize) -> String {
         match *self {
             RelationalOperator::Equal => "=",
+            // NOTE: the list is sent as an array parameter with PostgreSQL while it is expanded in
+            // as many parameters as there are values in the list with SQLite.
+            #[cfg(feature = "rusqlite")]
+            RelationalOperator::In => "IN (",
+            #[cfg(feature = "postgres")]
+            RelationalOperator::In => "= ANY(",
+            #[cfg(not(any(feature = "rusqlite", feature = "postgres")))]
+            RelationalOperator::In => unreachable!("Enable one of the following features: sqlite, pg"),
             RelationalOperator::LesserThan => "<",
             RelationalOperator::LesserThanEqual => "<=",
             RelationalOperator::NotEqual => "<>",
+            #[cfg(feature = "rusqlite")]
+            RelationalOperator::NotIn => "NOT IN (",
+            #[cfg(feature = "postgres")]
+            RelationalOperator::NotIn => "<> ALL(",
+            #[cfg(not(any(feature = "rusqlite", feature = "postgres")))]
+            RelationalOperator::NotIn => unreachable!("Enable one of the following features: sqlite, pg"),
             RelationalOperator::GreaterThan => ">=",
             RelationalOperator::GreaterThanEqual => ">",
         }.to_string()
@@ -619,7 +1309,7 @@ pub fn fields_to_sql(fields: &[TypedField]) -> TokenStream {
 /// Convert a `FilterExpression` to either " WHERE " or the empty string if there are no filters.
 fn filter_to_where_clause(filter: &FilterExpression) -> &str {
     match *filter {
-        FilterExpression::Filter(_) | FilterExpression::Filters(_) | FilterExpression::NegFilter(_) | FilterExpression::ParenFilter(_) | FilterExpression::FilterValue(_) => " WHERE ",
+        FilterExpression::Exists(_) | FilterExpression::Filter(_) | FilterExpression::Filters(_) | FilterExpression::InSubquery(_) | FilterExpression::ManyToMany(_) | FilterExpression::NegFilter(_) | FilterExpression::ParenFilter(_) | FilterExpression::FilterValue(_) => " WHERE ",
         FilterExpression::NoFilters => "",
     }
 }
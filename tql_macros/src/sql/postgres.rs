@@ -24,7 +24,7 @@
 use proc_macro2::{Span,TokenStream};
 use syn::Ident;
 
-use ast::Aggregate;
+use ast::{Aggregate, Nulls, Operand};
 use sql::{SqlBackend, ToSql, string_token};
 
 pub struct PostgresSqlBackend {}
@@ -33,15 +33,74 @@ pub fn create_sql_backend() -> PostgresSqlBackend {
     PostgresSqlBackend { }
 }
 
-impl ToSql for Aggregate {
-    fn to_sql(&self, index: &mut usize) -> String {
-        // TODO: do not hard-code the type.
-        "CAST(".to_string() + &self.sql_function.to_sql(index) + "(" +
-            &self.field.clone().expect("Aggregate field").to_sql(index) + ") AS DOUBLE PRECISION)"
+impl Aggregate {
+    /// Convert the aggregate function `call` to SQL, casting its result to the type of the aggregate
+    /// struct field.
+    pub fn call_to_tokens(&self, call: TokenStream, table: &str) -> TokenStream {
+        match self.sql_function.as_str() {
+            "AVG" => quote! {
+                "CAST(", #call, " AS DOUBLE PRECISION)"
+            },
+            // The sum of a BIGINT is a NUMERIC in PostgreSQL, so it is casted to the type of the sum.
+            // The fields of an expression all have the same type, so the type of the first one is used.
+            "SUM" => {
+                let field = self.argument.as_ref().and_then(|argument| argument.leaves().into_iter()
+                    .filter_map(|leaf|
+                        if let Operand::Field(_, ref field) = *leaf {
+                            Some(field)
+                        }
+                        else {
+                            None
+                        }
+                    )
+                    .next());
+                if let Some(field) = field {
+                    let macro_name = Ident::new(&format!("tql_{}_sum_type", table), Span::call_site());
+                    quote! {
+                        "CAST(", #call, " AS ", #macro_name!(#field), ")"
+                    }
+                }
+                else {
+                    quote! {
+                        #call
+                    }
+                }
+            },
+            _ => quote! {
+                #call
+            },
+        }
+    }
+}
+
+impl Nulls {
+    /// Convert the ORDER BY clause of `value` in the `direction` to SQL.
+    pub fn order_to_tokens(self, value: TokenStream, direction: &str) -> TokenStream {
+        let nulls =
+            match self {
+                Nulls::First => " NULLS FIRST",
+                Nulls::Last => " NULLS LAST",
+                Nulls::Unspecified => "",
+            };
+        quote! {
+            #value, #direction, #nulls
+        }
     }
 }
 
 impl SqlBackend for PostgresSqlBackend {
+    /// The field is searched in a generated tsvector column with a GIN index, which are dropped with
+    /// the table.
+    fn fulltext_queries(&self, table: &str, field: &str) -> (Vec<String>, Vec<String>) {
+        let create_queries = vec![
+            format!("ALTER TABLE {table} ADD COLUMN {field}_tsv TSVECTOR \
+                     GENERATED ALWAYS AS (TO_TSVECTOR('english', COALESCE({field}, ''))) STORED",
+                    table = table, field = field),
+            format!("CREATE INDEX {table}_{field}_tsv ON {table} USING GIN ({field}_tsv)", table = table, field = field),
+        ];
+        (create_queries, vec![])
+    }
+
     fn insert_query(&self, table: &str, fields: &[String], values: &[String]) -> TokenStream {
         let query_start =
             format!("INSERT INTO {table}({fields}) VALUES({values}) RETURNING ",
@@ -23,7 +23,7 @@
 
 use proc_macro2::TokenStream;
 
-use ast::Aggregate;
+use ast::{Aggregate, Nulls};
 use sql::{SqlBackend, ToSql};
 
 pub struct SqliteSqlBackend {}
@@ -32,13 +32,55 @@ pub fn create_sql_backend() -> SqliteSqlBackend {
     SqliteSqlBackend { }
 }
 
-impl ToSql for Aggregate {
-    fn to_sql(&self, index: &mut usize) -> String {
-        self.sql_function.to_sql(index) + "(" + &self.field.clone().expect("Aggregate field").to_sql(index) + ")"
+impl Aggregate {
+    pub fn call_to_tokens(&self, call: TokenStream, _table: &str) -> TokenStream {
+        call
+    }
+}
+
+impl Nulls {
+    /// Convert the ORDER BY clause of `value` in the `direction` to SQL.
+    /// Older versions of SQLite do not support NULLS FIRST/LAST, so the rows are first sorted by
+    /// whether the value is NULL.
+    pub fn order_to_tokens(self, value: TokenStream, direction: &str) -> TokenStream {
+        match self {
+            Nulls::First => quote! {
+                #value, " IS NULL DESC, ", #value, #direction
+            },
+            Nulls::Last => quote! {
+                #value, " IS NULL, ", #value, #direction
+            },
+            Nulls::Unspecified => quote! {
+                #value, #direction
+            },
+        }
     }
 }
 
 impl SqlBackend for SqliteSqlBackend {
+    /// The field is searched in an FTS5 table indexing the content of the table, which is kept up to
+    /// date by triggers.
+    fn fulltext_queries(&self, table: &str, field: &str) -> (Vec<String>, Vec<String>) {
+        let fts_table = format!("{}_{}_fts", table, field);
+        let insert = format!("INSERT INTO {fts_table}(rowid, {field}) VALUES (new.rowid, new.{field});",
+                             fts_table = fts_table, field = field);
+        let delete = format!("INSERT INTO {fts_table}({fts_table}, rowid, {field}) VALUES ('delete', old.rowid, old.{field});",
+                             fts_table = fts_table, field = field);
+        let create_queries = vec![
+            format!("CREATE VIRTUAL TABLE {fts_table} USING fts5({field}, content='{table}')",
+                    fts_table = fts_table, field = field, table = table),
+            format!("CREATE TRIGGER {fts_table}_insert AFTER INSERT ON {table} BEGIN {insert} END",
+                    fts_table = fts_table, table = table, insert = insert),
+            format!("CREATE TRIGGER {fts_table}_delete AFTER DELETE ON {table} BEGIN {delete} END",
+                    fts_table = fts_table, table = table, delete = delete),
+            format!("CREATE TRIGGER {fts_table}_update AFTER UPDATE ON {table} BEGIN {delete} {insert} END",
+                    fts_table = fts_table, table = table, delete = delete, insert = insert),
+        ];
+        // NOTE: the triggers are dropped with the table.
+        let drop_queries = vec![format!("DROP TABLE IF EXISTS {}", fts_table)];
+        (create_queries, drop_queries)
+    }
+
     fn insert_query(&self, table: &str, fields: &[String], values: &[String]) -> TokenStream {
         let query =
             format!("INSERT INTO {table}({fields}) VALUES({values})",
@@ -10,14 +10,22 @@ use ast::{
     Aggregate,
     Assignment,
     AssignmentOperator,
+    Distinct,
+    FieldPath,
     FilterExpression,
     FilterValue,
     Groups,
     Join,
+    JoinType,
     Limit,
     LogicalOperator,
     MethodCall,
+    Nulls,
+    Operand,
     Order,
+    OrderValue,
+    Pagination,
+    Projection,
     Query,
     RelationalOperator,
 };
@@ -27,42 +35,113 @@ pub fn generate_macro_patterns(query: &Query, calls: &MethodCalls) -> TokenStrea
     let mut count = 0;
     let mut dummy_count = 0;
     let mut args = vec![];
+    let query = query_to_args(query, calls, &mut dummy_count, &mut count, &mut args);
+    let args =
+        if args.len() == 1 {
+            quote! { #(&$#args)* }
+        }
+        else {
+            quote! { (#(&$#args),*) }
+        };
+    quote! {
+        #[allow(unused)]
+        macro_rules! __tql_extract_exprs {
+            (#query) => {
+                #args
+            };
+        }
+    }
+}
+
+/// Convert the `query` to a macro pattern matching the method `calls` it comes from.
+fn query_to_args(query: &Query, calls: &MethodCalls, dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>)
+    -> TokenStream
+{
     let table_name = calls.name.clone().expect("table name");
     let mut methods = quote! {};
+    let mut combination_index = 0;
     for call in &calls.calls {
         let name = &call.name;
         let args =
             match name.to_string().as_str() {
-                "all" | "create" | "delete" | "drop" => quote! {},
+                "all" | "count" | "create" | "delete" | "distinct" | "drop" | "exists" | "paginate" => quote! {},
+                "after" | "before" =>
+                    match *query {
+                        Query::Select { pagination: Pagination::After(ref cursor), .. } |
+                        Query::Select { pagination: Pagination::Before(ref cursor), .. } =>
+                            expr_to_args(cursor, dummy_count, count, args),
+                        _ => quote! {},
+                    },
                 "aggregate" =>
                     if let Query::Aggregate { ref aggregates, .. } = *query {
-                        aggregates_to_args(aggregates)
+                        aggregates_to_args(aggregates, dummy_count, count, args)
+                    }
+                    else {
+                        quote! {}
+                    },
+                "annotate" => {
+                    // NOTE: the arguments of the call are used since they do not contain
+                    // expressions sent as parameters.
+                    let annotations = &call.args;
+                    quote! {
+                        #(#annotations),*
+                    }
+                },
+                "distinct_on" =>
+                    if let Query::Select { distinct: Distinct::On(ref fields), .. } = *query {
+                        quote! {
+                            #(#fields),*
+                        }
                     }
                     else {
                         quote! {}
                     },
+                "except" | "intersect" | "union" =>
+                    match *query {
+                        Query::Select { ref combinations, .. } if combination_index < combinations.len() => {
+                            let subquery = &combinations[combination_index].query;
+                            combination_index += 1;
+                            query_to_args(&subquery.query, &subquery.calls, dummy_count, count, args)
+                        },
+                        _ => quote! {},
+                    },
                 "filter" | "get" =>
                     match *query {
                         Query::Aggregate { ref filter, .. } | Query::Delete { ref filter, .. } |
                             Query::Select { ref filter, .. } | Query::Update { ref filter, .. } =>
-                            filter_to_args(filter, &mut dummy_count, &mut count, &mut args),
+                            filter_to_args(filter, dummy_count, count, args),
                         _ => quote! {},
                     },
-                "join" =>
+                "join" | "left_join" =>
                     match *query {
-                        Query::Aggregate { ref joins, .. } | Query::Select { ref joins, .. } =>
-                            joins_to_args(joins),
+                        Query::Aggregate { ref joins, .. } | Query::Select { ref joins, .. } => {
+                            let join_type =
+                                if name == "join" {
+                                    JoinType::Inner
+                                }
+                                else {
+                                    JoinType::Left
+                                };
+                            joins_to_args(joins, join_type)
+                        },
                         _ => quote! {},
                     }
                 "insert" | "update" =>
                     match *query {
                         Query::Insert { ref assignments, .. } | Query::Update { ref assignments, .. } =>
-                            assignments_to_args(assignments, &mut dummy_count, &mut count, &mut args),
+                            assignments_to_args(assignments, dummy_count, count, args),
                         _ => quote! {},
                     },
                 "limit" =>
                     if let Query::Select { ref limit, .. } = *query {
-                        limit_to_args(limit, &mut dummy_count, &mut count, &mut args)
+                        limit_to_args(limit, dummy_count, count, args)
+                    }
+                    else {
+                        quote! {}
+                    },
+                "only" =>
+                    if let Query::Select { ref projection, .. } = *query {
+                        projection_to_args(projection)
                     }
                     else {
                         quote! {}
@@ -81,6 +160,14 @@ pub fn generate_macro_patterns(query: &Query, calls: &MethodCalls) -> TokenStrea
                     else {
                         quote! {}
                     },
+                "with" => {
+                    // NOTE: the arguments of the call are used since with() can be called more
+                    // than once.
+                    let fields = &call.args;
+                    quote! {
+                        #(#fields),*
+                    }
+                },
                 _ => unreachable!("No method named {}", name),
             };
         methods =
@@ -91,44 +178,42 @@ pub fn generate_macro_patterns(query: &Query, calls: &MethodCalls) -> TokenStrea
                 }
             }
             else {
+                let turbofish = &call.turbofish;
                 quote! {
                     #methods
-                    . #name (#args)
+                    . #name #turbofish (#args)
                 }
             };
     }
-    let args =
-        if args.len() == 1 {
-            quote! { #(&$#args)* }
-        }
-        else {
-            quote! { (#(&$#args),*) }
-        };
     quote! {
-        #[allow(unused)]
-        macro_rules! __tql_extract_exprs {
-            (#table_name #methods) => {
-                #args
-            };
-        }
+        #table_name #methods
     }
 }
 
 fn filter_to_args(filter: &FilterExpression, dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>) -> TokenStream {
     match *filter {
         FilterExpression::Filter(ref filter) => {
-            let left = filter_value_to_args(&filter.operand1);
-            let op =
-                if left.is_empty() {
-                    quote! {}
-                }
-                else {
-                    rel_op_to_args(filter.operator)
-                };
-            let right = &filter.operand2;
-            let right = expr_to_args(right, dummy_count, count, args);
-            quote! {
-                #left #op #right
+            let left = filter_value_to_args(&filter.operand1, dummy_count, count, args);
+            let right = operand_to_args(&filter.operand2, false, dummy_count, count, args);
+            match filter.operator {
+                RelationalOperator::In | RelationalOperator::NotIn => {
+                    let method = rel_op_to_args(filter.operator);
+                    quote! {
+                        #left . #method ( #right )
+                    }
+                },
+                _ => {
+                    let op =
+                        if left.is_empty() {
+                            quote! {}
+                        }
+                        else {
+                            rel_op_to_args(filter.operator)
+                        };
+                    quote! {
+                        #left #op #right
+                    }
+                },
             }
         },
         FilterExpression::Filters(ref filters) => {
@@ -139,7 +224,7 @@ fn filter_to_args(filter: &FilterExpression, dummy_count: &mut i32, count: &mut
                 #left #op #right
             }
         },
-        FilterExpression::FilterValue(ref value) => filter_value_to_args(&value.node),
+        FilterExpression::FilterValue(ref value) => filter_value_to_args(&value.node, dummy_count, count, args),
         FilterExpression::NegFilter(ref filter) => {
             let expr = filter_to_args(filter, dummy_count, count, args);
             quote! { - #expr }
@@ -149,19 +234,42 @@ fn filter_to_args(filter: &FilterExpression, dummy_count: &mut i32, count: &mut
             let expr = filter_to_args(filter, dummy_count, count, args);
             quote! { ( #expr ) }
         },
+        FilterExpression::Exists(ref subquery) => {
+            let subquery = query_to_args(&subquery.query, &subquery.calls, dummy_count, count, args);
+            quote! { Exists ( #subquery ) }
+        },
+        FilterExpression::InSubquery(ref filter) => {
+            let left = filter_value_to_args(&filter.operand1, dummy_count, count, args);
+            let method = rel_op_to_args(filter.operator);
+            let subquery = query_to_args(&filter.subquery.query, &filter.subquery.calls, dummy_count, count, args);
+            quote! {
+                #left . #method ( #subquery )
+            }
+        },
+        FilterExpression::ManyToMany(ref filter) => {
+            let field = &filter.field;
+            let expr = filter_to_args(&filter.filter, dummy_count, count, args);
+            quote! {
+                #field . any ( #expr )
+            }
+        },
     }
 }
 
-fn filter_value_to_args(filter_value: &FilterValue) -> TokenStream {
+fn filter_value_to_args(filter_value: &FilterValue, dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>) -> TokenStream {
     match *filter_value {
+        FilterValue::Arithmetic(ref operand) => operand_to_args(operand, true, dummy_count, count, args),
         FilterValue::Identifier(_, ref identifier) => {
             quote! { #identifier }
         },
-        FilterValue::MethodCall(MethodCall { ref arguments, ref method_name, ref object_name, .. }) => quote! {
+        FilterValue::MethodCall(_, MethodCall { ref arguments, ref method_name, ref object_name, .. }) => quote! {
             #object_name . #method_name ( #(#arguments),* )
         },
         FilterValue::None => unreachable!(),
         FilterValue::PrimaryKey(_) => quote! { },
+        FilterValue::RelatedIdentifier(_, ref foreign_key, ref field) => quote! {
+            #foreign_key . #field
+        },
     }
 }
 
@@ -176,9 +284,11 @@ fn log_op_to_args(operator: LogicalOperator) -> TokenStream {
 fn rel_op_to_args(operator: RelationalOperator) -> TokenStream {
     match operator {
         RelationalOperator::Equal => quote! { == },
+        RelationalOperator::In => quote! { is_in },
         RelationalOperator::LesserThan => quote! { < },
         RelationalOperator::LesserThanEqual => quote! { <= },
         RelationalOperator::NotEqual => quote! { != },
+        RelationalOperator::NotIn => quote! { not_in },
         RelationalOperator::GreaterThan => quote! { > },
         RelationalOperator::GreaterThanEqual => quote! { >= },
     }
@@ -200,7 +310,7 @@ fn assignments_to_args(assignments: &Vec<Assignment>, dummy_count: &mut i32, cou
         .map(|assignment| {
             let ident = &assignment.identifier;
             let op = assign_op_to_args(assignment.operator.node);
-            let expr = expr_to_args(&assignment.value, dummy_count, count, args);
+            let expr = operand_to_args(&assignment.value, false, dummy_count, count, args);
             quote! {
                 #ident #op #expr
             }
@@ -215,8 +325,11 @@ fn order_to_args(order: &[Order]) -> TokenStream {
         order.iter()
             .map(|order|
                  match *order {
-                     Order::Ascending(ref ident) => quote! { #ident },
-                     Order::Descending(ref ident) => quote! { - #ident },
+                     Order::Ascending(ref value, nulls) => order_value_to_args(value, nulls),
+                     Order::Descending(ref value, nulls) => {
+                         let value = order_value_to_args(value, nulls);
+                         quote! { - #value }
+                     },
                      Order::NoOrder => quote! {},
                  }
             );
@@ -225,6 +338,30 @@ fn order_to_args(order: &[Order]) -> TokenStream {
     }
 }
 
+fn order_value_to_args(value: &OrderValue, nulls: Nulls) -> TokenStream {
+    let value =
+        match *value {
+            OrderValue::Field(ref field) => field_path_to_args(field),
+            OrderValue::MethodCall(MethodCall { ref arguments, ref method_name, ref object_name, .. }) => quote! {
+                #object_name . #method_name ( #(#arguments),* )
+            },
+        };
+    match nulls {
+        Nulls::First => quote! { #value . nulls_first() },
+        Nulls::Last => quote! { #value . nulls_last() },
+        Nulls::Unspecified => value,
+    }
+}
+
+fn projection_to_args(projection: &Projection) -> TokenStream {
+    match *projection {
+        Projection::Fields(ref fields) => quote! {
+            #(#fields),*
+        },
+        Projection::All | Projection::Struct(_) => quote! {},
+    }
+}
+
 fn limit_to_args(limit: &Limit, dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>) -> TokenStream {
     match *limit {
         Limit::EndRange(ref expr) => {
@@ -257,6 +394,14 @@ fn expr_to_args(expr: &Expr, dummy_count: &mut i32, count: &mut i32, args: &mut
                 $#ident : tt
             }
         },
+        Expr::Block(_) => {
+            *count += 1;
+            let ident = Ident::new(&format!("__tql_arg{}", *count), proc_macro2::Span::call_site());
+            args.push(ident.clone());
+            quote! {
+                $#ident : block
+            }
+        },
         _ => {
             *count += 1;
             let ident = Ident::new(&format!("__tql_arg{}", *count), proc_macro2::Span::call_site());
@@ -268,13 +413,58 @@ fn expr_to_args(expr: &Expr, dummy_count: &mut i32, count: &mut i32, args: &mut
     }
 }
 
-fn joins_to_args(joins: &[Join]) -> TokenStream {
+/// Convert the operand to a macro pattern.
+/// The fields on the `left` side of a comparison are not prefixed by $.
+fn operand_to_args(operand: &Operand, left: bool, dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>) -> TokenStream {
+    match *operand {
+        Operand::Arithmetic(ref operand1, operator, ref operand2) => {
+            let left_operand = operand_to_args(operand1, left, dummy_count, count, args);
+            let right_operand = operand_to_args(operand2, left, dummy_count, count, args);
+            quote! {
+                #left_operand #operator #right_operand
+            }
+        },
+        Operand::Expression(ref expr) => expr_to_args(expr, dummy_count, count, args),
+        Operand::Field(_, ref field) if left => quote! { #field },
+        Operand::Field(_, ref field) => {
+            // NOTE: the $ sign cannot be written in a macro pattern, so it is matched by a dummy
+            // token tree.
+            *dummy_count += 1;
+            let ident = Ident::new(&format!("__tql_dummy_arg{}", *dummy_count), proc_macro2::Span::call_site());
+            quote! {
+                $#ident : tt #field
+            }
+        },
+        Operand::Function(ref function) => {
+            let name = &function.name;
+            let arguments: Vec<_> = function.arguments.iter()
+                .map(|argument| operand_to_args(argument, left, dummy_count, count, args))
+                .collect();
+            quote! { #name ( #(#arguments),* ) }
+        },
+        Operand::Paren(ref operand) => {
+            let operand = operand_to_args(operand, left, dummy_count, count, args);
+            quote! { ( #operand ) }
+        },
+    }
+}
+
+fn field_path_to_args(field: &FieldPath) -> TokenStream {
+    let foreign_key = field.foreign_key.iter();
+    let field = &field.field;
+    quote! {
+        #(#foreign_key .)* #field
+    }
+}
+
+fn joins_to_args(joins: &[Join], join_type: JoinType) -> TokenStream {
     let joins = joins.iter()
+        .filter(|join| join.join_type == join_type && !join.implicit)
         .map(|join| {
-            let base_field = &join.base_field;
+            let path = join.path();
             let base_table = Ident::new(join.base_table.as_str(), proc_macro2::Span::call_site());
             quote! {
-                #base_table.#base_field
+                #base_table.#(#path).*
             }
         });
     quote! {
@@ -282,7 +472,9 @@ fn joins_to_args(joins: &[Join]) -> TokenStream {
     }
 }
 
-fn aggregates_to_args(aggregates: &[Aggregate]) -> TokenStream {
+fn aggregates_to_args(aggregates: &[Aggregate], dummy_count: &mut i32, count: &mut i32, args: &mut Vec<Ident>)
+    -> TokenStream
+{
     let aggregates = aggregates.iter()
         .map(|aggregate| {
             let result =
@@ -294,9 +486,10 @@ fn aggregates_to_args(aggregates: &[Aggregate]) -> TokenStream {
                     quote! {}
                 };
             let function = &aggregate.function;
-            let field = &aggregate.field;
+            let argument = aggregate.argument.as_ref()
+                .map(|argument| operand_to_args(argument, true, dummy_count, count, args));
             quote! {
-                #result #function(#field)
+                #result #function(#argument)
             }
         });
     quote! {
@@ -305,6 +498,8 @@ fn aggregates_to_args(aggregates: &[Aggregate]) -> TokenStream {
 }
 
 fn values_to_args(groups: &Groups) -> TokenStream {
+    let groups = groups.iter()
+        .map(field_path_to_args);
     quote! {
         #(#groups),*
     }
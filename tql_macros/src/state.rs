@@ -26,15 +26,18 @@
 //! The aggregates global state contains the existing aggregate functions.
 //!
 //! The methods global state contains the existing aggregate functions.
+//!
+//! The functions global state contains the existing functions evaluated by the database.
 
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::mem;
+use std::ptr;
 
 use syn::{self, Ident};
 
 use ast::WithSpan;
-use methods::{add_initial_aggregates, add_initial_methods};
+use methods::{add_initial_aggregates, add_initial_functions, add_initial_methods};
 use types::Type;
 
 /// A collection of tql aggregate functions.
@@ -49,12 +52,27 @@ pub struct BothTypes {
 /// A collection of fields from an `SqlTable`.
 pub type SqlFields = BTreeMap<Ident, BothTypes>;
 
+/// A collection mapping function names to functions.
+pub type SqlFunctions = HashMap<String, SqlFunctionTypes>;
+
+/// Tql function argument types and template.
+pub struct SqlFunctionTypes {
+    pub argument_types: Vec<Type>,
+    /// Whether the function creates an interval, which can only be added to or subtracted from a date.
+    pub interval: bool,
+    pub template: String,
+}
+
 /// A collection mapping method names to methods.
 pub type SqlMethods = HashMap<String, SqlMethodTypes>;
 
 /// Tql method return type, argument types and template.
 pub struct SqlMethodTypes {
+    /// The template used instead of `template` when the method is called on an array field.
+    pub array_template: Option<String>,
     pub argument_types: Vec<Type>,
+    /// Whether the method can only be called on a field declared with #[sql(fulltext)].
+    pub fulltext: bool,
     pub object_type: Type,
     pub return_type: Type,
     pub template: Option<String>,
@@ -75,6 +93,21 @@ pub fn aggregates_singleton() -> &'static mut SqlAggregates {
     }
 }
 
+/// Returns the global functions state.
+pub fn functions_singleton() -> &'static mut SqlFunctions {
+    // FIXME: make this thread safe.
+    static mut HASH_MAP: *mut SqlFunctions = ptr::null_mut();
+
+    let map: SqlFunctions = HashMap::new();
+    unsafe {
+        if HASH_MAP.is_null() {
+            HASH_MAP = Box::into_raw(Box::new(map));
+            add_initial_functions();
+        }
+        &mut *HASH_MAP
+    }
+}
+
 /// Returns the global methods state.
 pub fn methods_singleton() -> &'static mut SqlMethods {
     // FIXME: make this thread safe.
@@ -42,6 +42,8 @@ use sql;
 /// A field type.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Type {
+    #[cfg(feature = "postgres")]
+    Array(Box<Type>),
     Bool,
     ByteString,
     Char,
@@ -53,6 +55,7 @@ pub enum Type {
     I16,
     I32,
     I64,
+    Json,
     LocalDateTime,
     NaiveDate,
     NaiveDateTime,
@@ -68,6 +71,8 @@ impl Display for Type {
     /// Get a string representation of the SQL `Type` for display in error messages.
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let typ = match *self {
+            #[cfg(feature = "postgres")]
+            Type::Array(ref typ) => "Vec<".to_string() + &typ.to_string() + ">",
             Type::Bool => "bool".to_string(),
             Type::ByteString => "Vec<u8>".to_string(),
             Type::Char => "char".to_string(),
@@ -79,6 +84,7 @@ impl Display for Type {
             Type::I16 => "i16".to_string(),
             Type::I32 => "i32".to_string(),
             Type::I64 => "i64".to_string(),
+            Type::Json => "tql::Json".to_string(),
             Type::LocalDateTime => "chrono::datetime::DateTime<chrono::offset::Local>".to_string(),
             Type::NaiveDate => "chrono::naive::NaiveDate".to_string(),
             Type::NaiveDateTime => "chrono::naive::NaiveDateTime".to_string(),
@@ -166,10 +172,15 @@ impl<'a> From<&'a Path> for Type {
                 "i16" => Type::I16,
                 "i32" => Type::I32,
                 "i64" => Type::I64,
+                "Json" => Type::Json,
                 "ForeignKey" => match get_type_parameter(&first_segment.arguments) {
                     Some(ty) => Type::Custom(ty),
                     None => Type::UnsupportedType("ForeignKey".to_string()),
                 },
+                "NullableForeignKey" => match get_type_parameter(&first_segment.arguments) {
+                    Some(ty) => Type::Nullable(Box::new(Type::Custom(ty))),
+                    None => Type::UnsupportedType("NullableForeignKey".to_string()),
+                },
                 "NaiveDate" => Type::NaiveDate,
                 "NaiveDateTime" => Type::NaiveDateTime,
                 "NaiveTime" => Type::NaiveTime,
@@ -194,10 +205,15 @@ impl<'a> From<&'a Path> for Type {
                 "String" => {
                     Type::String
                 },
-                "Vec" => match get_type_parameter(&first_segment.arguments) {
-                    Some(ty) => match ty.as_ref() {
-                        "u8" => Type::ByteString,
-                        parameter_type => Type::UnsupportedType("Vec<".to_string() + parameter_type + ">"),
+                "Vec" => match get_type_parameter_as_path(&first_segment.arguments) {
+                    Some(ty) => {
+                        let element = ty.segments.first().expect("first segment in path").value().ident.to_string();
+                        if element == "u8" {
+                            Type::ByteString
+                        }
+                        else {
+                            array_type(Type::from(ty), &element)
+                        }
                     },
                     None => Type::UnsupportedType("Vec".to_string()),
                 },
@@ -210,6 +226,22 @@ impl<'a> From<&'a Path> for Type {
     }
 }
 
+/// Get the type of an array of `element_type`, which can only be stored by PostgreSQL.
+/// Only the arrays of the types having a single column can be stored.
+#[cfg(feature = "postgres")]
+fn array_type(element_type: Type, element: &str) -> Type {
+    match element_type {
+        Type::Array(_) | Type::ByteString | Type::Custom(_) | Type::Generic | Type::Json | Type::Nullable(_) |
+            Type::Serial | Type::UnsupportedType(_) => Type::UnsupportedType("Vec<".to_string() + element + ">"),
+        element_type => Type::Array(Box::new(element_type)),
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+fn array_type(_element_type: Type, element: &str) -> Type {
+    Type::UnsupportedType("Vec<".to_string() + element + ">")
+}
+
 /// Get the type between < and > as a String.
 pub fn get_type_parameter(parameters: &PathArguments) -> Option<String> {
     get_type_parameter_as_path(parameters).map(|path| path.segments.first()